@@ -0,0 +1,115 @@
+use webby_addon_common::WrappingResponse;
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use hyper::StatusCode;
+
+use crate::AppState;
+
+/// Responds with 401 unless the request carries the configured `BOOKING_ADMIN_TOKEN` as its
+/// bearer token. Every request is refused while the token isn't set.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let authorized = match (&state.config.admin_token, token) {
+        // Every byte is compared so the time taken doesn't tell how much of the token matched.
+        (Some(expected), Some(token)) => {
+            expected.len() == token.len()
+                && expected
+                    .bytes()
+                    .zip(token.bytes())
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+        }
+        _ => false,
+    };
+
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(WrappingResponse::<()>::error(String::from(
+                "Admin token required",
+            ))),
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn state(admin_token: Option<&str>) -> AppState {
+        let config = Config::from_lookup(|key| match key {
+            "BOOKING_ADMIN_TOKEN" => admin_token.map(String::from),
+            _ => None,
+        })
+        .unwrap();
+
+        AppState::new(config).unwrap()
+    }
+
+    async fn status(state: &AppState, authorization: Option<&str>) -> StatusCode {
+        let router = Router::new()
+            .route("/", get(|| async {}))
+            .route_layer(middleware::from_fn_with_state(state.clone(), require_admin))
+            .with_state(state.clone());
+
+        let mut request = Request::builder();
+
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn only_the_admin_token_is_let_through() {
+        let state = state(Some("admin"));
+
+        assert_eq!(status(&state, Some("Bearer admin")).await, StatusCode::OK);
+        assert_eq!(
+            status(&state, Some("Bearer other")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&state, Some("admin")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(&state, None).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn everything_is_refused_without_a_token_set() {
+        let state = state(None);
+
+        assert_eq!(
+            status(&state, Some("Bearer ")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(&state, None).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use time::Date;
+use tokio::sync::Mutex;
+use webby_global_common::uuid::UuidType;
+
+/// Website, staff schedule id, day listed and whether raw offsets were requested.
+type CacheKey = (UuidType, String, Date, bool);
+
+/// Website, service id and first day of the month counted.
+type CountsKey = (UuidType, String, Date);
+
+/// Computed availability responses, reused for `ttl`. A `ttl` of `0` disables the cache.
+pub struct AvailabilityCache {
+    ttl: Duration,
+    /// Available hours responses and when they were computed.
+    available_hours: Mutex<HashMap<CacheKey, (Instant, serde_json::Value)>>,
+    /// Availability counts responses and when they were computed.
+    availability_counts: Mutex<HashMap<CountsKey, (Instant, serde_json::Value)>>,
+}
+
+impl AvailabilityCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            available_hours: Mutex::new(HashMap::new()),
+            availability_counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(
+        &self,
+        uuid: UuidType,
+        staff_schedule_id: &str,
+        date: Date,
+        raw_offsets: bool,
+    ) -> Option<serde_json::Value> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+
+        let mut cache = self.available_hours.lock().await;
+
+        cache.retain(|_, (computed, _)| computed.elapsed() < self.ttl);
+
+        cache
+            .get(&(uuid, staff_schedule_id.to_string(), date, raw_offsets))
+            .map(|(_, value)| value.clone())
+    }
+
+    pub async fn insert(
+        &self,
+        uuid: UuidType,
+        staff_schedule_id: String,
+        date: Date,
+        raw_offsets: bool,
+        value: serde_json::Value,
+    ) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        self.available_hours.lock().await.insert(
+            (uuid, staff_schedule_id, date, raw_offsets),
+            (Instant::now(), value),
+        );
+    }
+
+    pub async fn get_counts(
+        &self,
+        uuid: UuidType,
+        service_id: &str,
+        month_start: Date,
+    ) -> Option<serde_json::Value> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+
+        let mut cache = self.availability_counts.lock().await;
+
+        cache.retain(|_, (computed, _)| computed.elapsed() < self.ttl);
+
+        cache
+            .get(&(uuid, service_id.to_string(), month_start))
+            .map(|(_, value)| value.clone())
+    }
+
+    pub async fn insert_counts(
+        &self,
+        uuid: UuidType,
+        service_id: String,
+        month_start: Date,
+        value: serde_json::Value,
+    ) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        self.availability_counts
+            .lock()
+            .await
+            .insert((uuid, service_id, month_start), (Instant::now(), value));
+    }
+
+    /// Drops everything cached for the website.
+    ///
+    /// Called whenever a booking, block or schedule changes. Bookings are shared by every
+    /// schedule of the website so the whole website is cleared rather than a single day.
+    pub async fn invalidate(&self, uuid: UuidType) {
+        self.available_hours
+            .lock()
+            .await
+            .retain(|(cached_uuid, ..), _| *cached_uuid != uuid);
+
+        self.availability_counts
+            .lock()
+            .await
+            .retain(|(cached_uuid, ..), _| *cached_uuid != uuid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use super::*;
+
+    const FIRST: &str = "01938f4f-f50c-7203-9f89-b367e9d49efb";
+    const SECOND: &str = "01938f4f-f50c-7203-9f89-b367e9d49efc";
+
+    fn website(uuid: &str) -> UuidType {
+        serde_json::from_value(serde_json::json!(uuid)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_response_is_reused_for_the_same_request() {
+        let cache = AvailabilityCache::new(Duration::from_secs(60));
+
+        cache
+            .insert(
+                website(FIRST),
+                String::from("a"),
+                date!(2025 - 03 - 14),
+                false,
+                serde_json::json!([1]),
+            )
+            .await;
+
+        assert_eq!(
+            cache
+                .get(website(FIRST), "a", date!(2025 - 03 - 14), false)
+                .await,
+            Some(serde_json::json!([1]))
+        );
+        // Raw offsets are a different response.
+        assert_eq!(
+            cache
+                .get(website(FIRST), "a", date!(2025 - 03 - 14), true)
+                .await,
+            None
+        );
+        assert_eq!(
+            cache
+                .get(website(FIRST), "a", date!(2025 - 03 - 15), false)
+                .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_are_cached_per_service_and_month_until_invalidated() {
+        let cache = AvailabilityCache::new(Duration::from_secs(60));
+
+        cache
+            .insert_counts(
+                website(FIRST),
+                String::from("haircut"),
+                date!(2025 - 03 - 01),
+                serde_json::json!({ "days": [] }),
+            )
+            .await;
+
+        assert!(cache
+            .get_counts(website(FIRST), "haircut", date!(2025 - 03 - 01))
+            .await
+            .is_some());
+        assert!(cache
+            .get_counts(website(FIRST), "haircut", date!(2025 - 04 - 01))
+            .await
+            .is_none());
+        assert!(cache
+            .get_counts(website(FIRST), "beard", date!(2025 - 03 - 01))
+            .await
+            .is_none());
+
+        // A booking changes the counts as well as the hours.
+        cache.invalidate(website(FIRST)).await;
+
+        assert!(cache
+            .get_counts(website(FIRST), "haircut", date!(2025 - 03 - 01))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn a_zero_ttl_caches_nothing() {
+        let cache = AvailabilityCache::new(Duration::ZERO);
+
+        cache
+            .insert(
+                website(FIRST),
+                String::from("a"),
+                date!(2025 - 03 - 14),
+                false,
+                serde_json::json!([1]),
+            )
+            .await;
+
+        assert_eq!(
+            cache
+                .get(website(FIRST), "a", date!(2025 - 03 - 14), false)
+                .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn invalidating_only_clears_the_website() {
+        let cache = AvailabilityCache::new(Duration::from_secs(60));
+
+        for uuid in [FIRST, SECOND] {
+            cache
+                .insert(
+                    website(uuid),
+                    String::from("a"),
+                    date!(2025 - 03 - 14),
+                    false,
+                    serde_json::json!([1]),
+                )
+                .await;
+        }
+
+        cache.invalidate(website(FIRST)).await;
+
+        assert_eq!(
+            cache
+                .get(website(FIRST), "a", date!(2025 - 03 - 14), false)
+                .await,
+            None
+        );
+        assert!(cache
+            .get(website(SECOND), "a", date!(2025 - 03 - 14), false)
+            .await
+            .is_some());
+    }
+}
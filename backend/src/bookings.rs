@@ -0,0 +1,2217 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::Arc,
+};
+
+use webby_addon_common::{
+    request::{get_cms_row_by_id, import_data_row, query_cms_rows, update_cms_row_by_id},
+    JsonResponse, WrappingResponse,
+};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use eyre::ContextCompat;
+use futures_util::{stream, StreamExt};
+use webby_global_common::{
+    filter::{Filter, FilterConditionType, FilterValue},
+    request::CmsQuery,
+    response::CmsRowResponse,
+    schema::SchematicFieldKey,
+    uuid::{CollectionName, UuidType},
+    value::SimpleValue,
+};
+use time::{
+    format_description::well_known::Iso8601, macros::format_description, Date, Duration,
+    OffsetDateTime, PrimitiveDateTime, Time, UtcOffset,
+};
+use uuid::Uuid;
+
+use crate::{
+    apply_staff_time_zone, ensure_bookable, google_calendar, AppState, book_date_value,
+    calendar_date, book_id_value, booking_local_offset, booking_start, find_booking_by_reference,
+    legacy_book_date_offsets, metrics, page_bounds, resolve_booking_start, retry, row_id,
+    row_number, row_text, service_currency, service_payment, slot::SlotId, slot_lock::LockKey,
+    staff_time_zones, template, unique_booking_reference, NewBooking, validate_slot_free,
+    validate_slot_links, validate_time_zone, webhook, Error, ErrorCode, Result,
+    BOOKING_SOURCE_ADMIN_BLOCK, BOOKING_SOURCE_WIDGET, BOOKING_STATUSES, BOOKING_STATUS_CONFIRMED,
+    BOOKING_TYPE_BLOCK, BOOKING_TYPE_BOOKING, BOOKING_TYPE_VOID,
+};
+
+#[derive(serde::Deserialize)]
+pub struct StaffBookingsQuery {
+    /// First day to include (YYYY-MM-DD)
+    from: String,
+    /// Last day to include (YYYY-MM-DD)
+    to: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+pub async fn get_staff_bookings(
+    State(state): State<AppState>,
+    Path((uuid, staff_id)): Path<(UuidType, String)>,
+    Query(query): Query<StaffBookingsQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let date_format = format_description!("[year]-[month]-[day]");
+
+    let from =
+        Date::parse(&query.from, &date_format).map_err(|e| Error::from(e).into_validation())?;
+    let to = Date::parse(&query.to, &date_format).map_err(|e| Error::from(e).into_validation())?;
+
+    if from > to {
+        return Err(Error::Validation(vec![format!(
+            "From date {from} must not be after to date {to}"
+        )]));
+    }
+
+    let (offset, limit) = page_bounds(query.offset, query.limit);
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(vec![
+                    Filter {
+                        name: String::from("staffMember"),
+                        cond: FilterConditionType::Eq,
+                        value: FilterValue::Text(staff_id.clone()),
+                    },
+                    Filter {
+                        name: String::from("bookDate"),
+                        cond: FilterConditionType::Gte,
+                        value: FilterValue::Text(format!("{from} 00:00:00.0 +00:00:00")),
+                    },
+                    Filter {
+                        name: String::from("bookDate"),
+                        cond: FilterConditionType::Lte,
+                        value: FilterValue::Text(format!("{to} 23:59:59.0 +00:00:00")),
+                    },
+                ]),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let services = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let service_names = services
+        .items
+        .iter()
+        .map(|row| Ok((row_id(row)?, row_text(row, "name")?)))
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let service_cutoffs = services
+        .items
+        .iter()
+        .map(|row| Ok((row_id(row)?, row_number(row, "cancelCutoffHours")?)))
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let offsets = legacy_book_date_offsets(&state, uuid, &bookings.items).await?;
+
+    let mut items = bookings
+        .items
+        .iter()
+        .map(|row| {
+            Ok((
+                booking_start(row, booking_local_offset(row, &offsets)?)?,
+                row,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    items.sort_by_key(|(start, _)| *start);
+
+    let total = items.len();
+
+    let items = items
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(start, row)| {
+            let service_id = row_text(row, "service")?;
+
+            booking_json(
+                row,
+                start,
+                service_id
+                    .as_ref()
+                    .and_then(|id| service_names.get(id))
+                    .cloned()
+                    .flatten(),
+                service_id
+                    .as_ref()
+                    .and_then(|id| service_cutoffs.get(id))
+                    .copied()
+                    .flatten(),
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "total": total,
+        "offset": offset,
+        "limit": limit,
+        "items": items,
+    }))))
+}
+
+/// A booking as returned by the API, with the name & cancellation cutoff of its service.
+fn booking_json(
+    row: &CmsRowResponse,
+    start: OffsetDateTime,
+    service_name: Option<String>,
+    cancel_cutoff_hours: Option<f64>,
+) -> Result<serde_json::Value> {
+    Ok(serde_json::json!({
+        "id": row_id(row)?,
+        "bookID": row_text(row, "bookID")?,
+        "reference": row_text(row, "reference")?,
+        "type": row_text(row, "type")?,
+        "status": row_text(row, "status")?
+            .unwrap_or_else(|| String::from(BOOKING_STATUS_CONFIRMED)),
+        "bookDate": start.format(&Iso8601::DEFAULT)?,
+        "duration": row_number(row, "duration")?,
+        "currency": row_text(row, "currency")?,
+        "depositAmount": row_number(row, "depositAmount")?,
+        "balanceDue": row_number(row, "balanceDue")?,
+        "serviceId": row_text(row, "service")?,
+        "serviceName": service_name,
+        "cancelCutoffHours": cancel_cutoff_hours,
+        "staffId": row_text(row, "staffMember")?,
+        "contactUuid": row_text(row, "contactUuid")?,
+        "schemaDataUuid": row_text(row, "schemaDataUuid")?,
+        "submissionLink": row_text(row, "submissionLink")?,
+        "notes": row_text(row, "notes")?,
+        "createdAt": row_text(row, "createdAt")?,
+        "source": row_text(row, "source")?,
+    }))
+}
+
+/// A single booking, looked up by its id or the reference given to the customer.
+pub async fn get_booking(
+    State(state): State<AppState>,
+    Path((uuid, id)): Path<(UuidType, String)>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let booking = match find_booking_by_reference(&state, uuid, &id).await? {
+        Some(v) => v,
+        None => {
+            retry::read(&state.config, || {
+                get_cms_row_by_id(
+                    uuid,
+                    CollectionName {
+                        id: String::from("bookings"),
+                        ns: Some(String::from("@booking")),
+                    },
+                    &id,
+                )
+            })
+            .await?
+        }
+    };
+
+    // A removed service only leaves out its name & cutoff.
+    let service = match row_text(&booking, "service")? {
+        Some(service_id) => retry::read(&state.config, || {
+            get_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
+                },
+                &service_id,
+            )
+        })
+        .await
+        .inspect_err(|e| warn!("Unable to find service {service_id}: {e}"))
+        .ok(),
+        None => None,
+    };
+
+    Ok(Json(WrappingResponse::okay(booking_json(
+        &booking,
+        resolve_booking_start(&state, uuid, &booking).await?,
+        service
+            .as_ref()
+            .map(|v| row_text(v, "name"))
+            .transpose()?
+            .flatten(),
+        service
+            .as_ref()
+            .map(|v| row_number(v, "cancelCutoffHours"))
+            .transpose()?
+            .flatten(),
+    )?)))
+}
+
+/// Bookings fetched from the CMS for each chunk of an export.
+const EXPORT_PAGE_SIZE: usize = 100;
+
+const EXPORT_HEADER: &str = "date,service,staff,contact,duration,notes\n";
+
+#[derive(serde::Deserialize)]
+pub struct BookingsExportQuery {
+    /// First day to include (YYYY-MM-DD)
+    from: String,
+    /// Last day to include (YYYY-MM-DD)
+    to: String,
+}
+
+/// Every booking in the date range as a CSV download.
+///
+/// Bookings are fetched and written a page at a time rather than buffering the whole range.
+pub async fn get_bookings_export(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(query): Query<BookingsExportQuery>,
+) -> Result<Response> {
+    let date_format = format_description!("[year]-[month]-[day]");
+
+    let from =
+        Date::parse(&query.from, &date_format).map_err(|e| Error::from(e).into_validation())?;
+    let to = Date::parse(&query.to, &date_format).map_err(|e| Error::from(e).into_validation())?;
+
+    if from > to {
+        return Err(Error::Validation(vec![format!(
+            "From date {from} must not be after to date {to}"
+        )]));
+    }
+
+    let service_names = Arc::new(row_names(&state, uuid, "services", "name").await?);
+    let staff_names = Arc::new(row_names(&state, uuid, "staff", "staffName").await?);
+
+    let filters = vec![
+        Filter {
+            name: String::from("bookDate"),
+            cond: FilterConditionType::Gte,
+            value: FilterValue::Text(format!("{from} 00:00:00.0 +00:00:00")),
+        },
+        Filter {
+            name: String::from("bookDate"),
+            cond: FilterConditionType::Lte,
+            value: FilterValue::Text(format!("{to} 23:59:59.0 +00:00:00")),
+        },
+    ];
+
+    // The offset of the next page, `None` once the last page was written.
+    let rows = stream::try_unfold(Some(0), move |offset| {
+        let state = state.clone();
+        let filters = filters.clone();
+        let service_names = service_names.clone();
+        let staff_names = staff_names.clone();
+
+        async move {
+            let Some(offset) = offset else {
+                return Ok(None);
+            };
+
+            let page = retry::read(&state.config, || {
+                query_cms_rows(
+                    uuid,
+                    CollectionName {
+                        id: String::from("bookings"),
+                        ns: Some(String::from("@booking")),
+                    },
+                    CmsQuery {
+                        filters: Some(filters.clone()),
+                        limit: Some(EXPORT_PAGE_SIZE),
+                        offset: Some(offset),
+                        ..CmsQuery::default()
+                    },
+                )
+            })
+            .await?;
+
+            let offsets = legacy_book_date_offsets(&state, uuid, &page.items).await?;
+
+            let mut chunk = String::new();
+
+            for row in &page.items {
+                let name = |names: &HashMap<String, Option<String>>, field| -> Result<String> {
+                    Ok(row_text(row, field)?
+                        .and_then(|id| names.get(&id).cloned().flatten())
+                        .unwrap_or_default())
+                };
+
+                let values = [
+                    booking_start(row, booking_local_offset(row, &offsets)?)?
+                        .format(&Iso8601::DEFAULT)?,
+                    name(&service_names, "service")?,
+                    name(&staff_names, "staffMember")?,
+                    row_text(row, "contactUuid")?.unwrap_or_default(),
+                    row_number(row, "duration")?
+                        .map(|v| v.to_string())
+                        .unwrap_or_default(),
+                    row_text(row, "notes")?.unwrap_or_default(),
+                ];
+
+                chunk.push_str(
+                    &values
+                        .iter()
+                        .map(|v| csv_field(v))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                );
+                chunk.push('\n');
+            }
+
+            let next = (page.items.len() == EXPORT_PAGE_SIZE).then_some(offset + EXPORT_PAGE_SIZE);
+
+            Ok::<_, Error>(Some((chunk, next)))
+        }
+    });
+
+    Ok(export_response(from, to, rows))
+}
+
+/// Streams the CSV `rows` after its header as a download of the bookings from `from` to `to`.
+fn export_response(
+    from: Date,
+    to: Date,
+    rows: impl futures_util::Stream<Item = Result<String>> + Send + 'static,
+) -> Response {
+    let body = stream::once(async { Ok::<_, Error>(String::from(EXPORT_HEADER)) }).chain(rows);
+
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                String::from("text/csv; charset=utf-8"),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"bookings-{from}-{to}.csv\""),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+/// The name of every row in a collection, by row id.
+async fn row_names(
+    state: &AppState,
+    uuid: UuidType,
+    collection: &str,
+    field: &str,
+) -> Result<HashMap<String, Option<String>>> {
+    let rows = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: collection.to_string(),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    rows.items
+        .iter()
+        .map(|row| Ok((row_id(row)?, row_text(row, field)?)))
+        .collect()
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockJson {
+    schedule_id: String,
+    staff_id: String,
+
+    day: u8,
+    month: u8,
+    year: usize,
+    /// Local start time (HH:MM:SS)
+    time: String,
+}
+
+/// Reserves a slot for the staff member without a customer booking.
+///
+/// Stored as a booking with the `block` type so availability treats the slot as occupied.
+pub async fn post_block(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Json(BlockJson {
+        schedule_id,
+        staff_id,
+        day,
+        month,
+        year,
+        time,
+    }): Json<BlockJson>,
+) -> Result<()> {
+    let time = Time::parse(&time, &format_description!("[hour]:[minute]:[second]"))
+        .map_err(|e| Error::from(e).into_validation())?;
+    let book_time = calendar_date(year, month, day)?.with_time(time);
+
+    let schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &schedule_id,
+        )
+    })
+    .await?;
+
+    let service_id = row_text(&schedule, "service")?.context("Service ID")?;
+
+    // The block is given in the staff member's local time for this schedule.
+    let staff_schedules = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(vec![
+                    Filter {
+                        name: String::from("schedule"),
+                        cond: FilterConditionType::Eq,
+                        value: FilterValue::Text(schedule_id.clone()),
+                    },
+                    Filter {
+                        name: String::from("staff"),
+                        cond: FilterConditionType::Eq,
+                        value: FilterValue::Text(staff_id.clone()),
+                    },
+                ]),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let mut staff_schedule = staff_schedules
+        .items
+        .into_iter()
+        .next()
+        .context("Staff member has no schedule for this schedule")?;
+
+    apply_staff_time_zone(&mut staff_schedule, &staff_time_zones(&state, uuid).await?)?;
+
+    let time_zone_str = row_text(&staff_schedule, "timeZone")?.context("Missing TimeZone")?;
+
+    let book_start = book_time.assume_offset(validate_time_zone(&time_zone_str)?);
+
+    let duration = schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("duration")))
+        .context("Service Duration")?
+        .try_as_number()?;
+
+    let fields = block_fields(book_start, duration.into(), service_id, staff_id)?;
+
+    retry::write(&state.config, || {
+        import_data_row(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(())
+}
+
+/// The booking row of a block of the service's slot at `book_start`.
+fn block_fields(
+    book_start: OffsetDateTime,
+    duration: SimpleValue,
+    service_id: String,
+    staff_id: String,
+) -> Result<HashMap<String, SimpleValue>> {
+    Ok(HashMap::from([
+        (
+            String::from("bookDate"),
+            book_date_value(book_start)?.into(),
+        ),
+        (String::from("bookID"), book_id_value(book_start).into()),
+        (String::from("type"), BOOKING_TYPE_BLOCK.into()),
+        (String::from("status"), BOOKING_STATUS_CONFIRMED.into()),
+        (
+            String::from("createdAt"),
+            book_date_value(OffsetDateTime::now_utc())?.into(),
+        ),
+        (String::from("source"), BOOKING_SOURCE_ADMIN_BLOCK.into()),
+        (String::from("duration"), duration),
+        (String::from("service"), service_id.into()),
+        (String::from("staffMember"), staff_id.into()),
+    ]))
+}
+
+/// Most days a single block range can cover.
+const MAX_BLOCK_RANGE_DAYS: i64 = 62;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockRangeJson {
+    staff_id: String,
+    /// First day to block (YYYY-MM-DD)
+    from: String,
+    /// Last day to block (YYYY-MM-DD)
+    to: String,
+    /// Local start time blocked on each day (HH:MM:SS). Defaults to the start of the day.
+    start: Option<String>,
+    /// Local end time blocked on each day (HH:MM:SS). Defaults to the end of the day.
+    end: Option<String>,
+}
+
+/// Blocks every day in a range for a staff member, eg. while they're on vacation.
+///
+/// Writes one block per day covering the time window, in the staff member's time zone.
+/// The blocks share a `batchId`, returned as `rangeId`, to lift them together.
+pub async fn post_block_range(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Json(body): Json<BlockRangeJson>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let date_format = format_description!("[year]-[month]-[day]");
+    let time_format = format_description!("[hour]:[minute]:[second]");
+
+    let from =
+        Date::parse(&body.from, &date_format).map_err(|e| Error::from(e).into_validation())?;
+    let to = Date::parse(&body.to, &date_format).map_err(|e| Error::from(e).into_validation())?;
+
+    if from > to {
+        return Err(Error::Validation(vec![format!(
+            "From date {from} must not be after to date {to}"
+        )]));
+    }
+
+    let days = (to - from).whole_days() + 1;
+
+    if days > MAX_BLOCK_RANGE_DAYS {
+        return Err(Error::Validation(vec![format!(
+            "Can't block more than {MAX_BLOCK_RANGE_DAYS} days at once"
+        )]));
+    }
+
+    let start = body
+        .start
+        .as_deref()
+        .map(|v| Time::parse(v, &time_format))
+        .transpose()
+        .map_err(|e| Error::from(e).into_validation())?
+        .unwrap_or(Time::MIDNIGHT);
+
+    let end = body
+        .end
+        .as_deref()
+        .map(|v| Time::parse(v, &time_format))
+        .transpose()
+        .map_err(|e| Error::from(e).into_validation())?;
+
+    // Up to the end of the day when no end is given.
+    let window = match end {
+        Some(end) if end <= start => {
+            return Err(Error::Validation(vec![format!(
+                "Start time {start} must be before end time {end}"
+            )]));
+        }
+        Some(end) => end - start,
+        None => Duration::DAY - (start - Time::MIDNIGHT),
+    };
+
+    let time_zone_str = staff_booking_time_zone(&state, uuid, &body.staff_id)
+        .await?
+        .with_context(|| format!("Staff {} has no time zone", body.staff_id))?;
+
+    let local_offset = validate_time_zone(&time_zone_str)?;
+
+    let range_id = Uuid::now_v7().to_string();
+
+    let rows = block_range_rows(
+        &range_id,
+        &body.staff_id,
+        (from, days),
+        (start, window),
+        local_offset,
+    )?;
+
+    // The written days stay blocked until they're lifted.
+    let written = write_all_or_undo(
+        &rows,
+        |fields| write_booking_row(&state, uuid, fields),
+        || async { lift_block_range(&state, uuid, &range_id).await.map(|_| ()) },
+    )
+    .await;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    written?;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "rangeId": range_id,
+        "created": days,
+    }))))
+}
+
+/// The block of each of the `days` from `from`, covering `window` from the local `start`.
+fn block_range_rows(
+    range_id: &str,
+    staff_id: &str,
+    (from, days): (Date, i64),
+    (start, window): (Time, Duration),
+    local_offset: UtcOffset,
+) -> Result<Vec<HashMap<String, SimpleValue>>> {
+    (0..days)
+        .filter_map(|v| from.checked_add(Duration::days(v)))
+        .map(|day| {
+            let block_start = day.with_time(start).assume_offset(local_offset);
+
+            Ok(HashMap::from([
+                (
+                    String::from("bookDate"),
+                    book_date_value(block_start)?.into(),
+                ),
+                (String::from("bookID"), book_id_value(block_start).into()),
+                (String::from("type"), BOOKING_TYPE_BLOCK.into()),
+                (String::from("status"), BOOKING_STATUS_CONFIRMED.into()),
+                (
+                    String::from("createdAt"),
+                    book_date_value(OffsetDateTime::now_utc())?.into(),
+                ),
+                (String::from("source"), BOOKING_SOURCE_ADMIN_BLOCK.into()),
+                (String::from("batchId"), range_id.into()),
+                (
+                    String::from("duration"),
+                    serde_json::json!(window.whole_minutes()).into(),
+                ),
+                (String::from("staffMember"), staff_id.into()),
+            ]))
+        })
+        .collect()
+}
+
+/// Lifts every block of a range created by `post_block_range`.
+///
+/// The blocks are cancelled rather than deleted, which frees their slots.
+pub async fn delete_block_range(
+    State(state): State<AppState>,
+    Path((uuid, range_id)): Path<(UuidType, String)>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let lifted = lift_block_range(&state, uuid, &range_id).await?;
+
+    if lifted == 0 {
+        return Err(Error::coded(
+            ErrorCode::BlockRangeNotFound,
+            format!("Block range {range_id} not found"),
+        ));
+    }
+
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "lifted": lifted,
+    }))))
+}
+
+/// Cancels the blocks of a range. Returns how many there were.
+async fn lift_block_range(state: &AppState, uuid: UuidType, range_id: &str) -> Result<usize> {
+    let blocks = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(vec![Filter {
+                    name: String::from("batchId"),
+                    cond: FilterConditionType::Eq,
+                    value: FilterValue::Text(range_id.to_string()),
+                }]),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let mut lifted = 0;
+
+    for row in &blocks.items {
+        if row_text(row, "type")?.as_deref() != Some(BOOKING_TYPE_BLOCK) {
+            continue;
+        }
+
+        let id = row_id(row)?;
+
+        retry::write(&state.config, || {
+            update_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                &id,
+                lifted_block_fields(),
+            )
+        })
+        .await?;
+
+        lifted += 1;
+    }
+
+    Ok(lifted)
+}
+
+/// The fields a block is updated with to lift it.
+fn lifted_block_fields() -> HashMap<String, SimpleValue> {
+    HashMap::from([(String::from("status"), "cancelled".into())])
+}
+
+#[derive(serde::Deserialize)]
+pub struct BookingStatusJson {
+    /// One of `confirmed`, `cancelled`, `completed` or `no_show`.
+    status: String,
+    /// Why the booking was cancelled. Passed on to the cancellation webhook.
+    reason: Option<String>,
+}
+
+/// The booking's current status if it can move to `status`, or `None` if it's already in it.
+fn status_transition(id: &str, booking: &CmsRowResponse, status: &str) -> Result<Option<String>> {
+    let current =
+        row_text(booking, "status")?.unwrap_or_else(|| String::from(BOOKING_STATUS_CONFIRMED));
+
+    if current == status {
+        return Ok(None);
+    }
+
+    let is_allowed = match current.as_str() {
+        BOOKING_STATUS_CONFIRMED => true,
+        // Fixes marking an attended booking as a no-show or the other way around.
+        "completed" | "no_show" => status != BOOKING_STATUS_CONFIRMED && status != "cancelled",
+        _ => false,
+    };
+
+    if !is_allowed || row_text(booking, "type")?.as_deref() == Some(BOOKING_TYPE_VOID) {
+        return Err(Error::coded(
+            ErrorCode::InvalidStatusTransition,
+            format!("Booking {id} can't move from {current} to {status}"),
+        ));
+    }
+
+    Ok(Some(current))
+}
+
+/// Moves a booking to another status. Bookings are kept rather than deleted for their history.
+///
+/// Only confirmed bookings occupy their slot, so cancelling one frees it.
+/// Cancelled bookings can't be changed again as their slot may have been rebooked since.
+pub async fn put_booking_status(
+    State(state): State<AppState>,
+    Path((uuid, id)): Path<(UuidType, String)>,
+    Json(BookingStatusJson { status, reason }): Json<BookingStatusJson>,
+) -> Result<()> {
+    if !BOOKING_STATUSES.contains(&status.as_str()) {
+        return Err(Error::Validation(vec![format!(
+            "Invalid status {status}. Expected one of {}",
+            BOOKING_STATUSES.join(", ")
+        )]));
+    }
+
+    let booking = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            &id,
+        )
+    })
+    .await?;
+
+    let Some(current) = status_transition(&id, &booking, &status)? else {
+        return Ok(());
+    };
+
+    if status == "cancelled" {
+        ensure_cancellable(&state, uuid, &booking).await?;
+    }
+
+    let fields = HashMap::from([(String::from("status"), status.clone().into())]);
+
+    retry::write(&state.config, || {
+        update_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            &id,
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    if status == "cancelled" {
+        webhook::send(
+            &state.config,
+            webhook::EVENT_BOOKING_CANCELLED,
+            cancellation_data(
+                uuid,
+                &booking,
+                resolve_booking_start(&state, uuid, &booking).await?,
+                &current,
+                reason,
+            )?,
+        );
+    }
+
+    Ok(())
+}
+
+/// The `booking.cancelled` webhook's data, with the booking as it is once cancelled.
+fn cancellation_data(
+    uuid: UuidType,
+    booking: &CmsRowResponse,
+    start: OffsetDateTime,
+    current: &str,
+    reason: Option<String>,
+) -> Result<serde_json::Value> {
+    let mut details = booking_json(booking, start, None, None)?;
+
+    details["status"] = serde_json::json!("cancelled");
+
+    Ok(serde_json::json!({
+        "uuid": uuid,
+        "booking": details,
+        "reason": reason,
+        "transition": {
+            "from": current,
+            "to": "cancelled",
+        },
+    }))
+}
+
+/// Rejects cancelling a booking which starts within its service's `cancelCutoffHours`.
+async fn ensure_cancellable(
+    state: &AppState,
+    uuid: UuidType,
+    booking: &CmsRowResponse,
+) -> Result<()> {
+    let Some(service_id) = row_text(booking, "service")? else {
+        return Ok(());
+    };
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            &service_id,
+        )
+    })
+    .await?;
+
+    let Some(cutoff_hours) = row_number(&service, "cancelCutoffHours")?.filter(|v| *v > 0.0) else {
+        return Ok(());
+    };
+
+    check_cancel_cutoff(
+        cutoff_hours,
+        resolve_booking_start(state, uuid, booking).await?,
+        OffsetDateTime::now_utc(),
+    )
+}
+
+/// Rejects cancelling at `now` a booking starting at `start` within `cutoff_hours` of it.
+fn check_cancel_cutoff(
+    cutoff_hours: f64,
+    start: OffsetDateTime,
+    now: OffsetDateTime,
+) -> Result<()> {
+    if now > start - Duration::seconds_f64(cutoff_hours * 3600.0) {
+        return Err(Error::coded(
+            ErrorCode::CancellationWindowClosed,
+            format!(
+                "Bookings of this service can't be cancelled within {cutoff_hours} hours of the start"
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Most services a single batch can book.
+const MAX_BATCH_ITEMS: usize = 5;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchBookingItemJson {
+    /// The `slotId` returned by `availableHours`.
+    slot_id: String,
+    /// Length chosen by the customer for services with `durationOptions`.
+    duration_minutes: Option<f64>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchBookingJson {
+    contact_uuid: Uuid,
+    notes: Option<String>,
+    /// Booked back-to-back in this order.
+    items: Vec<BatchBookingItemJson>,
+}
+
+/// Books several services back-to-back with the same staff member, eg. a haircut then a beard trim.
+///
+/// Every slot is locked and passes the same checks as `form-process/after` before anything is
+/// written, and each slot has to start when the previous one ends. If writing fails partway, the
+/// bookings already written are voided.
+pub async fn post_batch_booking(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Json(BatchBookingJson {
+        contact_uuid,
+        notes,
+        items,
+    }): Json<BatchBookingJson>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    if items.is_empty() || items.len() > MAX_BATCH_ITEMS {
+        return Err(Error::Validation(vec![format!(
+            "A batch has to book between 1 and {MAX_BATCH_ITEMS} services"
+        )]));
+    }
+
+    let slots = items
+        .into_iter()
+        .map(|item| {
+            let slot_id = item
+                .slot_id
+                .parse::<SlotId>()
+                .map_err(Error::into_validation)?;
+
+            Ok((slot_id, item.duration_minutes))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if slots
+        .windows(2)
+        .any(|pair| pair[0].0.staff_id != pair[1].0.staff_id)
+    {
+        return Err(Error::Validation(vec![String::from(
+            "Every slot of a batch has to be with the same staff member",
+        )]));
+    }
+
+    let batch_id = Uuid::now_v7().to_string();
+    let client_key = format!("batch:{batch_id}");
+
+    let keys = slots
+        .iter()
+        .map(|(slot, _)| slot_lock_key(uuid, slot))
+        .collect::<Result<HashSet<_>>>()?;
+
+    let result = book_batch(
+        &state,
+        uuid,
+        &batch_id,
+        &client_key,
+        contact_uuid,
+        notes,
+        slots,
+    )
+    .await;
+
+    // The holds are released whether the batch was booked or not.
+    for key in &keys {
+        if state
+            .slot_lock
+            .release_if_owner(key, &client_key)
+            .await?
+            .is_some()
+        {
+            metrics::inc(&metrics::LOCKS_RELEASED);
+        }
+    }
+
+    Ok(Json(WrappingResponse::okay(result?)))
+}
+
+fn slot_lock_key(uuid: UuidType, slot: &SlotId) -> Result<LockKey> {
+    Ok((
+        uuid,
+        slot.schedule_id.clone(),
+        slot.day,
+        slot.month,
+        slot.year,
+        slot.time
+            .format(&format_description!("[hour]:[minute]:[second]"))?,
+    ))
+}
+
+/// Holds each slot for `client_key` once it's checked, so every slot is held before anything is
+/// written.
+async fn book_batch(
+    state: &AppState,
+    uuid: UuidType,
+    batch_id: &str,
+    client_key: &str,
+    contact_uuid: Uuid,
+    notes: Option<String>,
+    slots: Vec<(SlotId, Option<f64>)>,
+) -> Result<serde_json::Value> {
+    let mut rows = Vec::new();
+    let mut references = HashSet::new();
+    let mut booked = Vec::new();
+    // Service & start of each checked slot, as the contact's bookings aren't written yet.
+    let mut batched = Vec::new();
+    let mut calendar_events = Vec::new();
+    let mut previous_end: Option<OffsetDateTime> = None;
+
+    for (slot, duration_minutes) in slots {
+        let (schedule, staff_schedule) = validate_slot_links(
+            state,
+            uuid,
+            &slot.staff_schedule_id,
+            &slot.schedule_id,
+            &slot.service_id,
+            &slot.staff_id,
+        )
+        .await?;
+
+        // The customer's chosen length replaces the schedule's.
+        let duration: SimpleValue = match duration_minutes {
+            Some(minutes) => serde_json::json!(minutes).into(),
+            None => schedule
+                .fields
+                .get(&SchematicFieldKey::Other(String::from("duration")))
+                .context("Service Duration")?
+                .try_as_number()?
+                .into(),
+        };
+
+        let key = slot_lock_key(uuid, &slot)?;
+
+        let (start, end, places_left) = match validate_slot_free(
+            state,
+            uuid,
+            schedule.clone(),
+            staff_schedule,
+            calendar_date(slot.year, slot.month, slot.day)?,
+            &key.5,
+            duration_minutes,
+        )
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                if matches!(
+                    e,
+                    Error::Coded {
+                        code: ErrorCode::AlreadyBooked,
+                        ..
+                    }
+                ) {
+                    metrics::inc(&metrics::CONFLICTS_REJECTED);
+                }
+
+                return Err(e);
+            }
+        };
+
+        hold_batch_slot(state, &key, client_key, places_left, &slot).await?;
+        ensure_contiguous(&slot, previous_end, start)?;
+
+        previous_end = Some(end);
+
+        let service = retry::read(&state.config, || {
+            get_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
+                },
+                &slot.service_id,
+            )
+        })
+        .await?;
+
+        let payment = service_payment(&service)?;
+
+        ensure_bookable(
+            state,
+            uuid,
+            &NewBooking {
+                service: &service,
+                schedule: &schedule,
+                staff_id: &slot.staff_id,
+                contact_uuid,
+                start,
+                end,
+            },
+            &batched,
+        )
+        .await?;
+
+        batched.push((slot.service_id.clone(), start));
+
+        let mut reference = unique_booking_reference(state, uuid).await?;
+
+        // The batch's rows aren't written yet, so they're not seen by the lookup.
+        while !references.insert(reference.clone()) {
+            reference = unique_booking_reference(state, uuid).await?;
+        }
+
+        let mut fields = HashMap::from([
+            (String::from("bookDate"), book_date_value(start)?.into()),
+            (String::from("bookID"), book_id_value(start).into()),
+            (String::from("reference"), reference.clone().into()),
+            (String::from("type"), BOOKING_TYPE_BOOKING.into()),
+            (String::from("status"), BOOKING_STATUS_CONFIRMED.into()),
+            (
+                String::from("createdAt"),
+                book_date_value(OffsetDateTime::now_utc())?.into(),
+            ),
+            (String::from("source"), BOOKING_SOURCE_WIDGET.into()),
+            (String::from("batchId"), batch_id.into()),
+            (String::from("duration"), duration),
+            (
+                String::from("currency"),
+                service_currency(&state.config, &service)?.into(),
+            ),
+            (String::from("service"), slot.service_id.clone().into()),
+            (String::from("staffMember"), slot.staff_id.clone().into()),
+            (String::from("contactUuid"), contact_uuid.to_string().into()),
+            (
+                String::from("balanceDue"),
+                serde_json::json!(payment.balance_due).into(),
+            ),
+        ]);
+
+        if let Some(deposit_amount) = payment.deposit_amount {
+            fields.insert(
+                String::from("depositAmount"),
+                serde_json::json!(deposit_amount).into(),
+            );
+        }
+
+        if let Some(notes) = notes.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+            fields.insert(String::from("notes"), notes.into());
+        }
+
+        calendar_events.push((
+            slot.staff_id.clone(),
+            row_text(&service, "name")?.unwrap_or_default(),
+            reference.clone(),
+            start,
+            end,
+        ));
+
+        booked.push(serde_json::json!({
+            "reference": reference,
+            "slotId": slot.to_string(),
+            "start": start.format(&Iso8601::DEFAULT)?,
+            "end": end.format(&Iso8601::DEFAULT)?,
+            "serviceName": row_text(&service, "name")?,
+        }));
+
+        rows.push(fields);
+    }
+
+    // The written bookings occupy their slots until voided.
+    let written = write_all_or_undo(
+        &rows,
+        |fields| async move {
+            write_booking_row(state, uuid, fields).await?;
+
+            metrics::inc(&metrics::BOOKINGS_CREATED);
+
+            Ok(())
+        },
+        || void_batch(state, uuid, batch_id),
+    )
+    .await;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    written?;
+
+    for (staff_id, service_name, reference, start, end) in calendar_events {
+        google_calendar::push_booking(
+            state,
+            uuid,
+            staff_id,
+            service_name,
+            format!("Booking {reference}"),
+            start,
+            end,
+        );
+    }
+
+    Ok(serde_json::json!({
+        "batchId": batch_id,
+        "bookings": booked,
+    }))
+}
+
+/// Holds a place of the batch's slot for `client_key`, refusing it while others hold every place.
+async fn hold_batch_slot(
+    state: &AppState,
+    key: &LockKey,
+    client_key: &str,
+    places_left: usize,
+    slot: &SlotId,
+) -> Result<()> {
+    if state
+        .slot_lock
+        .try_acquire(key, client_key, places_left)
+        .await?
+        .is_none()
+    {
+        metrics::inc(&metrics::CONFLICTS_REJECTED);
+
+        return Err(Error::coded(
+            ErrorCode::AlreadyProcessing,
+            format!("Slot {slot} of the batch is already being booked"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks a slot of a batch starts when the previous one ends.
+fn ensure_contiguous(
+    slot: &SlotId,
+    previous_end: Option<OffsetDateTime>,
+    start: OffsetDateTime,
+) -> Result<()> {
+    if let Some(previous_end) = previous_end.filter(|v| *v != start) {
+        return Err(Error::coded(
+            ErrorCode::NotContiguous,
+            format!("Slot {slot} doesn't start when the previous slot ends at {previous_end}"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Writes a row to the bookings collection.
+async fn write_booking_row(
+    state: &AppState,
+    uuid: UuidType,
+    fields: &HashMap<String, SimpleValue>,
+) -> Result<()> {
+    retry::write(&state.config, || {
+        import_data_row(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Writes `rows` one by one. Once a row fails after others were written, `undo` is run so the
+/// written rows don't stay behind.
+async fn write_all_or_undo<'a, W, U>(
+    rows: &'a [HashMap<String, SimpleValue>],
+    mut write: impl FnMut(&'a HashMap<String, SimpleValue>) -> W,
+    undo: impl FnOnce() -> U,
+) -> Result<()>
+where
+    W: Future<Output = Result<()>>,
+    U: Future<Output = Result<()>>,
+{
+    for (index, fields) in rows.iter().enumerate() {
+        if let Err(e) = write(fields).await {
+            if index > 0 {
+                if let Err(undo_error) = undo().await {
+                    return Err(eyre::eyre!(
+                        "{e}, and the {index} rows written before couldn't be undone: {undo_error}"
+                    )
+                    .into());
+                }
+            }
+
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Voids the bookings a failed batch already wrote so they stop occupying their slots.
+async fn void_batch(state: &AppState, uuid: UuidType, batch_id: &str) -> Result<()> {
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(vec![Filter {
+                    name: String::from("batchId"),
+                    cond: FilterConditionType::Eq,
+                    value: FilterValue::Text(batch_id.to_string()),
+                }]),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    for row in &bookings.items {
+        let id = row_id(row)?;
+
+        retry::write(&state.config, || {
+            update_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                &id,
+                HashMap::from([(String::from("type"), BOOKING_TYPE_VOID.into())]),
+            )
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailPreviewQuery {
+    /// Booking to fill the placeholders in with. Sample values are used without one.
+    booking_id: Option<String>,
+}
+
+/// The booking email with its placeholders filled in, as the owner would receive it.
+pub async fn get_email_preview(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(EmailPreviewQuery { booking_id }): Query<EmailPreviewQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let values = match booking_id {
+        Some(booking_id) => booking_email_values(&state, uuid, &booking_id).await?,
+        None => template::email_values(
+            template::format_booking_date_time(
+                OffsetDateTime::now_utc()
+                    .date()
+                    .next_day()
+                    .context("Sample date")?
+                    .with_time(Time::from_hms(10, 0, 0)?),
+                &state.config.default_time_zone,
+            )?,
+            String::from("Haircut"),
+            String::from("Alex"),
+            template::submission_link(
+                &state.config,
+                &Uuid::nil().to_string(),
+                &Uuid::nil().to_string(),
+            ),
+        ),
+    };
+
+    // Placeholders of the body are filled in by the platform.
+    let mut body_values = values.clone();
+    body_values.insert("SUBMISSION_LINK", values["submissionLink"].clone());
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "subject": template::render(template::EMAIL_SUBJECT_TEMPLATE, &values),
+        "body": template::render(template::EMAIL_BODY_TEMPLATE, &body_values),
+        "variables": values,
+    }))))
+}
+
+/// The email placeholders of an existing booking.
+async fn booking_email_values(
+    state: &AppState,
+    uuid: UuidType,
+    booking_id: &str,
+) -> Result<HashMap<&'static str, String>> {
+    let booking = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            booking_id,
+        )
+    })
+    .await?;
+
+    let start = resolve_booking_start(state, uuid, &booking).await?;
+
+    let service_name = match row_text(&booking, "service")? {
+        Some(service_id) => retry::read(&state.config, || {
+            get_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
+                },
+                &service_id,
+            )
+        })
+        .await
+        .ok()
+        .map(|service| row_text(&service, "name"))
+        .transpose()?
+        .flatten(),
+        None => None,
+    };
+
+    let staff_id = row_text(&booking, "staffMember")?;
+
+    let staff_name = match &staff_id {
+        Some(staff_id) => retry::read(&state.config, || {
+            get_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("staff"),
+                    ns: Some(String::from("@booking")),
+                },
+                staff_id,
+            )
+        })
+        .await
+        .ok()
+        .map(|staff| row_text(&staff, "staffName"))
+        .transpose()?
+        .flatten(),
+        None => None,
+    };
+
+    let time_zone = match &staff_id {
+        Some(staff_id) => staff_booking_time_zone(state, uuid, staff_id).await?,
+        None => None,
+    };
+
+    // Bookings only store UTC, so it's shown in the staff member's time zone.
+    let booking_date_time = match time_zone {
+        Some(time_zone) => {
+            let local = start.to_offset(validate_time_zone(&time_zone)?);
+
+            template::format_booking_date_time(
+                PrimitiveDateTime::new(local.date(), local.time()),
+                &time_zone,
+            )?
+        }
+        None => template::format_booking_date_time(
+            PrimitiveDateTime::new(start.date(), start.time()),
+            "UTC",
+        )?,
+    };
+
+    let submission_link = match row_text(&booking, "submissionLink")? {
+        Some(link) => link,
+        None => template::submission_link(
+            &state.config,
+            &row_text(&booking, "schemaDataUuid")?.unwrap_or_default(),
+            &row_text(&booking, "contactUuid")?.unwrap_or_default(),
+        ),
+    };
+
+    Ok(template::email_values(
+        booking_date_time,
+        service_name.unwrap_or_default(),
+        staff_name.unwrap_or_default(),
+        submission_link,
+    ))
+}
+
+/// Time zone a staff member's bookings are shown in: their own, else that of one of their schedules.
+async fn staff_booking_time_zone(
+    state: &AppState,
+    uuid: UuidType,
+    staff_id: &str,
+) -> Result<Option<String>> {
+    if let Some(time_zone) = staff_time_zones(state, uuid).await?.remove(staff_id) {
+        return Ok(Some(time_zone));
+    }
+
+    let staff_schedules = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(vec![Filter {
+                    name: String::from("staff"),
+                    cond: FilterConditionType::Eq,
+                    value: FilterValue::Text(staff_id.to_string()),
+                }]),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    match staff_schedules.items.first() {
+        Some(row) => row_text(row, "timeZone"),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use super::*;
+    use crate::{gather_available_hours, tests::test_row, Occupied, BOOK_DATE_FORMAT};
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("Haircut"), "Haircut");
+        assert_eq!(csv_field("Cut, wash"), "\"Cut, wash\"");
+        assert_eq!(csv_field("The \"deluxe\""), "\"The \"\"deluxe\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[tokio::test]
+    async fn the_export_streams_each_page_after_the_header() {
+        let pages = stream::iter([
+            Ok(String::from(
+                "2025-03-14T09:00:00.000000000+01:00,Haircut,Ana,,30,\n",
+            )),
+            Ok(String::from(
+                "2025-03-15T10:00:00.000000000+01:00,Beard trim,Ana,,15,\n",
+            )),
+        ]);
+
+        let response = export_response(date!(2025 - 03 - 14), date!(2025 - 03 - 15), pages);
+
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"bookings-2025-03-14-2025-03-15.csv\""
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "date,service,staff,contact,duration,notes\n\
+             2025-03-14T09:00:00.000000000+01:00,Haircut,Ana,,30,\n\
+             2025-03-15T10:00:00.000000000+01:00,Beard trim,Ana,,15,\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_export_dates_are_bad_requests() {
+        use axum::{routing::get, Router};
+        use tower::ServiceExt;
+
+        use crate::config::Config;
+
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+
+        for query in [
+            "from=2025-13-01&to=2025-12-31",
+            "from=2025-01-01&to=tomorrow",
+        ] {
+            let response = Router::new()
+                .route("/:uuid/bookings/export.csv", get(get_bookings_export))
+                .with_state(state.clone())
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(format!(
+                            "/01938f4f-f50c-7203-9f89-b367e9d49efb/bookings/export.csv?{query}"
+                        ))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    /// Start hour, `isBooked` & `isBlocked` of the hourly slots from 9 to 12 UTC on `day`.
+    fn booked_hours(day: Date, bookings: Vec<CmsRowResponse>) -> Vec<(u8, bool, bool)> {
+        let service = test_row("service", HashMap::<String, _>::new());
+        let schedule = test_row("schedule", [("duration", serde_json::json!(60).into())]);
+        let staff_schedule = test_row(
+            "staffSchedule",
+            [
+                ("staff", "staff".into()),
+                ("timeZone", "UTC".into()),
+                ("start", "09:00:00".into()),
+                ("end", "12:00:00".into()),
+            ],
+        );
+
+        let occupied = Occupied {
+            bookings,
+            busy: Vec::new(),
+        };
+
+        gather_available_hours(
+            day.midnight(),
+            &service,
+            &schedule,
+            &staff_schedule,
+            &occupied,
+            None,
+            true,
+        )
+        .unwrap()
+        .iter()
+        .map(|hour| (hour.start.hour(), hour.is_booked, hour.is_blocked))
+        .collect()
+    }
+
+    #[test]
+    fn a_block_records_when_and_how_it_was_created() {
+        let before = OffsetDateTime::now_utc();
+
+        let block = block_fields(
+            datetime!(2025-03-14 10:00 UTC),
+            serde_json::json!(60).into(),
+            String::from("service"),
+            String::from("staff"),
+        )
+        .unwrap();
+
+        let created_at = crate::parse_book_date(
+            &block["createdAt"].any_as_text().unwrap(),
+            time::UtcOffset::UTC,
+        )
+        .unwrap();
+
+        assert!(before <= created_at && created_at <= OffsetDateTime::now_utc());
+        assert_eq!(
+            block["source"].any_as_text().unwrap(),
+            BOOKING_SOURCE_ADMIN_BLOCK
+        );
+    }
+
+    #[test]
+    fn a_cancelled_booking_frees_its_slot_and_stays_cancelled() {
+        let booking = |status: Option<&str>| {
+            let mut fields = vec![(
+                "bookDate",
+                SimpleValue::from(book_date_value(datetime!(2025-03-14 10:00 UTC)).unwrap()),
+            )];
+            fields.extend(status.map(|v| ("status", SimpleValue::from(v))));
+
+            test_row("booking", fields)
+        };
+
+        assert_eq!(
+            booked_hours(date!(2025 - 03 - 14), vec![booking(None)])[1],
+            (10, true, false)
+        );
+        assert_eq!(
+            booked_hours(date!(2025 - 03 - 14), vec![booking(Some("cancelled"))])[1],
+            (10, false, false)
+        );
+
+        assert_eq!(
+            status_transition("booking", &booking(None), "cancelled")
+                .unwrap()
+                .as_deref(),
+            Some(BOOKING_STATUS_CONFIRMED)
+        );
+        assert!(
+            status_transition("booking", &booking(Some("cancelled")), "cancelled")
+                .unwrap()
+                .is_none()
+        );
+
+        // Its slot may have been rebooked since.
+        for status in [BOOKING_STATUS_CONFIRMED, "completed"] {
+            assert!(matches!(
+                status_transition("booking", &booking(Some("cancelled")), status),
+                Err(Error::Coded {
+                    code: ErrorCode::InvalidStatusTransition,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn the_cancellation_webhook_has_the_booking_and_its_transition() {
+        let (config, mut rx) = webhook::tests::receiver(0).await;
+
+        let uuid: UuidType =
+            serde_json::from_value(serde_json::json!("01938f4f-f50c-7203-9f89-b367e9d49efb"))
+                .unwrap();
+        let booking = test_row(
+            "booking",
+            [
+                ("reference", SimpleValue::from("7K3QX9MD")),
+                ("status", BOOKING_STATUS_CONFIRMED.into()),
+                ("service", "haircut".into()),
+            ],
+        );
+
+        webhook::send(
+            &config,
+            webhook::EVENT_BOOKING_CANCELLED,
+            cancellation_data(
+                uuid,
+                &booking,
+                datetime!(2025-03-14 09:00 UTC),
+                BOOKING_STATUS_CONFIRMED,
+                Some(String::from("Sick")),
+            )
+            .unwrap(),
+        );
+
+        let delivered = webhook::tests::delivery(&mut rx).await;
+        let data = &delivered["data"];
+
+        assert_eq!(delivered["event"], "booking.cancelled");
+        assert_eq!(data["uuid"], "01938f4f-f50c-7203-9f89-b367e9d49efb");
+        assert_eq!(data["reason"], "Sick");
+        assert_eq!(
+            data["transition"],
+            serde_json::json!({ "from": "confirmed", "to": "cancelled" })
+        );
+        assert_eq!(data["booking"]["id"], "booking");
+        assert_eq!(data["booking"]["reference"], "7K3QX9MD");
+        assert_eq!(data["booking"]["serviceId"], "haircut");
+        assert_eq!(data["booking"]["status"], "cancelled");
+        assert_eq!(
+            data["booking"]["bookDate"],
+            "2025-03-14T09:00:00.000000000Z"
+        );
+    }
+
+    #[test]
+    fn bookings_cant_be_cancelled_within_the_cutoff() {
+        let start = datetime!(2025-03-14 10:00 UTC);
+
+        assert!(check_cancel_cutoff(24.0, start, datetime!(2025-03-13 09:59 UTC)).is_ok());
+        assert!(check_cancel_cutoff(24.0, start, datetime!(2025-03-13 10:00 UTC)).is_ok());
+        assert!(check_cancel_cutoff(1.5, start, datetime!(2025-03-14 08:30 UTC)).is_ok());
+
+        assert!(matches!(
+            check_cancel_cutoff(1.5, start, datetime!(2025-03-14 08:31 UTC)),
+            Err(Error::Coded {
+                code: ErrorCode::CancellationWindowClosed,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn a_blocked_slot_is_booked() {
+        let block = block_fields(
+            datetime!(2025-03-14 10:00 UTC),
+            serde_json::json!(60).into(),
+            String::from("service"),
+            String::from("staff"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            booked_hours(date!(2025 - 03 - 14), vec![test_row("block", block)]),
+            [(9, false, false), (10, true, true), (11, false, false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_email_preview_uses_sample_values_without_a_booking() {
+        use axum::{routing::get, Router};
+        use tower::ServiceExt;
+
+        use crate::config::Config;
+
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+        let time_zone = state.config.default_time_zone.clone();
+
+        let response = Router::new()
+            .route("/:uuid/email/preview", get(get_email_preview))
+            .with_state(state)
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri("/01938f4f-f50c-7203-9f89-b367e9d49efb/email/preview")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let preview = &serde_json::from_slice::<serde_json::Value>(&body).unwrap()["result"];
+
+        let subject = preview["subject"].as_str().unwrap();
+
+        assert!(
+            subject.starts_with("You received a new booking for "),
+            "{subject}"
+        );
+        assert!(
+            subject.ends_with(&format!("at 10:00 AM ({time_zone})!")),
+            "{subject}"
+        );
+        // The link the platform fills in is shown as well.
+        assert_eq!(
+            preview["body"],
+            format!("/forms/submissions/{}", Uuid::nil())
+        );
+        assert_eq!(preview["variables"]["serviceName"], "Haircut");
+    }
+
+    #[tokio::test]
+    async fn a_malformed_block_time_is_a_bad_request() {
+        use axum::{routing::post, Router};
+        use tower::ServiceExt;
+
+        use crate::config::Config;
+
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+
+        let response = Router::new()
+            .route("/:uuid/block", post(post_block))
+            .with_state(state)
+            .oneshot(
+                axum::http::Request::builder()
+                    .method("POST")
+                    .uri("/01938f4f-f50c-7203-9f89-b367e9d49efb/block")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        r#"{"scheduleId":"schedule","staffId":"staff","day":14,"month":3,"year":2025,"time":"10am"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    fn slot(hour: u8) -> SlotId {
+        SlotId {
+            staff_schedule_id: String::from("staffSchedule"),
+            schedule_id: String::from("schedule"),
+            service_id: String::from("service"),
+            staff_id: String::from("staff"),
+            day: 14,
+            month: 3,
+            year: 2025,
+            time: Time::from_hms(hour, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn batch_slots_have_to_be_contiguous() {
+        let end = datetime!(2025-03-14 10:00 UTC);
+
+        assert!(ensure_contiguous(&slot(9), None, datetime!(2025-03-14 9:00 UTC)).is_ok());
+        assert!(ensure_contiguous(&slot(10), Some(end), end).is_ok());
+
+        for start in [
+            datetime!(2025-03-14 10:30 UTC),
+            datetime!(2025-03-14 9:00 UTC),
+        ] {
+            assert!(matches!(
+                ensure_contiguous(&slot(10), Some(end), start),
+                Err(Error::Coded {
+                    code: ErrorCode::NotContiguous,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn a_batch_slot_held_by_another_client_conflicts() {
+        use crate::config::Config;
+
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+        let uuid =
+            serde_json::from_value(serde_json::json!("01938f4f-f50c-7203-9f89-b367e9d49efb"))
+                .unwrap();
+        let key = slot_lock_key(uuid, &slot(9)).unwrap();
+
+        state.slot_lock.try_acquire(&key, "other", 1).await.unwrap();
+
+        assert!(matches!(
+            hold_batch_slot(&state, &key, "batch:a", 1, &slot(9)).await,
+            Err(Error::Coded {
+                code: ErrorCode::AlreadyProcessing,
+                ..
+            })
+        ));
+
+        // A group slot still has a place for the batch.
+        assert!(hold_batch_slot(&state, &key, "batch:a", 2, &slot(9))
+            .await
+            .is_ok());
+    }
+
+    /// Writes 3 rows, failing from the `failing` one, and returns the result and whether the
+    /// written rows were undone.
+    async fn write_failing_from(failing: usize, undo_fails: bool) -> (Result<()>, bool) {
+        let rows = vec![HashMap::new(); 3];
+        let written = std::cell::Cell::new(0);
+        let undone = std::cell::Cell::new(false);
+
+        let result = write_all_or_undo(
+            &rows,
+            |_| async {
+                if written.get() == failing {
+                    return Err(eyre::eyre!("CMS unavailable").into());
+                }
+
+                written.set(written.get() + 1);
+
+                Ok(())
+            },
+            || async {
+                undone.set(true);
+
+                if undo_fails {
+                    return Err(eyre::eyre!("CMS still unavailable").into());
+                }
+
+                Ok(())
+            },
+        )
+        .await;
+
+        (result, undone.get())
+    }
+
+    #[tokio::test]
+    async fn a_partially_written_batch_is_undone() {
+        let (result, undone) = write_failing_from(2, false).await;
+
+        let message = result.unwrap_err().to_string();
+
+        assert!(message.ends_with("CMS unavailable"), "{message}");
+        assert!(undone);
+    }
+
+    #[tokio::test]
+    async fn nothing_is_undone_when_the_first_row_fails() {
+        let (result, undone) = write_failing_from(0, false).await;
+
+        assert!(result.is_err());
+        assert!(!undone);
+
+        let (result, undone) = write_failing_from(3, false).await;
+
+        assert!(result.is_ok());
+        assert!(!undone);
+    }
+
+    #[tokio::test]
+    async fn a_failed_undo_is_reported() {
+        let (result, _) = write_failing_from(1, true).await;
+
+        let message = result.unwrap_err().to_string();
+
+        assert!(message.contains("CMS unavailable"), "{message}");
+        assert!(message.contains("CMS still unavailable"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn invalid_staff_booking_ranges_are_bad_requests() {
+        use axum::{routing::get, Router};
+        use tower::ServiceExt;
+
+        use crate::config::Config;
+
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+
+        for query in [
+            "from=2025-03-32&to=2025-04-01",
+            "from=2025-03-14&to=next-week",
+            "from=2025-03-15&to=2025-03-14",
+        ] {
+            let response = Router::new()
+                .route("/:uuid/staff/:staffId/bookings", get(get_staff_bookings))
+                .with_state(state.clone())
+                .oneshot(
+                    axum::http::Request::builder()
+                        .uri(format!(
+                            "/01938f4f-f50c-7203-9f89-b367e9d49efb/staff/staff/bookings?{query}"
+                        ))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                response.status(),
+                axum::http::StatusCode::BAD_REQUEST,
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn staff_booking_pages_are_bounded() {
+        assert_eq!(page_bounds(None, None), (0, 50));
+        assert_eq!(page_bounds(Some(100), Some(0)), (100, 1));
+        assert_eq!(page_bounds(None, Some(1000)), (0, 250));
+    }
+
+    #[test]
+    fn a_block_range_covers_every_day_of_the_window() {
+        let offset = UtcOffset::from_hms(1, 0, 0).unwrap();
+
+        let rows = block_range_rows(
+            "range",
+            "staff",
+            (date!(2025 - 03 - 14), 3),
+            (time::macros::time!(9:00), Duration::hours(8)),
+            offset,
+        )
+        .unwrap();
+
+        assert_eq!(rows.len(), 3);
+
+        for (day, fields) in rows.iter().enumerate() {
+            let text = |name: &str| fields[name].any_as_text().unwrap();
+
+            let start = OffsetDateTime::parse(&text("bookDate"), BOOK_DATE_FORMAT).unwrap();
+
+            assert_eq!(
+                start,
+                date!(2025 - 03 - 14)
+                    .with_time(time::macros::time!(9:00))
+                    .assume_offset(offset)
+                    + Duration::days(day as i64)
+            );
+            assert_eq!(text("duration"), "480");
+            assert_eq!(text("type"), BOOKING_TYPE_BLOCK);
+            assert_eq!(text("status"), BOOKING_STATUS_CONFIRMED);
+            assert_eq!(text("batchId"), "range");
+            assert_eq!(text("staffMember"), "staff");
+        }
+    }
+
+    #[test]
+    fn a_block_range_is_unavailable_until_lifted() {
+        let rows = block_range_rows(
+            "range",
+            "staff",
+            (date!(2025 - 03 - 14), 2),
+            (Time::MIDNIGHT, Duration::DAY),
+            UtcOffset::UTC,
+        )
+        .unwrap();
+
+        let blocks = rows
+            .iter()
+            .enumerate()
+            .map(|(index, fields)| test_row(&index.to_string(), fields.clone()))
+            .collect::<Vec<_>>();
+
+        let lifted = rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut fields)| {
+                fields.extend(lifted_block_fields());
+
+                test_row(&index.to_string(), fields)
+            })
+            .collect::<Vec<_>>();
+
+        for day in [date!(2025 - 03 - 14), date!(2025 - 03 - 15)] {
+            assert_eq!(
+                booked_hours(day, blocks.clone()),
+                [(9, true, true), (10, true, true), (11, true, true)]
+            );
+            assert_eq!(
+                booked_hours(day, lifted.clone()),
+                [(9, false, false), (10, false, false), (11, false, false)]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_block_range_dates_and_times_are_bad_requests() {
+        use axum::{routing::post, Router};
+        use tower::ServiceExt;
+
+        use crate::config::Config;
+
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+
+        for body in [
+            r#"{"staffId":"staff","from":"2025-13-01","to":"2025-12-31"}"#,
+            r#"{"staffId":"staff","from":"2025-03-14","to":"2025-03-15","start":"9am"}"#,
+            r#"{"staffId":"staff","from":"2025-03-14","to":"2025-03-15","end":"25:00:00"}"#,
+        ] {
+            let response = Router::new()
+                .route("/:uuid/block/range", post(post_block_range))
+                .with_state(state.clone())
+                .oneshot(
+                    axum::http::Request::builder()
+                        .method("POST")
+                        .uri("/01938f4f-f50c-7203-9f89-b367e9d49efb/block/range")
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use std::{collections::HashMap, time::Duration};
+
+use uuid::Uuid;
+
+use crate::Result;
+
+/// Settings which differ between deployments, loaded once at startup and shared through
+/// [`crate::AppState`].
+///
+/// Each value is read from its environment variable, then from the `KEY=value` lines of
+/// the file at `BOOKING_CONFIG_FILE`, then falls back to its default.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Token the addon registers its calls with. `BOOKING_ADDON_UUID`
+    pub addon_uuid: Uuid,
+    /// Sender of the booking emails. `BOOKING_FROM_EMAIL`
+    pub from_email: String,
+    /// Reply-To of the booking emails. `BOOKING_REPLY_TO_EMAIL`
+    pub reply_to_email: String,
+    /// Who is emailed when a booking is made. Comma separated, `{{OWNER_EMAIL}}` is the
+    /// installing member. `BOOKING_NOTIFICATION_RECIPIENTS`
+    pub notification_recipients: String,
+    /// `BOOKING_PORT`
+    pub port: u16,
+    /// Currency of the services which don't specify their own. `BOOKING_DEFAULT_CURRENCY`
+    pub default_currency: String,
+    /// Time zone of the schedules created on install when none is given. `BOOKING_DEFAULT_TIME_ZONE`
+    pub default_time_zone: String,
+    /// How long a slot stays locked for the form to be submitted. `BOOKING_LOCK_TTL_SECS`
+    pub lock_ttl: Duration,
+    /// Keeps slot locks in Redis to share them between replicas. In memory when not set.
+    /// `BOOKING_LOCK_REDIS_URL`
+    pub lock_redis_url: Option<String>,
+    /// Receives booking events, eg. cancellations. Not sent when unset. `BOOKING_WEBHOOK_URL`
+    pub webhook_url: Option<String>,
+    /// Max attempts of a CMS request, including the first. `BOOKING_CMS_RETRY_ATTEMPTS`
+    pub cms_retry_attempts: u32,
+    /// Wait before the first retry of a CMS request. Doubles with each further attempt.
+    /// `BOOKING_CMS_RETRY_BACKOFF_MS`
+    pub cms_retry_backoff: Duration,
+    /// Max requests a single IP can make to the public routes within `rate_limit_window`.
+    /// `BOOKING_RATE_LIMIT`
+    pub rate_limit: u32,
+    /// Max requests a single website can make to the form-process routes within
+    /// `rate_limit_window`. `BOOKING_WEBSITE_RATE_LIMIT`
+    pub website_rate_limit: u32,
+    /// `BOOKING_RATE_LIMIT_WINDOW_SECS`
+    pub rate_limit_window: Duration,
+    /// How long computed availability is reused for. `0` disables the cache.
+    /// `BOOKING_AVAILABILITY_CACHE_TTL_SECS`
+    pub availability_cache_ttl: Duration,
+    /// Link to the form submission of a booking. `{{SCHEMA_DATA_UUID}}` & `{{CONTACT_UUID}}`
+    /// are filled in. Relative to the dashboard by default. `BOOKING_SUBMISSION_LINK_TEMPLATE`
+    pub submission_link_template: String,
+    /// Where a stored file is served from. `{{FILE}}` is filled in with the file's name.
+    /// Relative to the dashboard by default. `BOOKING_FILE_URL_TEMPLATE`
+    pub file_url_template: String,
+    /// Whether `GET /metrics` is exposed. `BOOKING_METRICS_ENABLED`
+    pub metrics_enabled: bool,
+    /// Bearer token of the admin routes, eg. the slot lock ones. They're refused while unset.
+    /// `BOOKING_ADMIN_TOKEN`
+    pub admin_token: Option<String>,
+    /// OAuth client of the Google Calendar integration. Disabled unless all three of
+    /// `BOOKING_GOOGLE_CLIENT_ID`, `BOOKING_GOOGLE_CLIENT_SECRET` & `BOOKING_GOOGLE_REDIRECT_URI` are set.
+    pub google: Option<GoogleConfig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GoogleConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    /// AES-256 key the staff refresh tokens are encrypted with, base64 encoded. Required
+    /// once the client is set. `BOOKING_GOOGLE_TOKEN_KEY`
+    pub token_key: [u8; 32],
+    /// `BOOKING_GOOGLE_TOKEN_URL`
+    pub token_url: String,
+    /// `BOOKING_GOOGLE_CALENDAR_API_URL`
+    pub calendar_api_url: String,
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let file = match std::env::var("BOOKING_CONFIG_FILE") {
+            Ok(path) => parse_config_file(&std::fs::read_to_string(path)?),
+            Err(_) => HashMap::new(),
+        };
+
+        Self::from_lookup(|key| std::env::var(key).ok().or_else(|| file.get(key).cloned()))
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let addon_uuid = match lookup("BOOKING_ADDON_UUID") {
+            Some(v) => v.parse()?,
+            None => Uuid::from_u128(0x01938f4ff50c72039f89b367e9d49efbu128),
+        };
+
+        let port = match lookup("BOOKING_PORT") {
+            Some(v) => v.parse()?,
+            None => 5941,
+        };
+
+        let lock_ttl_secs = match lookup("BOOKING_LOCK_TTL_SECS") {
+            Some(v) => v.parse()?,
+            None => 60 * 10,
+        };
+
+        let cms_retry_attempts = match lookup("BOOKING_CMS_RETRY_ATTEMPTS") {
+            Some(v) => v.parse()?,
+            None => 3,
+        };
+
+        if cms_retry_attempts == 0 {
+            return Err(eyre::eyre!("BOOKING_CMS_RETRY_ATTEMPTS must be at least 1"))?;
+        }
+
+        let cms_retry_backoff_ms = match lookup("BOOKING_CMS_RETRY_BACKOFF_MS") {
+            Some(v) => v.parse()?,
+            None => 100,
+        };
+
+        let rate_limit = match lookup("BOOKING_RATE_LIMIT") {
+            Some(v) => v.parse()?,
+            None => 120,
+        };
+
+        let website_rate_limit = match lookup("BOOKING_WEBSITE_RATE_LIMIT") {
+            Some(v) => v.parse()?,
+            None => 600,
+        };
+
+        let rate_limit_window_secs = match lookup("BOOKING_RATE_LIMIT_WINDOW_SECS") {
+            Some(v) => v.parse()?,
+            None => 60,
+        };
+
+        let availability_cache_ttl_secs = match lookup("BOOKING_AVAILABILITY_CACHE_TTL_SECS") {
+            Some(v) => v.parse()?,
+            None => 30,
+        };
+
+        let from_email =
+            lookup("BOOKING_FROM_EMAIL").unwrap_or_else(|| String::from("noreply@dinko.space"));
+
+        let reply_to_email = lookup("BOOKING_REPLY_TO_EMAIL").unwrap_or_else(|| from_email.clone());
+
+        let default_time_zone = lookup("BOOKING_DEFAULT_TIME_ZONE")
+            .unwrap_or_else(|| String::from("America/Los_Angeles"));
+
+        let default_currency =
+            lookup("BOOKING_DEFAULT_CURRENCY").unwrap_or_else(|| String::from("USD"));
+
+        crate::validate_time_zone(&default_time_zone)?;
+        crate::validate_currency(&default_currency)?;
+        crate::validate_email(&from_email)?;
+        crate::validate_email(&reply_to_email)?;
+
+        let google = match (
+            lookup("BOOKING_GOOGLE_CLIENT_ID"),
+            lookup("BOOKING_GOOGLE_CLIENT_SECRET"),
+            lookup("BOOKING_GOOGLE_REDIRECT_URI"),
+        ) {
+            (Some(client_id), Some(client_secret), Some(redirect_uri)) => Some(GoogleConfig {
+                client_id,
+                client_secret,
+                redirect_uri,
+                token_key: parse_token_key(
+                    &lookup("BOOKING_GOOGLE_TOKEN_KEY")
+                        .ok_or_else(|| eyre::eyre!("BOOKING_GOOGLE_TOKEN_KEY must be set"))?,
+                )?,
+                token_url: lookup("BOOKING_GOOGLE_TOKEN_URL")
+                    .unwrap_or_else(|| String::from("https://oauth2.googleapis.com/token")),
+                calendar_api_url: lookup("BOOKING_GOOGLE_CALENDAR_API_URL")
+                    .unwrap_or_else(|| String::from("https://www.googleapis.com/calendar/v3/")),
+            }),
+            _ => None,
+        };
+
+        Ok(Self {
+            addon_uuid,
+            from_email,
+            reply_to_email,
+            notification_recipients: lookup("BOOKING_NOTIFICATION_RECIPIENTS")
+                .unwrap_or_else(|| String::from("{{OWNER_EMAIL}}")),
+            port,
+            default_currency,
+            default_time_zone,
+            lock_ttl: Duration::from_secs(lock_ttl_secs),
+            lock_redis_url: lookup("BOOKING_LOCK_REDIS_URL"),
+            webhook_url: lookup("BOOKING_WEBHOOK_URL"),
+            cms_retry_attempts,
+            cms_retry_backoff: Duration::from_millis(cms_retry_backoff_ms),
+            rate_limit,
+            website_rate_limit,
+            rate_limit_window: Duration::from_secs(rate_limit_window_secs),
+            availability_cache_ttl: Duration::from_secs(availability_cache_ttl_secs),
+            submission_link_template: lookup("BOOKING_SUBMISSION_LINK_TEMPLATE")
+                .unwrap_or_else(|| String::from("/forms/submissions/{{SCHEMA_DATA_UUID}}")),
+            file_url_template: lookup("BOOKING_FILE_URL_TEMPLATE")
+                .unwrap_or_else(|| String::from("/files/{{FILE}}")),
+            metrics_enabled: lookup("BOOKING_METRICS_ENABLED")
+                .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true")),
+            admin_token: lookup("BOOKING_ADMIN_TOKEN").filter(|v| !v.is_empty()),
+            google,
+        })
+    }
+}
+
+/// A base64 encoded 32 byte key.
+fn parse_token_key(value: &str) -> Result<[u8; 32]> {
+    use base64::Engine;
+    use eyre::WrapErr;
+
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(value.trim())
+        .wrap_err("BOOKING_GOOGLE_TOKEN_KEY must be base64")?;
+
+    Ok(key
+        .try_into()
+        .map_err(|_| eyre::eyre!("BOOKING_GOOGLE_TOKEN_KEY must be 32 bytes"))?)
+}
+
+/// `KEY=value` pairs, one per line. Blank lines and lines starting with `#` are skipped.
+fn parse_config_file(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 32 bytes of `7`.
+    const TOKEN_KEY: &str = "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=";
+
+    fn config(values: &[(&str, &str)]) -> Result<Config> {
+        let values: HashMap<String, String> = values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        Config::from_lookup(|key| values.get(key).cloned())
+    }
+
+    #[test]
+    fn defaults() {
+        let config = config(&[]).unwrap();
+
+        assert_eq!(config.port, 5941);
+        assert_eq!(config.default_currency, "USD");
+        assert_eq!(config.lock_ttl, Duration::from_secs(600));
+        assert_eq!(config.cms_retry_attempts, 3);
+        assert_eq!(config.reply_to_email, config.from_email);
+        assert_eq!(config.notification_recipients, "{{OWNER_EMAIL}}");
+        assert!(config.lock_redis_url.is_none());
+        assert!(config.google.is_none());
+        assert!(!config.metrics_enabled);
+        assert!(config.admin_token.is_none());
+        assert_eq!(config.website_rate_limit, 600);
+    }
+
+    #[test]
+    fn overrides() {
+        let config = config(&[
+            ("BOOKING_PORT", "8080"),
+            ("BOOKING_DEFAULT_CURRENCY", "EUR"),
+            ("BOOKING_LOCK_TTL_SECS", "30"),
+            ("BOOKING_FROM_EMAIL", "bookings@example.com"),
+            ("BOOKING_DEFAULT_TIME_ZONE", "Europe/Zagreb"),
+            ("BOOKING_RATE_LIMIT", "5"),
+            ("BOOKING_AVAILABILITY_CACHE_TTL_SECS", "0"),
+            ("BOOKING_METRICS_ENABLED", "true"),
+            ("BOOKING_GOOGLE_CLIENT_ID", "id"),
+            ("BOOKING_GOOGLE_CLIENT_SECRET", "secret"),
+            (
+                "BOOKING_GOOGLE_REDIRECT_URI",
+                "https://example.com/callback",
+            ),
+            ("BOOKING_GOOGLE_TOKEN_KEY", TOKEN_KEY),
+            ("BOOKING_GOOGLE_TOKEN_URL", "http://localhost:9000/token"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.default_currency, "EUR");
+        assert_eq!(config.lock_ttl, Duration::from_secs(30));
+        assert_eq!(config.from_email, "bookings@example.com");
+        assert_eq!(config.reply_to_email, "bookings@example.com");
+        assert_eq!(config.default_time_zone, "Europe/Zagreb");
+        assert_eq!(config.rate_limit, 5);
+        assert_eq!(config.availability_cache_ttl, Duration::ZERO);
+        assert!(config.metrics_enabled);
+
+        let google = config.google.unwrap();
+
+        assert_eq!(google.client_id, "id");
+        assert_eq!(google.token_key, [7; 32]);
+        assert_eq!(google.token_url, "http://localhost:9000/token");
+        assert_eq!(
+            google.calendar_api_url,
+            "https://www.googleapis.com/calendar/v3/"
+        );
+    }
+
+    #[test]
+    fn google_client_requires_a_token_key() {
+        let client = [
+            ("BOOKING_GOOGLE_CLIENT_ID", "id"),
+            ("BOOKING_GOOGLE_CLIENT_SECRET", "secret"),
+            (
+                "BOOKING_GOOGLE_REDIRECT_URI",
+                "https://example.com/callback",
+            ),
+        ];
+
+        assert!(config(&client).is_err());
+        assert!(config(
+            &[
+                client.as_slice(),
+                &[("BOOKING_GOOGLE_TOKEN_KEY", "c2hvcnQ=")]
+            ]
+            .concat()
+        )
+        .is_err());
+        assert!(config(
+            &[
+                client.as_slice(),
+                &[("BOOKING_GOOGLE_TOKEN_KEY", TOKEN_KEY)]
+            ]
+            .concat()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn partial_google_client_is_disabled() {
+        let config = config(&[("BOOKING_GOOGLE_CLIENT_ID", "id")]).unwrap();
+
+        assert!(config.google.is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(config(&[("BOOKING_PORT", "port")]).is_err());
+        assert!(config(&[("BOOKING_CMS_RETRY_ATTEMPTS", "0")]).is_err());
+        assert!(config(&[("BOOKING_DEFAULT_CURRENCY", "usd")]).is_err());
+        assert!(config(&[("BOOKING_FROM_EMAIL", "not an email")]).is_err());
+        assert!(config(&[("BOOKING_DEFAULT_TIME_ZONE", "Mars/Base")]).is_err());
+    }
+
+    #[test]
+    fn parses_config_file() {
+        let values = parse_config_file(
+            "# comment\n\nBOOKING_PORT = 8080\nBOOKING_WEBHOOK_URL=https://example.com/a=b\n",
+        );
+
+        assert_eq!(values.get("BOOKING_PORT").unwrap(), "8080");
+        assert_eq!(
+            values.get("BOOKING_WEBHOOK_URL").unwrap(),
+            "https://example.com/a=b"
+        );
+        assert_eq!(values.len(), 2);
+    }
+}
@@ -28,22 +28,200 @@ pub enum Error {
     TimeRange(#[from] time::error::ComponentRange),
     #[error("Time Parse Error: {0}")]
     TimeParse(#[from] time::error::Parse),
+    #[error("Time Format Error: {0}")]
+    TimeFormat(#[from] time::error::Format),
 
     #[error("Multipart Error: {0}")]
     Multipart(#[from] axum::extract::multipart::MultipartError),
     #[error("Axum Error: {0}")]
     Axum(#[from] axum::Error),
+    #[error("Reqwest Error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Redis Error: {0}")]
+    Redis(#[from] redis::RedisError),
 
     #[error("Convert PathBuf to String Error")]
     ConvertPathBufToString,
+
+    #[error("{message}")]
+    Coded {
+        code: ErrorCode,
+        message: String,
+        /// Extra context for the client, eg. the slot a booking conflicts with.
+        details: Option<serde_json::Value>,
+    },
+    /// Every problem found with the request's inputs, so they can be fixed at once.
+    #[error("{}", .0.join("; "))]
+    Validation(Vec<String>),
+}
+
+impl Error {
+    pub fn coded(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Coded {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn coded_with_details(
+        code: ErrorCode,
+        message: impl Into<String>,
+        details: serde_json::Value,
+    ) -> Self {
+        Self::Coded {
+            code,
+            message: message.into(),
+            details: Some(details),
+        }
+    }
+
+    /// The message of the error as one of several invalid inputs, without the error kind prefix.
+    pub fn validation_message(self) -> String {
+        match self {
+            Self::Eyre(report) => report.to_string(),
+            error => error.to_string(),
+        }
+    }
+
+    /// Reports the error as a problem with the request's inputs, eg. a date which didn't parse.
+    pub fn into_validation(self) -> Self {
+        match self {
+            Self::Validation(_) | Self::Coded { .. } => self,
+            Self::Eyre(report) => Self::Validation(vec![report.to_string()]),
+            error => Self::Validation(vec![error.to_string()]),
+        }
+    }
+}
+
+/// Machine-readable codes for known booking conditions.
+/// Returned alongside the error message so clients don't need to match on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The schedule belongs to a different service than the one sent.
+    ServiceScheduleMismatch,
+    /// The staff schedule belongs to a different schedule than the one sent.
+    ScheduleMismatch,
+    /// The staff schedule belongs to a different staff member than the one sent.
+    StaffMismatch,
+    AlreadyProcessing,
+    TimeNotFound,
+    /// The slot is full. Sent from `form-process/before` with the slot and an alternative in
+    /// `details`.
+    AlreadyBooked,
+    DuplicateBooking,
+    /// The form was submitted after the slot's lock expired.
+    LockExpired,
+    /// A CMS row is missing a field it needs, eg. a schedule without a `duration`.
+    MissingField,
+    /// The chosen `durationMinutes` isn't one of the service's `durationOptions`.
+    DurationNotAllowed,
+    /// The service has no active staff schedule left, eg. after its schedules were removed.
+    ServiceNotBookable,
+    /// A slot of a batch doesn't start when the previous one ends.
+    NotContiguous,
+    /// The booking can't move from its current `status` to the requested one.
+    InvalidStatusTransition,
+    /// The requested `day`, `month` & `year` aren't a real date, eg. February 30th.
+    InvalidDate,
+    /// The booking starts within its service's `cancelCutoffHours`.
+    CancellationWindowClosed,
+    /// Some of the inputs are invalid. Each problem is listed in `errors`.
+    ValidationFailed,
+    /// A row id sent belongs to a different website than the one in the request.
+    NotOwned,
+    /// The contact already has the service's `maxPerContactPerDay` bookings on the day.
+    ContactDailyLimitReached,
+    /// The `Idempotency-Key` was already used for a request with a different slot or contact.
+    IdempotencyKeyReused,
+    /// No blocks of the range were found, eg. a range id which was never created.
+    BlockRangeNotFound,
+}
+
+impl ErrorCode {
+    fn status(self) -> StatusCode {
+        match self {
+            Self::MissingField
+            | Self::DurationNotAllowed
+            | Self::NotContiguous
+            | Self::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::AlreadyProcessing
+            | Self::AlreadyBooked
+            | Self::DuplicateBooking
+            | Self::LockExpired
+            | Self::ServiceNotBookable
+            | Self::InvalidStatusTransition
+            | Self::CancellationWindowClosed
+            | Self::ContactDailyLimitReached => StatusCode::CONFLICT,
+            Self::ServiceScheduleMismatch
+            | Self::ScheduleMismatch
+            | Self::StaffMismatch
+            | Self::InvalidDate
+            | Self::ValidationFailed => StatusCode::BAD_REQUEST,
+            Self::TimeNotFound | Self::BlockRangeNotFound => StatusCode::NOT_FOUND,
+            Self::NotOwned => StatusCode::FORBIDDEN,
+        }
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(WrappingResponse::<()>::error(self.to_string())),
-        )
-            .into_response()
+        let mut body = serde_json::to_value(WrappingResponse::<()>::error(self.to_string()))
+            .unwrap_or_default();
+
+        let mut status = StatusCode::INTERNAL_SERVER_ERROR;
+
+        if let (Self::Coded { code, details, .. }, Some(body)) = (&self, body.as_object_mut()) {
+            body.insert(String::from("code"), serde_json::json!(code));
+            status = code.status();
+
+            if let Some(details) = details {
+                body.insert(String::from("details"), details.clone());
+            }
+        }
+
+        if let (Self::Validation(errors), Some(body)) = (&self, body.as_object_mut()) {
+            let code = ErrorCode::ValidationFailed;
+
+            body.insert(String::from("code"), serde_json::json!(code));
+            body.insert(String::from("errors"), serde_json::json!(errors));
+            status = code.status();
+        }
+
+        (status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn booking_conditions_are_client_errors() {
+        for code in [
+            ErrorCode::ServiceScheduleMismatch,
+            ErrorCode::ScheduleMismatch,
+            ErrorCode::StaffMismatch,
+            ErrorCode::AlreadyProcessing,
+            ErrorCode::TimeNotFound,
+            ErrorCode::DuplicateBooking,
+            ErrorCode::LockExpired,
+        ] {
+            assert!(code.status().is_client_error(), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn input_errors_are_bad_requests() {
+        let error = Error::from(eyre::eyre!("Invalid slotId: x")).into_validation();
+
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            Error::from(eyre::eyre!("CMS unreachable"))
+                .into_response()
+                .status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
     }
 }
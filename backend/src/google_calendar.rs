@@ -0,0 +1,686 @@
+use std::{
+    collections::HashMap,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use webby_addon_common::{
+    request::{get_cms_row_by_id, update_cms_row_by_id},
+    JsonResponse, WrappingResponse,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use eyre::{ContextCompat, WrapErr};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use webby_global_common::{
+    uuid::{CollectionName, UuidType},
+    value::SimpleValue,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{config::Config, retry, row_bool, row_text, AppState, Result};
+
+const AUTHORIZE_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const CALENDAR_SCOPE: &str = "https://www.googleapis.com/auth/calendar";
+
+/// Calendar used when the staff member didn't pick one.
+const PRIMARY_CALENDAR: &str = "primary";
+
+/// How long an authorization link can be used for.
+const AUTHORIZE_TTL: Duration = Duration::from_secs(10 * 60);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("failed to build the Google Calendar client")
+});
+
+/// When an authorization was started, with the website & staff member it's for.
+type PendingAuthorization = (Instant, UuidType, String);
+
+/// OAuth state of the integration.
+#[derive(Default)]
+pub struct GoogleCalendar {
+    /// Pending authorizations by their `state`.
+    pending_authorizations: Mutex<HashMap<String, PendingAuthorization>>,
+    /// Access tokens by refresh token, with when they expire.
+    access_tokens: Mutex<HashMap<String, (Instant, String)>>,
+}
+
+struct OAuthClient<'a> {
+    id: &'a str,
+    secret: &'a str,
+    redirect_uri: &'a str,
+    token_key: &'a [u8; 32],
+    token_url: &'a str,
+    calendar_api_url: &'a str,
+}
+
+/// The addon's OAuth client. The integration is disabled without one.
+fn oauth_client(config: &Config) -> Option<OAuthClient<'_>> {
+    let google = config.google.as_ref()?;
+
+    Some(OAuthClient {
+        id: &google.client_id,
+        secret: &google.client_secret,
+        redirect_uri: &google.redirect_uri,
+        token_key: &google.token_key,
+        token_url: &google.token_url,
+        calendar_api_url: &google.calendar_api_url,
+    })
+}
+
+fn token_cipher(key: &[u8; 32]) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| eyre::eyre!("Invalid token key"))?;
+
+    Ok(LessSafeKey::new(key))
+}
+
+/// Encrypts a refresh token before it's stored on the staff member. Base64 of the random
+/// nonce followed by the ciphertext.
+fn encrypt_token(key: &[u8; 32], token: &str) -> Result<String> {
+    let mut nonce = [0; NONCE_LEN];
+
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| eyre::eyre!("Unable to generate a nonce"))?;
+
+    let mut ciphertext = token.as_bytes().to_vec();
+
+    token_cipher(key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut ciphertext,
+        )
+        .map_err(|_| eyre::eyre!("Unable to encrypt the refresh token"))?;
+
+    Ok(BASE64.encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+fn decrypt_token(key: &[u8; 32], stored: &str) -> Result<String> {
+    let mut stored = BASE64
+        .decode(stored)
+        .wrap_err("Stored refresh token isn't base64")?;
+
+    if stored.len() < NONCE_LEN {
+        return Err(eyre::eyre!("Stored refresh token is too short").into());
+    }
+
+    let (nonce, ciphertext) = stored.split_at_mut(NONCE_LEN);
+
+    let nonce = Nonce::try_assume_unique_for_key(nonce)
+        .map_err(|_| eyre::eyre!("Invalid refresh token nonce"))?;
+
+    let token = token_cipher(key)?
+        .open_in_place(nonce, Aad::empty(), ciphertext)
+        .map_err(|_| {
+            eyre::eyre!("Unable to decrypt the refresh token, the calendar has to be reconnected")
+        })?;
+
+    Ok(String::from_utf8(token.to_vec())?)
+}
+
+/// The calendar a staff member syncs with.
+struct StaffCalendar {
+    refresh_token: String,
+    calendar_id: String,
+}
+
+/// The staff member's calendar, if they connected one and have the sync turned on.
+async fn staff_calendar(
+    state: &AppState,
+    client: &OAuthClient<'_>,
+    uuid: UuidType,
+    staff_id: &str,
+) -> Result<Option<StaffCalendar>> {
+    let staff = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staff"),
+                ns: Some(String::from("@booking")),
+            },
+            staff_id,
+        )
+    })
+    .await?;
+
+    if !row_bool(&staff, "googleSync")?.unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let Some(refresh_token) = row_text(&staff, "googleRefreshToken")?.filter(|v| !v.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    let calendar_id = row_text(&staff, "googleCalendarId")?
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| PRIMARY_CALENDAR.to_string());
+
+    Ok(Some(StaffCalendar {
+        refresh_token: decrypt_token(client.token_key, &refresh_token)?,
+        calendar_id,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+    refresh_token: Option<String>,
+}
+
+async fn request_token(client: &OAuthClient<'_>, params: &[(&str, &str)]) -> Result<TokenResponse> {
+    Ok(CLIENT
+        .post(client.token_url)
+        .form(params)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+async fn access_token(
+    google_calendar: &GoogleCalendar,
+    client: &OAuthClient<'_>,
+    refresh_token: &str,
+) -> Result<String> {
+    let mut tokens = google_calendar.access_tokens.lock().await;
+
+    tokens.retain(|_, (expires_at, _)| *expires_at > Instant::now());
+
+    if let Some((_, token)) = tokens.get(refresh_token) {
+        return Ok(token.clone());
+    }
+
+    let response = request_token(
+        client,
+        &[
+            ("client_id", client.id),
+            ("client_secret", client.secret),
+            ("refresh_token", refresh_token),
+            ("grant_type", "refresh_token"),
+        ],
+    )
+    .await?;
+
+    // Refreshed a minute early so a token doesn't expire mid request.
+    let expires_at = Instant::now() + Duration::from_secs(response.expires_in.saturating_sub(60));
+
+    tokens.insert(
+        refresh_token.to_string(),
+        (expires_at, response.access_token.clone()),
+    );
+
+    Ok(response.access_token)
+}
+
+fn calendar_url(client: &OAuthClient<'_>, segments: &[&str]) -> Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse(client.calendar_api_url).wrap_err("Calendar API URL")?;
+
+    url.path_segments_mut()
+        .map_err(|_| eyre::eyre!("Calendar API URL can't have a path"))?
+        .pop_if_empty()
+        .extend(segments);
+
+    Ok(url)
+}
+
+#[derive(serde::Deserialize)]
+struct FreeBusyResponse {
+    calendars: HashMap<String, FreeBusyCalendar>,
+}
+
+#[derive(serde::Deserialize)]
+struct FreeBusyCalendar {
+    #[serde(default)]
+    busy: Vec<FreeBusyPeriod>,
+}
+
+#[derive(serde::Deserialize)]
+struct FreeBusyPeriod {
+    start: String,
+    end: String,
+}
+
+async fn request_busy_periods(
+    state: &AppState,
+    uuid: UuidType,
+    staff_id: &str,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Result<Vec<(OffsetDateTime, OffsetDateTime)>> {
+    let Some(client) = oauth_client(&state.config) else {
+        return Ok(Vec::new());
+    };
+
+    let Some(calendar) = staff_calendar(state, &client, uuid, staff_id).await? else {
+        return Ok(Vec::new());
+    };
+
+    calendar_busy_periods(&state.google_calendar, &client, &calendar, start, end).await
+}
+
+async fn calendar_busy_periods(
+    google_calendar: &GoogleCalendar,
+    client: &OAuthClient<'_>,
+    calendar: &StaffCalendar,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Result<Vec<(OffsetDateTime, OffsetDateTime)>> {
+    let access_token = access_token(google_calendar, client, &calendar.refresh_token).await?;
+
+    let mut response: FreeBusyResponse = CLIENT
+        .post(calendar_url(client, &["freeBusy"])?)
+        .bearer_auth(access_token)
+        .json(&serde_json::json!({
+            "timeMin": start.format(&Rfc3339)?,
+            "timeMax": end.format(&Rfc3339)?,
+            "items": [{ "id": calendar.calendar_id }],
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    response
+        .calendars
+        .remove(&calendar.calendar_id)
+        .map(|v| v.busy)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|v| {
+            Ok((
+                OffsetDateTime::parse(&v.start, &Rfc3339)?,
+                OffsetDateTime::parse(&v.end, &Rfc3339)?,
+            ))
+        })
+        .collect()
+}
+
+/// Busy periods of the staff member's calendar between `start` & `end`.
+///
+/// Empty when they haven't connected a calendar. Google being unreachable doesn't stop
+/// bookings, so failures are logged and treated as no busy periods.
+pub async fn busy_periods(
+    state: &AppState,
+    uuid: UuidType,
+    staff_id: &str,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+    match request_busy_periods(state, uuid, staff_id, start, end).await {
+        Ok(busy) => busy,
+        Err(e) => {
+            warn!("Unable to get Google Calendar busy periods of staff {staff_id}: {e}");
+            Vec::new()
+        }
+    }
+}
+
+async fn insert_event(
+    state: &AppState,
+    uuid: UuidType,
+    staff_id: &str,
+    summary: &str,
+    description: &str,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Result<()> {
+    let Some(client) = oauth_client(&state.config) else {
+        return Ok(());
+    };
+
+    let Some(calendar) = staff_calendar(state, &client, uuid, staff_id).await? else {
+        return Ok(());
+    };
+
+    let access_token =
+        access_token(&state.google_calendar, &client, &calendar.refresh_token).await?;
+
+    CLIENT
+        .post(calendar_url(
+            &client,
+            &["calendars", &calendar.calendar_id, "events"],
+        )?)
+        .bearer_auth(access_token)
+        .json(&serde_json::json!({
+            "summary": summary,
+            "description": description,
+            "start": { "dateTime": start.format(&Rfc3339)? },
+            "end": { "dateTime": end.format(&Rfc3339)? },
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// Adds a booking to the staff member's calendar in the background, if they synced one.
+pub fn push_booking(
+    state: &AppState,
+    uuid: UuidType,
+    staff_id: String,
+    summary: String,
+    description: String,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) {
+    if oauth_client(&state.config).is_none() {
+        return;
+    }
+
+    let state = state.clone();
+
+    tokio::spawn(async move {
+        if let Err(e) =
+            insert_event(&state, uuid, &staff_id, &summary, &description, start, end).await
+        {
+            warn!("Unable to add booking to the Google Calendar of staff {staff_id}: {e}");
+        }
+    });
+}
+
+async fn update_staff(
+    state: &AppState,
+    uuid: UuidType,
+    staff_id: &str,
+    fields: HashMap<String, SimpleValue>,
+) -> Result<()> {
+    retry::write(&state.config, || {
+        update_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staff"),
+                ns: Some(String::from("@booking")),
+            },
+            staff_id,
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(())
+}
+
+/// Link the staff member follows to connect their Google Calendar.
+pub async fn get_google_calendar_authorize(
+    State(state): State<AppState>,
+    Path((uuid, staff_id)): Path<(UuidType, String)>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let client = oauth_client(&state.config).context("Google Calendar isn't configured")?;
+
+    let authorization = Uuid::now_v7().simple().to_string();
+
+    {
+        let mut pending = state.google_calendar.pending_authorizations.lock().await;
+
+        pending.retain(|_, (created_at, _, _)| created_at.elapsed() < AUTHORIZE_TTL);
+        pending.insert(authorization.clone(), (Instant::now(), uuid, staff_id));
+    }
+
+    let url = reqwest::Url::parse_with_params(
+        AUTHORIZE_URL,
+        [
+            ("client_id", client.id),
+            ("redirect_uri", client.redirect_uri),
+            ("response_type", "code"),
+            ("scope", CALENDAR_SCOPE),
+            // A refresh token is only returned with offline access & when consent is asked for.
+            ("access_type", "offline"),
+            ("prompt", "consent"),
+            ("state", authorization.as_str()),
+        ],
+    )
+    .wrap_err("Google authorize URL")?;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "url": url.to_string(),
+    }))))
+}
+
+#[derive(serde::Deserialize)]
+pub struct GoogleCalendarCallbackQuery {
+    code: String,
+    state: String,
+}
+
+/// Where Google redirects to once the staff member allowed access. Stores the refresh token & turns the sync on.
+pub async fn get_google_calendar_callback(
+    State(app_state): State<AppState>,
+    Query(GoogleCalendarCallbackQuery { code, state }): Query<GoogleCalendarCallbackQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let client = oauth_client(&app_state.config).context("Google Calendar isn't configured")?;
+
+    let (created_at, uuid, staff_id) = app_state
+        .google_calendar
+        .pending_authorizations
+        .lock()
+        .await
+        .remove(&state)
+        .context("Unknown authorization state")?;
+
+    if created_at.elapsed() >= AUTHORIZE_TTL {
+        return Err(eyre::eyre!("The authorization link expired"))?;
+    }
+
+    let response = request_token(
+        &client,
+        &[
+            ("client_id", client.id),
+            ("client_secret", client.secret),
+            ("redirect_uri", client.redirect_uri),
+            ("code", code.as_str()),
+            ("grant_type", "authorization_code"),
+        ],
+    )
+    .await?;
+
+    let refresh_token = response
+        .refresh_token
+        .context("Google didn't return a refresh token")?;
+
+    update_staff(
+        &app_state,
+        uuid,
+        &staff_id,
+        HashMap::from([
+            (
+                String::from("googleRefreshToken"),
+                encrypt_token(client.token_key, &refresh_token)?.into(),
+            ),
+            (String::from("googleSync"), serde_json::json!(true).into()),
+        ]),
+    )
+    .await?;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "staffId": staff_id,
+        "connected": true,
+    }))))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoogleCalendarSettingsJson {
+    /// Whether the calendar blocks slots & receives new bookings.
+    enabled: bool,
+    /// Calendar to sync with. Defaults to the staff member's primary calendar.
+    calendar_id: Option<String>,
+}
+
+/// Turns the sync of a connected calendar on or off.
+pub async fn put_google_calendar(
+    State(state): State<AppState>,
+    Path((uuid, staff_id)): Path<(UuidType, String)>,
+    Json(GoogleCalendarSettingsJson {
+        enabled,
+        calendar_id,
+    }): Json<GoogleCalendarSettingsJson>,
+) -> Result<()> {
+    let mut fields = HashMap::from([(
+        String::from("googleSync"),
+        serde_json::json!(enabled).into(),
+    )]);
+
+    if let Some(calendar_id) = calendar_id {
+        fields.insert(String::from("googleCalendarId"), calendar_id.into());
+    }
+
+    update_staff(&state, uuid, &staff_id, fields).await
+}
+
+/// Disconnects the staff member's calendar, forgetting its refresh token.
+pub async fn delete_google_calendar(
+    State(state): State<AppState>,
+    Path((uuid, staff_id)): Path<(UuidType, String)>,
+) -> Result<()> {
+    update_staff(
+        &state,
+        uuid,
+        &staff_id,
+        HashMap::from([
+            (String::from("googleRefreshToken"), "".into()),
+            (String::from("googleSync"), serde_json::json!(false).into()),
+        ]),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use time::macros::datetime;
+
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn refresh_tokens_are_encrypted() {
+        let stored = encrypt_token(&KEY, "refresh").unwrap();
+
+        assert!(!stored.contains("refresh"));
+        assert_ne!(stored, encrypt_token(&KEY, "refresh").unwrap());
+        assert_eq!(decrypt_token(&KEY, &stored).unwrap(), "refresh");
+    }
+
+    #[test]
+    fn tampered_or_plain_tokens_arent_decrypted() {
+        let mut stored = BASE64
+            .decode(encrypt_token(&KEY, "refresh").unwrap())
+            .unwrap();
+        *stored.last_mut().unwrap() ^= 1;
+
+        assert!(decrypt_token(&KEY, &BASE64.encode(stored)).is_err());
+        assert!(decrypt_token(&[8; 32], &encrypt_token(&KEY, "refresh").unwrap()).is_err());
+        assert!(decrypt_token(&KEY, "1//plain-refresh-token").is_err());
+    }
+
+    /// Serves the token & freeBusy endpoints on a random local port.
+    async fn mock_google() -> String {
+        let app = Router::new()
+            .route(
+                "/token",
+                post(|body: String| async move {
+                    assert!(body.contains("refresh_token=refresh"));
+
+                    Json(serde_json::json!({ "access_token": "access", "expires_in": 3600 }))
+                }),
+            )
+            .route(
+                "/calendar/v3/freeBusy",
+                post(
+                    |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                        if headers["authorization"] != "Bearer access" {
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+
+                        Ok(Json(serde_json::json!({
+                            "calendars": {
+                                body["items"][0]["id"].as_str().unwrap(): {
+                                    "busy": [{
+                                        "start": "2024-05-06T09:00:00Z",
+                                        "end": "2024-05-06T10:30:00Z",
+                                    }],
+                                },
+                            },
+                        })))
+                    },
+                ),
+            );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn busy_periods_are_read_from_the_calendar() {
+        let url = mock_google().await;
+        let token_url = format!("{url}/token");
+        let calendar_api_url = format!("{url}/calendar/v3/");
+
+        let client = OAuthClient {
+            id: "id",
+            secret: "secret",
+            redirect_uri: "https://example.com/callback",
+            token_key: &KEY,
+            token_url: &token_url,
+            calendar_api_url: &calendar_api_url,
+        };
+
+        let calendar = StaffCalendar {
+            refresh_token: String::from("refresh"),
+            calendar_id: String::from("staff@example.com"),
+        };
+
+        let google_calendar = GoogleCalendar::default();
+
+        let busy = calendar_busy_periods(
+            &google_calendar,
+            &client,
+            &calendar,
+            datetime!(2024-05-06 0:00 UTC),
+            datetime!(2024-05-07 0:00 UTC),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            busy,
+            vec![(
+                datetime!(2024-05-06 9:00 UTC),
+                datetime!(2024-05-06 10:30 UTC)
+            )]
+        );
+        assert!(google_calendar
+            .access_tokens
+            .lock()
+            .await
+            .contains_key("refresh"));
+    }
+}
@@ -0,0 +1,93 @@
+/// Strings seeded into a website on install.
+pub struct Labels {
+    pub first_name: &'static str,
+    pub last_name: &'static str,
+    pub email: &'static str,
+    pub phone: &'static str,
+    pub message: &'static str,
+
+    pub form_name: &'static str,
+    pub service_name: &'static str,
+    pub service_category: &'static str,
+}
+
+static EN: Labels = Labels {
+    first_name: "First Name",
+    last_name: "Last Name",
+    email: "Email Address",
+    phone: "Phone Number",
+    message: "Message",
+
+    form_name: "Haircut Service",
+    service_name: "Haircut",
+    service_category: "Hair",
+};
+
+static ES: Labels = Labels {
+    first_name: "Nombre",
+    last_name: "Apellido",
+    email: "Correo Electrónico",
+    phone: "Número de Teléfono",
+    message: "Mensaje",
+
+    form_name: "Servicio de Corte de Pelo",
+    service_name: "Corte de Pelo",
+    service_category: "Cabello",
+};
+
+static FR: Labels = Labels {
+    first_name: "Prénom",
+    last_name: "Nom",
+    email: "Adresse E-mail",
+    phone: "Numéro de Téléphone",
+    message: "Message",
+
+    form_name: "Service de Coupe de Cheveux",
+    service_name: "Coupe de Cheveux",
+    service_category: "Cheveux",
+};
+
+static DE: Labels = Labels {
+    first_name: "Vorname",
+    last_name: "Nachname",
+    email: "E-Mail-Adresse",
+    phone: "Telefonnummer",
+    message: "Nachricht",
+
+    form_name: "Haarschnitt Service",
+    service_name: "Haarschnitt",
+    service_category: "Haare",
+};
+
+/// Labels for a locale such as `es` or `es-MX`. Falls back to English for unknown locales.
+pub fn labels(locale: Option<&str>) -> &'static Labels {
+    let language = locale
+        .and_then(|v| v.split(['-', '_']).next())
+        .map(|v| v.trim().to_lowercase());
+
+    match language.as_deref() {
+        Some("es") => &ES,
+        Some("fr") => &FR,
+        Some("de") => &DE,
+        _ => &EN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_regional_locale_uses_its_language() {
+        assert_eq!(labels(Some("es-MX")).first_name, "Nombre");
+        assert_eq!(labels(Some("fr_CA")).first_name, "Prénom");
+        assert_eq!(labels(Some("DE")).first_name, "Vorname");
+    }
+
+    #[test]
+    fn an_unknown_or_missing_locale_is_english() {
+        assert_eq!(labels(Some("ja")).first_name, "First Name");
+        assert_eq!(labels(Some("")).first_name, "First Name");
+        assert_eq!(labels(None).first_name, "First Name");
+    }
+}
@@ -2,16 +2,20 @@ use std::collections::HashMap;
 
 use webby_addon_common::{
     request::{
-        create_cms_collection, create_website_form, create_website_form_action, CreateWebsiteForm,
-        FormAction, FormActionEmail, FormFieldType, FormType, Layer, LayerInput, LayerInputData,
-        LayerRow,
+        create_cms_collection, create_website_form, create_website_form_action, query_cms_rows,
+        update_cms_row_by_id, CreateWebsiteForm, FormAction, FormActionEmail, FormFieldType,
+        FormType, Layer, LayerInput, LayerInputData, LayerRow,
     },
     InstallResponse, JsonResponse, RegisterNewJson, WrappingResponse,
 };
-use axum::{routing::post, Json, Router};
+use axum::{
+    extract::{Path, Query, State},
+    routing::post,
+    Json, Router,
+};
 use eyre::ContextCompat;
 use webby_global_common::{
-    request::{CmsCreate, CmsCreateDataColumn, CmsUpdate},
+    request::{CmsCreate, CmsCreateDataColumn, CmsQuery, CmsUpdate},
     schema::SchematicFieldType,
     uuid::CollectionName,
     value::{Number, SimpleValue},
@@ -19,13 +23,87 @@ use webby_global_common::{
 use time::macros::format_description;
 use uuid::Uuid;
 
-use crate::Result;
+use crate::{
+    config::Config, retry, row_id, template, validate_email, validate_time_zone, AppState, Error,
+    Result, RECURRENCE_TYPE_RECURRING,
+};
+
+mod locale;
+
+/// Resolves the configured recipients, dropping duplicates and validating each address.
+fn notification_recipients(config: &Config, owner_email: &str) -> Result<Vec<String>> {
+    let values = HashMap::from([("OWNER_EMAIL", owner_email.to_string())]);
+
+    let mut recipients = Vec::new();
 
-pub fn routes() -> Router<()> {
+    for recipient in template::render(&config.notification_recipients, &values).split(',') {
+        let recipient = recipient.trim();
+
+        if recipient.is_empty() || recipients.iter().any(|v| v == recipient) {
+            continue;
+        }
+
+        validate_email(recipient)?;
+
+        recipients.push(recipient.to_string());
+    }
+
+    if recipients.is_empty() {
+        return Err(eyre::eyre!("No notification recipients configured"))?;
+    }
+
+    Ok(recipients)
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallQuery {
+    /// Language of the seeded form and sample service (eg. `es`). Defaults to English.
+    locale: Option<String>,
+    /// Time zone of the seeded staff schedules (eg. `Europe/London`). Defaults to [`Config::default_time_zone`].
+    time_zone: Option<String>,
+}
+
+pub fn routes() -> Router<AppState> {
     Router::new().route("/", post(post_install))
 }
 
+/// The time zone to seed schedules with, falling back to the configured one when none is given.
+fn install_time_zone(config: &Config, time_zone: Option<String>) -> String {
+    time_zone
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| config.default_time_zone.clone())
+}
+
+/// Checks every install input at once, returning the owner's email.
+/// Nothing is created unless every input is valid.
+fn validate_install(config: &Config, time_zone: &str, email: Option<String>) -> Result<String> {
+    let mut errors = Vec::new();
+
+    if let Err(e) = validate_time_zone(time_zone) {
+        errors.push(e.validation_message());
+    }
+
+    let owner_email = email.filter(|v| !v.trim().is_empty()).unwrap_or_default();
+
+    if owner_email.is_empty() {
+        errors.push(String::from("Missing member email"));
+    } else if let Err(e) =
+        validate_email(&owner_email).and_then(|_| notification_recipients(config, &owner_email))
+    {
+        errors.push(e.validation_message());
+    }
+
+    if !errors.is_empty() {
+        return Err(Error::Validation(errors));
+    }
+
+    Ok(owner_email)
+}
+
 async fn post_install(
+    State(state): State<AppState>,
+    Query(InstallQuery { locale, time_zone }): Query<InstallQuery>,
     Json(RegisterNewJson {
         instance_id,
         website_id,
@@ -38,11 +116,642 @@ async fn post_install(
     let date_format = format_description!("[year]-[month]-[day]");
     let time_format = format_description!("[hour]:[minute]:[second].[subsecond]");
 
+    let time_zone = install_time_zone(&state.config, time_zone);
+
+    let owner_email = validate_install(&state.config, &time_zone, member.email.clone())?;
+
+    let labels = locale::labels(locale.as_deref());
+
     // TODO: Ability to wrap requests in a "transaction".
     // Send a the same unique x-transaction-id header with each request.r
     // Store each master copy id w/ ability to delete everything if it fails.
 
-    let mut index = 0;
+    let form_id = create_booking_form(&state, website_id, owner_email, labels).await?;
+
+    retry::write(&state.config, || {
+        create_cms_collection(
+            website_id.into(),
+            CmsCreate {
+                id: CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                name: String::from("Bookings Scheduled"),
+                update: CmsUpdate::default(),
+                columns: Some(vec![
+                    CmsCreateDataColumn {
+                        id: String::from("bookDate"),
+                        name: String::from("Book Date"),
+                        type_of: SchematicFieldType::DateTime,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("bookID"),
+                        name: String::from("Book ID"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("reference"),
+                        name: String::from("Reference"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("clientKey"),
+                        name: String::from("Client Key"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("createdAt"),
+                        name: String::from("Created At"),
+                        type_of: SchematicFieldType::DateTime,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("source"),
+                        name: String::from("Source"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("batchId"),
+                        name: String::from("Batch ID"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("status"),
+                        name: String::from("Status"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("type"),
+                        name: String::from("Type"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("contactUuid"),
+                        name: String::from("Contact UUID"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("duration"),
+                        name: String::from("Duration"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("currency"),
+                        name: String::from("Currency"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("schemaDataUuid"),
+                        name: String::from("Schema Data Uuid"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("depositAmount"),
+                        name: String::from("Deposit Amount"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("balanceDue"),
+                        name: String::from("Balance Due"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("submissionLink"),
+                        name: String::from("Submission Link"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("notes"),
+                        name: String::from("Notes"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("customFields"),
+                        name: String::from("Custom Fields"),
+                        type_of: SchematicFieldType::Object,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("service"),
+                        name: String::from("Service"),
+                        type_of: SchematicFieldType::Reference,
+                        referenced_schema: Some(String::from("@booking:services")),
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("staffMember"),
+                        name: String::from("Staff Member"),
+                        type_of: SchematicFieldType::Reference,
+                        referenced_schema: Some(String::from("@booking:staff")),
+                    },
+                ]),
+                data: None,
+                is_single: true,
+            },
+        )
+    })
+    .await?;
+
+    let services_cms = retry::write(&state.config, || {
+        create_cms_collection(
+            website_id.into(),
+            CmsCreate {
+                id: CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
+                },
+                name: String::from("Bookings Services"),
+                update: CmsUpdate::default(),
+                columns: Some(services_columns()),
+                data: Some(HashMap::from([
+                    (String::from("name"), vec![labels.service_name.into()]),
+                    (String::from("paymentType"), vec!["in_person".into()]),
+                    (String::from("type"), vec!["appointment".into()]),
+                    (
+                        String::from("category"),
+                        vec![labels.service_category.into()],
+                    ),
+                    (String::from("maxParticipants"), vec![1.into()]),
+                    (String::from("minParticipants"), vec![1.into()]),
+                    (String::from("priceAmount"), vec![20.into()]),
+                    (
+                        String::from("currency"),
+                        vec![state.config.default_currency.as_str().into()],
+                    ),
+                    (String::from("formId"), vec![form_id.clone().into()]),
+                ])),
+                is_single: true,
+            },
+        )
+    })
+    .await?;
+
+    let staff_cms = retry::write(&state.config, || {
+        create_cms_collection(
+            website_id.into(),
+            CmsCreate {
+                id: CollectionName {
+                    id: String::from("staff"),
+                    ns: Some(String::from("@booking")),
+                },
+                name: String::from("Bookings Staff"),
+                update: CmsUpdate::default(),
+                columns: Some(vec![
+                    CmsCreateDataColumn {
+                        id: String::from("staffImage"),
+                        name: String::from("Staff Image"),
+                        type_of: SchematicFieldType::Image,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("staffName"),
+                        name: String::from("Staff Name"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("timeZone"),
+                        name: String::from("Time Zone"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("googleSync"),
+                        name: String::from("Google Calendar Sync"),
+                        type_of: SchematicFieldType::Boolean,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("googleCalendarId"),
+                        name: String::from("Google Calendar ID"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("googleRefreshToken"),
+                        name: String::from("Google Refresh Token"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                ]),
+                data: Some(HashMap::from([(
+                    String::from("staffName"),
+                    vec!["Staff Member #1".into()],
+                )])),
+                is_single: true,
+            },
+        )
+    })
+    .await?;
+
+    let staff_ids = staff_cms.data_ids.context("Staff Ids")?;
+    let service_ids = services_cms.data_ids.context("Uploaded Service Ids")?;
+
+    let schedule_cms = retry::write(&state.config, || {
+        create_cms_collection(
+            website_id.into(),
+            CmsCreate {
+                id: CollectionName {
+                    id: String::from("schedule"),
+                    ns: Some(String::from("@booking")),
+                },
+                name: String::from("Bookings Schedule"),
+                update: CmsUpdate::default(),
+                columns: Some(vec![
+                    CmsCreateDataColumn {
+                        id: String::from("break"),
+                        name: String::from("Break"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("duration"),
+                        name: String::from("Duration"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("repeats"),
+                        name: String::from("Repeats"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("slotInterval"),
+                        name: String::from("Slot Interval"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("roundToMinutes"),
+                        name: String::from("Round To Minutes"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("concurrency"),
+                        name: String::from("Concurrency"),
+                        type_of: SchematicFieldType::Number,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("service"),
+                        name: String::from("Service"),
+                        type_of: SchematicFieldType::Reference,
+                        referenced_schema: Some(String::from("@booking:services")),
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("serviceSchedule"),
+                        name: String::from("Service Schedule"),
+                        type_of: SchematicFieldType::Object,
+                        referenced_schema: None,
+                    },
+                ]),
+                data: Some(HashMap::from([
+                    (String::from("break"), vec![15.into()]),
+                    (String::from("duration"), vec![45.into()]),
+                    // Repeat forever
+                    (String::from("repeats"), vec![0.into()]),
+                    (
+                        String::from("service"),
+                        vec![service_ids[0].to_string().into()],
+                    ),
+                    (
+                        String::from("serviceSchedule"),
+                        vec![SimpleValue::ObjectUnknown(serde_json::json!({
+                            "fri": [], "mon": [], "sat": [], "sun": [], "thu": [], "tue": [], "wed": []
+                        }))],
+                    ),
+                ])),
+                is_single: true,
+            },
+        )
+    })
+    .await?;
+
+    let schedule_ids = schedule_cms.data_ids.context("Schedule Ids")?;
+
+    const DAYS: [&str; 5] = ["MONDAY", "TUESDAY", "WEDNESDAY", "THURSDAY", "FRIDAY"];
+
+    retry::write(&state.config, || {
+        create_cms_collection(
+            website_id.into(),
+            CmsCreate {
+                id: CollectionName {
+                    id: String::from("staffSchedule"),
+                    ns: Some(String::from("@booking")),
+                },
+                name: String::from("Bookings Staff Schedule"),
+                update: CmsUpdate::default(),
+                columns: Some(vec![
+                    CmsCreateDataColumn {
+                        id: String::from("name"),
+                        name: String::from("Name"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("start"),
+                        name: String::from("Start"),
+                        type_of: SchematicFieldType::Time,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("end"),
+                        name: String::from("End"),
+                        type_of: SchematicFieldType::Time,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("recurrenceRule"),
+                        name: String::from("Recurrence Rule"),
+                        type_of: SchematicFieldType::Object,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("recurrenceType"),
+                        name: String::from("Recurrence Type"),
+                        type_of: SchematicFieldType::Tags,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("schedule"),
+                        name: String::from("Schedule"),
+                        type_of: SchematicFieldType::Reference,
+                        referenced_schema: Some(String::from("@booking:schedule")),
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("staff"),
+                        name: String::from("Staff"),
+                        type_of: SchematicFieldType::Reference,
+                        referenced_schema: Some(String::from("@booking:staff")),
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("startDay"),
+                        name: String::from("Start Day"),
+                        type_of: SchematicFieldType::Date,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("endDay"),
+                        name: String::from("End Day"),
+                        type_of: SchematicFieldType::Date,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("breaks"),
+                        name: String::from("Breaks"),
+                        type_of: SchematicFieldType::Object,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("adHocDates"),
+                        name: String::from("Ad Hoc Dates"),
+                        type_of: SchematicFieldType::Object,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("timeZone"),
+                        name: String::from("Time Zone"),
+                        type_of: SchematicFieldType::Text,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("type"),
+                        name: String::from("Type"),
+                        type_of: SchematicFieldType::Tags,
+                        referenced_schema: None,
+                    },
+                    CmsCreateDataColumn {
+                        id: String::from("active"),
+                        name: String::from("Active"),
+                        type_of: SchematicFieldType::Boolean,
+                        referenced_schema: None,
+                    },
+                ]),
+                data: Some(HashMap::from([
+                    (
+                        String::from("name"),
+                        (0..DAYS.len())
+                            .map(|_| "business".into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("active"),
+                        (0..DAYS.len())
+                            .map(|_| serde_json::json!(true).into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("breaks"),
+                        (0..DAYS.len())
+                            .map(|_| serde_json::json!([]).into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("timeZone"),
+                        (0..DAYS.len())
+                            .map(|_| time_zone.as_str().into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("startDay"),
+                        (0..DAYS.len())
+                            .map(|i| {
+                                time::Date::parse("2024-12-02", &date_format)
+                                    .unwrap()
+                                    .replace_day(2 + i as u8)
+                                    .unwrap()
+                                    .into()
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("start"),
+                        (0..DAYS.len())
+                            .map(|_| {
+                                time::Time::parse("10:00:00.0", &time_format)
+                                    .unwrap()
+                                    .into()
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("end"),
+                        (0..DAYS.len())
+                            .map(|_| {
+                                time::Time::parse("18:00:00.0", &time_format)
+                                    .unwrap()
+                                    .into()
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("schedule"),
+                        (0..DAYS.len())
+                            .map(|_| schedule_ids[0].to_string().into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("staff"),
+                        (0..DAYS.len())
+                            .map(|_| staff_ids[0].to_string().into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("recurrenceType"),
+                        (0..DAYS.len())
+                            .map(|_| RECURRENCE_TYPE_RECURRING.into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("type"),
+                        (0..DAYS.len())
+                            .map(|_| "WORKING_HOURS".into())
+                            .collect::<Vec<_>>(),
+                    ),
+                    (
+                        String::from("recurrenceRule"),
+                        DAYS.iter()
+                            .map(|day| {
+                                serde_json::from_str::<serde_json::Value>(&format!(
+                                r#"{{ "days": [ "{day}" ], "frequency": "WEEKLY", "interval": 1 }}"#
+                            ))
+                            .unwrap()
+                            .into()
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                ])),
+                is_single: false,
+            },
+        )
+    })
+    .await?;
+
+    Ok(Json(WrappingResponse::okay(InstallResponse::Complete)))
+}
+
+/// Columns of the services collection. Ids are camelCase like every other collection's.
+fn services_columns() -> Vec<CmsCreateDataColumn> {
+    vec![
+        CmsCreateDataColumn {
+            id: String::from("description"),
+            name: String::from("Description"),
+            type_of: SchematicFieldType::Text,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("image"),
+            name: String::from("Image"),
+            type_of: SchematicFieldType::Image,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("maxParticipants"),
+            name: String::from("Max Participants"),
+            type_of: SchematicFieldType::Number,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("minParticipants"),
+            name: String::from("Min Participants"),
+            type_of: SchematicFieldType::Number,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("cancelCutoffHours"),
+            name: String::from("Cancel Cutoff Hours"),
+            type_of: SchematicFieldType::Number,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("maxPerContactPerDay"),
+            name: String::from("Max Per Contact Per Day"),
+            type_of: SchematicFieldType::Number,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("name"),
+            name: String::from("Name"),
+            type_of: SchematicFieldType::Text,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("paymentType"),
+            name: String::from("Payment Type"),
+            type_of: SchematicFieldType::Text,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("priceAmount"),
+            name: String::from("Price Amount"),
+            type_of: SchematicFieldType::Number,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("depositAmount"),
+            name: String::from("Deposit Amount"),
+            type_of: SchematicFieldType::Number,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("currency"),
+            name: String::from("Currency"),
+            type_of: SchematicFieldType::Text,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("type"),
+            name: String::from("Type"),
+            type_of: SchematicFieldType::Text,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("category"),
+            name: String::from("Category"),
+            type_of: SchematicFieldType::Text,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("durationOptions"),
+            name: String::from("Duration Options"),
+            type_of: SchematicFieldType::Object,
+            referenced_schema: None,
+        },
+        CmsCreateDataColumn {
+            id: String::from("formId"),
+            name: String::from("Form"),
+            type_of: SchematicFieldType::Text,
+            // TODO: Somehow reference Forms here.
+            referenced_schema: None,
+        },
+    ]
+}
+
+/// Creates the booking contact form and the email sent when it's submitted. Returns the form id.
+async fn create_booking_form(
+    state: &AppState,
+    website_id: Uuid,
+    owner_email: String,
+    labels: &locale::Labels,
+) -> Result<String> {
+    let send_to = notification_recipients(&state.config, &owner_email)?;
 
     fn gen_id(field_type: FormFieldType, index: &mut usize) -> String {
         *index += 1;
@@ -50,521 +759,362 @@ async fn post_install(
         format!("{}{index}", field_type.to_string())
     }
 
-    let form = create_website_form(
-        website_id,
-        CreateWebsiteForm {
-            name: Some(String::from("Haircut Service")),
-            type_of: FormType::Contact,
-            addon_uuid: Some(Uuid::from_u128(0x01938f4ff50c72039f89b367e9d49efbu128)),
-            layers: Some(vec![Layer {
-                id: String::new(),
-                name: None,
-                rows: vec![
-                    LayerRow {
-                        id: String::new(),
-                        items: vec![
-                            LayerInput::Input {
-                                id: gen_id(FormFieldType::FirstName, &mut index),
-                                key: Some(String::from("firstName")),
-                                type_of: FormFieldType::FirstName,
-                                offset: 0,
-                                size: 8,
-                                data: LayerInputData::Text {
-                                    hidden: false,
-                                    required: true,
-                                    field_title: None,
-                                    placeholder_text: Some(String::from("First Name")),
-                                    field_description: None,
-                                    personal_info: false,
-                                    limit: Some((Number::Integer(0), Number::Integer(30))),
+    let form = retry::write(&state.config, || {
+        let mut index = 0;
+
+        create_website_form(
+            website_id,
+            CreateWebsiteForm {
+                name: Some(String::from(labels.form_name)),
+                type_of: FormType::Contact,
+                addon_uuid: Some(Uuid::from_u128(0x01938f4ff50c72039f89b367e9d49efbu128)),
+                layers: Some(vec![Layer {
+                    id: String::new(),
+                    name: None,
+                    rows: vec![
+                        LayerRow {
+                            id: String::new(),
+                            items: vec![
+                                LayerInput::Input {
+                                    id: gen_id(FormFieldType::FirstName, &mut index),
+                                    key: Some(String::from("firstName")),
+                                    type_of: FormFieldType::FirstName,
+                                    offset: 0,
+                                    size: 8,
+                                    data: LayerInputData::Text {
+                                        hidden: false,
+                                        required: true,
+                                        field_title: None,
+                                        placeholder_text: Some(String::from(labels.first_name)),
+                                        field_description: None,
+                                        personal_info: false,
+                                        limit: Some((Number::Integer(0), Number::Integer(30))),
+                                    },
                                 },
-                            },
-                            LayerInput::Input {
-                                id: gen_id(FormFieldType::LastName, &mut index),
-                                key: Some(String::from("lastName")),
-                                type_of: FormFieldType::LastName,
-                                offset: 8,
-                                size: 8,
-                                data: LayerInputData::Text {
-                                    hidden: false,
-                                    required: true,
-                                    field_title: None,
-                                    placeholder_text: Some(String::from("Last Name")),
-                                    field_description: None,
-                                    personal_info: false,
-                                    limit: Some((Number::Integer(0), Number::Integer(30))),
+                                LayerInput::Input {
+                                    id: gen_id(FormFieldType::LastName, &mut index),
+                                    key: Some(String::from("lastName")),
+                                    type_of: FormFieldType::LastName,
+                                    offset: 8,
+                                    size: 8,
+                                    data: LayerInputData::Text {
+                                        hidden: false,
+                                        required: true,
+                                        field_title: None,
+                                        placeholder_text: Some(String::from(labels.last_name)),
+                                        field_description: None,
+                                        personal_info: false,
+                                        limit: Some((Number::Integer(0), Number::Integer(30))),
+                                    },
                                 },
-                            },
-                        ],
-                    },
-                    LayerRow {
-                        id: String::new(),
-                        items: vec![
-                            LayerInput::Input {
-                                id: gen_id(FormFieldType::Email, &mut index),
-                                key: Some(String::from("email")),
-                                type_of: FormFieldType::Email,
-                                offset: 0,
-                                size: 8,
-                                data: LayerInputData::Text {
-                                    hidden: false,
-                                    required: true,
-                                    field_title: None,
-                                    placeholder_text: Some(String::from("Email Address")),
-                                    field_description: None,
-                                    personal_info: false,
-                                    limit: Some((Number::Integer(0), Number::Integer(60))),
+                            ],
+                        },
+                        LayerRow {
+                            id: String::new(),
+                            items: vec![
+                                LayerInput::Input {
+                                    id: gen_id(FormFieldType::Email, &mut index),
+                                    key: Some(String::from("email")),
+                                    type_of: FormFieldType::Email,
+                                    offset: 0,
+                                    size: 8,
+                                    data: LayerInputData::Text {
+                                        hidden: false,
+                                        required: true,
+                                        field_title: None,
+                                        placeholder_text: Some(String::from(labels.email)),
+                                        field_description: None,
+                                        personal_info: false,
+                                        limit: Some((Number::Integer(0), Number::Integer(60))),
+                                    },
                                 },
-                            },
-                            LayerInput::Input {
-                                id: gen_id(FormFieldType::Phone, &mut index),
-                                key: Some(String::from("phone")),
-                                type_of: FormFieldType::Phone,
-                                offset: 8,
-                                size: 8,
+                                LayerInput::Input {
+                                    id: gen_id(FormFieldType::Phone, &mut index),
+                                    key: Some(String::from("phone")),
+                                    type_of: FormFieldType::Phone,
+                                    offset: 8,
+                                    size: 8,
+                                    data: LayerInputData::Text {
+                                        hidden: false,
+                                        required: false,
+                                        field_title: None,
+                                        placeholder_text: Some(String::from(labels.phone)),
+                                        field_description: None,
+                                        personal_info: false,
+                                        limit: None,
+                                    },
+                                },
+                            ],
+                        },
+                        LayerRow {
+                            id: String::new(),
+                            items: vec![LayerInput::Input {
+                                id: gen_id(FormFieldType::LongText, &mut index),
+                                key: Some(String::from("message")),
+                                type_of: FormFieldType::LongText,
+                                offset: 0,
+                                size: 16,
                                 data: LayerInputData::Text {
                                     hidden: false,
                                     required: false,
                                     field_title: None,
-                                    placeholder_text: Some(String::from("Phone Number")),
+                                    placeholder_text: Some(String::from(labels.message)),
                                     field_description: None,
                                     personal_info: false,
                                     limit: None,
                                 },
-                            },
-                        ],
-                    },
-                    LayerRow {
-                        id: String::new(),
-                        items: vec![LayerInput::Input {
-                            id: gen_id(FormFieldType::LongText, &mut index),
-                            key: Some(String::from("message")),
-                            type_of: FormFieldType::LongText,
-                            offset: 0,
-                            size: 16,
-                            data: LayerInputData::Text {
-                                hidden: false,
-                                required: false,
-                                field_title: None,
-                                placeholder_text: Some(String::from("Message")),
-                                field_description: None,
-                                personal_info: false,
-                                limit: None,
-                            },
-                        }],
-                    },
-                ],
-            }]),
-            conditions: None,
-        },
-    )
+                            }],
+                        },
+                    ],
+                }]),
+                conditions: None,
+            },
+        )
+    })
     .await?;
 
-    create_website_form_action(
-        website_id,
-        form.id,
-        FormAction::Email(FormActionEmail {
-            subject: String::from("You received a new booking for {{bookingDateTime}}!"),
-            // TODO: Replace w/ String::from("{{OWNER_EMAIL}}")
-            send_to: vec![member.email.clone().context("Member Email")?],
-            from_name: member.email.clone().context("Member Email")?,
-            from_email: vec!["noreply@dinko.space".to_string()],
-            reply_to_email: "noreply@dinko.space".to_string(),
-            body: String::from("{{SUBMISSION_LINK}}"),
-            attachments: Vec::new(),
-        }),
-    )
+    retry::write(&state.config, || {
+        create_website_form_action(
+            website_id,
+            form.id,
+            FormAction::Email(email_action(&state.config, send_to.clone(), &owner_email)),
+        )
+    })
     .await?;
 
-    create_cms_collection(
-        website_id.into(),
-        CmsCreate {
-            id: CollectionName {
-                id: String::from("bookings"),
-                ns: Some(String::from("@booking")),
-            },
-            name: String::from("Bookings Scheduled"),
-            update: CmsUpdate::default(),
-            columns: Some(vec![
-                CmsCreateDataColumn {
-                    id: String::from("bookDate"),
-                    name: String::from("Book Date"),
-                    type_of: SchematicFieldType::DateTime,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("bookID"),
-                    name: String::from("Book ID"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("contactUuid"),
-                    name: String::from("Contact UUID"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("duration"),
-                    name: String::from("Duration"),
-                    type_of: SchematicFieldType::Number,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("schemaDataUuid"),
-                    name: String::from("Schema Data Uuid"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("service"),
-                    name: String::from("Service"),
-                    type_of: SchematicFieldType::Reference,
-                    referenced_schema: Some(String::from("@booking:services")),
-                },
-                CmsCreateDataColumn {
-                    id: String::from("staffMember"),
-                    name: String::from("Staff Member"),
-                    type_of: SchematicFieldType::Reference,
-                    referenced_schema: Some(String::from("@booking:staff")),
-                },
-            ]),
-            data: None,
-            is_single: true,
-        },
+    Ok(form.id.to_string())
+}
+
+/// Emails each booking submission to `send_to`, sent from the configured addresses.
+fn email_action(config: &Config, send_to: Vec<String>, owner_email: &str) -> FormActionEmail {
+    FormActionEmail {
+        subject: String::from(template::EMAIL_SUBJECT_TEMPLATE),
+        send_to,
+        from_name: owner_email.to_string(),
+        from_email: vec![config.from_email.clone()],
+        reply_to_email: config.reply_to_email.clone(),
+        body: String::from(template::EMAIL_BODY_TEMPLATE),
+        // TODO: Attach the booking's `.ics` once ICS generation exists. The action is
+        // created once per form, so the attachment also needs a per-submission hook.
+        attachments: Vec::new(),
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegenerateFormJson {
+    owner_email: String,
+    /// Language of the form's labels (eg. `es`). Defaults to English.
+    locale: Option<String>,
+}
+
+/// Recreates the booking form & its email action, pointing every service at the new form.
+///
+/// The previous form is left in place so submissions already made to it are kept.
+pub async fn post_form_regenerate(
+    State(state): State<AppState>,
+    Path(website_id): Path<Uuid>,
+    Json(RegenerateFormJson {
+        owner_email,
+        locale,
+    }): Json<RegenerateFormJson>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    validate_email(&owner_email)?;
+
+    let form_id = create_booking_form(
+        &state,
+        website_id,
+        owner_email,
+        locale::labels(locale.as_deref()),
     )
     .await?;
 
-    let services_cms = create_cms_collection(
-        website_id.into(),
-        CmsCreate {
-            id: CollectionName {
+    let services = retry::read(&state.config, || {
+        query_cms_rows(
+            website_id.into(),
+            CollectionName {
                 id: String::from("services"),
                 ns: Some(String::from("@booking")),
             },
-            name: String::from("Bookings Services"),
-            update: CmsUpdate::default(),
-            columns: Some(vec![
-                CmsCreateDataColumn {
-                    id: String::from("description"),
-                    name: String::from("Description"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("image"),
-                    name: String::from("Image"),
-                    type_of: SchematicFieldType::Image,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("maxParticipants"),
-                    name: String::from("Max Participants"),
-                    type_of: SchematicFieldType::Number,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("NAME"),
-                    name: String::from("Name"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("paymentType"),
-                    name: String::from("Payment Type"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("priceAmount"),
-                    name: String::from("Price Amount"),
-                    type_of: SchematicFieldType::Number,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("type"),
-                    name: String::from("Type"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("formId"),
-                    name: String::from("Form"),
-                    type_of: SchematicFieldType::Text,
-                    // TODO: Somehow reference Forms here.
-                    referenced_schema: None,
-                },
-            ]),
-            data: Some(HashMap::from([
-                (String::from("name"), vec!["Haircut".into()]),
-                (String::from("paymentType"), vec!["in_person".into()]),
-                (String::from("type"), vec!["appointment".into()]),
-                (String::from("maxParticipants"), vec![1.into()]),
-                (String::from("priceAmount"), vec![20.into()]),
-                (String::from("formId"), vec![form.id.to_string().into()]),
-            ])),
-            is_single: true,
-        },
-    )
+            CmsQuery::default(),
+        )
+    })
     .await?;
 
-    let staff_cms = create_cms_collection(
-        website_id.into(),
-        CmsCreate {
-            id: CollectionName {
-                id: String::from("staff"),
-                ns: Some(String::from("@booking")),
-            },
-            name: String::from("Bookings Staff"),
-            update: CmsUpdate::default(),
-            columns: Some(vec![
-                CmsCreateDataColumn {
-                    id: String::from("staffImage"),
-                    name: String::from("Staff Image"),
-                    type_of: SchematicFieldType::Image,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("staffName"),
-                    name: String::from("Staff Name"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-            ]),
-            data: Some(HashMap::from([(
-                String::from("staffName"),
-                vec!["Staff Member #1".into()],
-            )])),
-            is_single: true,
-        },
-    )
-    .await?;
+    let mut updated = Vec::new();
 
-    let staff_ids = staff_cms.data_ids.context("Staff Ids")?;
-    let service_ids = services_cms.data_ids.context("Uploaded Service Ids")?;
+    for row in &services.items {
+        let id = row_id(row)?;
 
-    let schedule_cms = create_cms_collection(
-        website_id.into(),
-        CmsCreate {
-            id: CollectionName {
-                id: String::from("schedule"),
-                ns: Some(String::from("@booking")),
-            },
-            name: String::from("Bookings Schedule"),
-            update: CmsUpdate::default(),
-            columns: Some(vec![
-                CmsCreateDataColumn {
-                    id: String::from("break"),
-                    name: String::from("Break"),
-                    type_of: SchematicFieldType::Number,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("duration"),
-                    name: String::from("Duration"),
-                    type_of: SchematicFieldType::Number,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("repeats"),
-                    name: String::from("Repeats"),
-                    type_of: SchematicFieldType::Number,
-                    referenced_schema: None,
+        retry::write(&state.config, || {
+            update_cms_row_by_id(
+                website_id.into(),
+                CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
                 },
-                CmsCreateDataColumn {
-                    id: String::from("service"),
-                    name: String::from("Service"),
-                    type_of: SchematicFieldType::Reference,
-                    referenced_schema: Some(String::from("@booking:services")),
-                },
-                CmsCreateDataColumn {
-                    id: String::from("serviceSchedule"),
-                    name: String::from("Service Schedule"),
-                    type_of: SchematicFieldType::Object,
-                    referenced_schema: None,
-                },
-            ]),
-            data: Some(HashMap::from([
-                (String::from("break"), vec![15.into()]),
-                (String::from("duration"), vec![45.into()]),
-                (String::from("repeats"), vec![1.into()]),
-                (
-                    String::from("service"),
-                    vec![service_ids[0].to_string().into()],
-                ),
-                (
-                    String::from("serviceSchedule"),
-                    vec![SimpleValue::ObjectUnknown(serde_json::json!({
-                        "fri": [], "mon": [], "sat": [], "sun": [], "thu": [], "tue": [], "wed": []
-                    }))],
-                ),
-            ])),
-            is_single: true,
-        },
-    )
-    .await?;
+                &id,
+                HashMap::from([(String::from("formId"), form_id.clone().into())]),
+            )
+        })
+        .await?;
 
-    let schedule_ids = schedule_cms.data_ids.context("Schedule Ids")?;
+        updated.push(id);
+    }
 
-    const DAYS: [&str; 5] = ["MONDAY", "TUESDAY", "WEDNESDAY", "THURSDAY", "FRIDAY"];
+    // `formId` is part of the available hours.
+    state.availability_cache.invalidate(website_id.into()).await;
 
-    create_cms_collection(
-        website_id.into(),
-        CmsCreate {
-            id: CollectionName {
-                id: String::from("staffSchedule"),
-                ns: Some(String::from("@booking")),
-            },
-            name: String::from("Bookings Staff Schedule"),
-            update: CmsUpdate::default(),
-            columns: Some(vec![
-                CmsCreateDataColumn {
-                    id: String::from("name"),
-                    name: String::from("Name"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("start"),
-                    name: String::from("Start"),
-                    type_of: SchematicFieldType::Time,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("end"),
-                    name: String::from("End"),
-                    type_of: SchematicFieldType::Time,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("recurrenceRule"),
-                    name: String::from("Recurrence Rule"),
-                    type_of: SchematicFieldType::Object,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("recurrenceType"),
-                    name: String::from("Recurrence Type"),
-                    type_of: SchematicFieldType::Tags,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("schedule"),
-                    name: String::from("Schedule"),
-                    type_of: SchematicFieldType::Reference,
-                    referenced_schema: Some(String::from("@booking:schedule")),
-                },
-                CmsCreateDataColumn {
-                    id: String::from("staff"),
-                    name: String::from("Staff"),
-                    type_of: SchematicFieldType::Reference,
-                    referenced_schema: Some(String::from("@booking:staff")),
-                },
-                CmsCreateDataColumn {
-                    id: String::from("startDay"),
-                    name: String::from("Start Day"),
-                    type_of: SchematicFieldType::Date,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("timeZone"),
-                    name: String::from("Time Zone"),
-                    type_of: SchematicFieldType::Text,
-                    referenced_schema: None,
-                },
-                CmsCreateDataColumn {
-                    id: String::from("type"),
-                    name: String::from("Type"),
-                    type_of: SchematicFieldType::Tags,
-                    referenced_schema: None,
-                },
-            ]),
-            data: Some(HashMap::from([
-                (
-                    String::from("name"),
-                    (0..DAYS.len())
-                        .map(|_| "business".into())
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("timeZone"),
-                    (0..DAYS.len())
-                        .map(|_| "America/Los_Angeles".into())
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("startDay"),
-                    (0..DAYS.len())
-                        .map(|i| {
-                            time::Date::parse("2024-12-02", &date_format)
-                                .unwrap()
-                                .replace_day(2 + i as u8)
-                                .unwrap()
-                                .into()
-                        })
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("start"),
-                    (0..DAYS.len())
-                        .map(|_| {
-                            time::Time::parse("10:00:00.0", &time_format)
-                                .unwrap()
-                                .into()
-                        })
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("end"),
-                    (0..DAYS.len())
-                        .map(|_| {
-                            time::Time::parse("18:00:00.0", &time_format)
-                                .unwrap()
-                                .into()
-                        })
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("schedule"),
-                    (0..DAYS.len())
-                        .map(|_| schedule_ids[0].to_string().into())
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("staff"),
-                    (0..DAYS.len())
-                        .map(|_| staff_ids[0].to_string().into())
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("recurrenceType"),
-                    (0..DAYS.len())
-                        .map(|_| "INSTANCE".into())
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("type"),
-                    (0..DAYS.len())
-                        .map(|_| "WORKING_HOURS".into())
-                        .collect::<Vec<_>>(),
-                ),
-                (
-                    String::from("recurrenceRule"),
-                    DAYS.iter()
-                        .map(|day| {
-                            serde_json::from_str::<serde_json::Value>(&format!(
-                                r#"{{ "days": [ "{day}" ], "frequency": "WEEKLY", "interval": 1 }}"#
-                            ))
-                            .unwrap()
-                            .into()
-                        })
-                        .collect::<Vec<_>>(),
-                ),
-            ])),
-            is_single: false,
-        },
-    )
-    .await?;
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "formId": form_id,
+        "services": updated,
+    }))))
+}
 
-    Ok(Json(WrappingResponse::okay(InstallResponse::Complete)))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_recipients(recipients: &str) -> Config {
+        Config::from_lookup(|name| {
+            (name == "BOOKING_NOTIFICATION_RECIPIENTS").then(|| recipients.to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn schedules_are_seeded_in_the_configured_time_zone_by_default() {
+        let config = Config::from_lookup(|name| {
+            (name == "BOOKING_DEFAULT_TIME_ZONE").then(|| String::from("Europe/Zagreb"))
+        })
+        .unwrap();
+
+        assert_eq!(install_time_zone(&config, None), "Europe/Zagreb");
+        assert_eq!(
+            install_time_zone(&config, Some(String::from(" "))),
+            "Europe/Zagreb"
+        );
+        assert_eq!(
+            install_time_zone(&config, Some(String::from("Europe/London"))),
+            "Europe/London"
+        );
+    }
+
+    #[test]
+    fn every_invalid_install_input_is_listed() {
+        let config = Config::from_lookup(|_| None).unwrap();
+
+        let Err(Error::Validation(errors)) = validate_install(&config, "Mars/Base", None) else {
+            panic!("expected a validation error");
+        };
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors[0].contains("Mars/Base"), "{errors:?}");
+        assert_eq!(errors[1], "Missing member email");
+
+        assert_eq!(
+            validate_install(
+                &config,
+                "Europe/London",
+                Some(String::from("owner@example.com"))
+            )
+            .unwrap(),
+            "owner@example.com"
+        );
+    }
+
+    #[test]
+    fn submissions_are_emailed_from_the_configured_addresses() {
+        let config = Config::from_lookup(|name| match name {
+            "BOOKING_FROM_EMAIL" => Some(String::from("bookings@example.com")),
+            "BOOKING_REPLY_TO_EMAIL" => Some(String::from("front@example.com")),
+            _ => None,
+        })
+        .unwrap();
+
+        let email = email_action(
+            &config,
+            vec![String::from("owner@example.com")],
+            "owner@example.com",
+        );
+
+        assert_eq!(email.from_email, ["bookings@example.com"]);
+        assert_eq!(email.reply_to_email, "front@example.com");
+        assert_eq!(email.send_to, ["owner@example.com"]);
+    }
+
+    #[test]
+    fn the_owner_and_extra_recipients_are_emailed_once() {
+        let config =
+            config_with_recipients("{{OWNER_EMAIL}}, front@example.com,,owner@example.com");
+
+        assert_eq!(
+            notification_recipients(&config, "owner@example.com").unwrap(),
+            ["owner@example.com", "front@example.com"]
+        );
+    }
+
+    #[test]
+    fn invalid_or_missing_recipients_are_errors() {
+        assert!(notification_recipients(
+            &config_with_recipients("not-an-email"),
+            "owner@example.com"
+        )
+        .is_err());
+        assert!(
+            notification_recipients(&config_with_recipients(" , "), "owner@example.com").is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn a_form_isnt_regenerated_for_an_invalid_owner_email() {
+        use axum::{body::Body, routing::post};
+        use tower::ServiceExt;
+
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+
+        let response = Router::new()
+            .route("/:uuid/form/regenerate", post(post_form_regenerate))
+            .with_state(state)
+            .oneshot(
+                axum::http::Request::builder()
+                    .method("POST")
+                    .uri("/01938f4f-f50c-7203-9f89-b367e9d49efb/form/regenerate")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({ "ownerEmail": "owner" }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(!response.status().is_success());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+
+        // Rejected before the CMS, which isn't reachable here, is called.
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid email address: owner"));
+    }
+
+    #[test]
+    fn service_columns_are_camel_case() {
+        let ids = services_columns()
+            .into_iter()
+            .map(|column| column.id)
+            .collect::<Vec<_>>();
+
+        // Read from every service, eg. for `serviceName` in availability.
+        assert!(ids.iter().any(|id| id == "name"), "{ids:?}");
+        assert!(
+            ids.iter().all(|id| id.starts_with(char::is_lowercase)),
+            "{ids:?}"
+        );
+    }
 }
@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use axum::http::HeaderMap;
+use tokio::sync::Mutex;
+use webby_global_common::uuid::UuidType;
+
+use crate::{Error, ErrorCode, Result};
+
+/// How long a completed form-process request is remembered by its `Idempotency-Key`.
+const IDEMPOTENCY_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// The request's `Idempotency-Key`, scoped by endpoint & website.
+pub fn key(scope: &str, uuid: UuidType, headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(|key| format!("{scope}:{uuid:?}:{key}"))
+}
+
+struct Completed {
+    at: Instant,
+    /// Identifies the request the key was first used for, eg. its slot & contact.
+    fingerprint: String,
+    response: serde_json::Value,
+}
+
+/// Completed `Idempotency-Key`s and their responses.
+#[derive(Default)]
+pub struct IdempotencyStore {
+    keys: Mutex<HashMap<String, Completed>>,
+}
+
+impl IdempotencyStore {
+    /// Returns the original response if a request with this key already completed within the TTL.
+    ///
+    /// Errors if the key was used for a different request.
+    pub async fn replay(
+        &self,
+        key: Option<&String>,
+        fingerprint: &str,
+    ) -> Result<Option<serde_json::Value>> {
+        let Some(key) = key else {
+            return Ok(None);
+        };
+
+        let mut keys = self.keys.lock().await;
+
+        keys.retain(|_, completed| completed.at.elapsed() < IDEMPOTENCY_TTL);
+
+        let Some(completed) = keys.get(key) else {
+            return Ok(None);
+        };
+
+        if completed.fingerprint != fingerprint {
+            return Err(Error::coded(
+                ErrorCode::IdempotencyKeyReused,
+                "The Idempotency-Key was already used for a different request",
+            ));
+        }
+
+        Ok(Some(completed.response.clone()))
+    }
+
+    pub async fn store(
+        &self,
+        key: Option<String>,
+        fingerprint: String,
+        response: serde_json::Value,
+    ) {
+        if let Some(key) = key {
+            self.keys.lock().await.insert(
+                key,
+                Completed {
+                    at: Instant::now(),
+                    fingerprint,
+                    response,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replays_the_same_request() {
+        let store = IdempotencyStore::default();
+        let key = Some(String::from("after:1:abc"));
+
+        assert!(store.replay(key.as_ref(), "a").await.unwrap().is_none());
+
+        store
+            .store(
+                key.clone(),
+                String::from("a"),
+                serde_json::json!({ "reference": "R1" }),
+            )
+            .await;
+
+        assert_eq!(
+            store.replay(key.as_ref(), "a").await.unwrap(),
+            Some(serde_json::json!({ "reference": "R1" }))
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_a_key_reused_for_another_request() {
+        let store = IdempotencyStore::default();
+        let key = Some(String::from("after:1:abc"));
+
+        store
+            .store(key.clone(), String::from("a"), serde_json::json!({}))
+            .await;
+
+        assert!(matches!(
+            store.replay(key.as_ref(), "b").await,
+            Err(Error::Coded {
+                code: ErrorCode::IdempotencyKeyReused,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_key_are_not_replayed() {
+        let store = IdempotencyStore::default();
+
+        store
+            .store(None, String::from("a"), serde_json::json!({}))
+            .await;
+
+        assert!(store.replay(None, "a").await.unwrap().is_none());
+    }
+}
@@ -1,16 +1,25 @@
+// The OpenAPI document is one large `json!`.
+#![recursion_limit = "256"]
+
 #[macro_use]
 extern crate tracing;
 
-use std::{collections::HashMap, net::SocketAddr, sync::LazyLock};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+};
 
 use webby_addon_common::{
     register_call_token,
     request::{get_cms_row_by_id, import_data_row, query_cms_rows},
-    JsonResponse, ListResponse, WrappingResponse,
+    JsonResponse, WrappingResponse,
 };
 use axum::{
-    extract::{Path, Query},
-    routing::{get, post},
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
+    middleware,
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use eyre::ContextCompat;
@@ -21,20 +30,48 @@ use webby_global_common::{
     schema::SchematicFieldKey,
     tz::find_offset_by_id,
     uuid::{CollectionName, UuidType},
+    value::SimpleValue,
 };
 use time::{
     format_description::well_known::Iso8601, macros::format_description, Date, Duration, Month,
     OffsetDateTime, PrimitiveDateTime, Time, UtcOffset,
 };
-use tokio::{net::TcpListener, sync::Mutex};
+use time_tz::{OffsetResult, PrimitiveDateTimeExt};
+use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
 
+mod admin;
+mod availability_cache;
+mod bookings;
+mod config;
 mod error;
+mod google_calendar;
 mod http;
-
-pub use error::{Error, Result};
+mod idempotency;
+mod metrics;
+mod openapi;
+mod rate_limit;
+mod recurrence;
+mod retry;
+mod search;
+mod services;
+mod slot;
+mod slot_lock;
+mod staff_schedule;
+mod template;
+mod webhook;
+
+pub use error::{Error, ErrorCode, Result};
+use availability_cache::AvailabilityCache;
+use config::Config;
+use google_calendar::GoogleCalendar;
+use idempotency::IdempotencyStore;
+use rate_limit::RateLimiter;
+use recurrence::RecurrenceRules;
+use slot::SlotId;
+use slot_lock::{HeldLock, LockKey, SlotLock};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -46,86 +83,412 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // TODO: Ultimately I'll need to decide if I want to send a unique token per-website or per-app
-    register_call_token(Uuid::from_u128(0x01938f4ff50c72039f89b367e9d49efbu128));
+    let config = Config::load()?;
 
-    let port = 5941;
+    // TODO: Ultimately I'll need to decide if I want to send a unique token per-website or per-app
+    register_call_token(config.addon_uuid);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     debug!("Addon Booking listening on {addr}");
 
     let listener = TcpListener::bind(addr).await.unwrap();
 
+    let state = AppState::new(config)?;
+
+    {
+        let rate_limiter = state.rate_limiter.clone();
+        let website_rate_limiter = state.website_rate_limiter.clone();
+        let mut interval = tokio::time::interval(
+            state
+                .config
+                .rate_limit_window
+                .max(std::time::Duration::from_secs(1)),
+        );
+
+        tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                rate_limiter.sweep().await;
+                website_rate_limiter.sweep().await;
+            }
+        });
+    }
+
+    // Publicly reachable from the booking widget, so these are rate limited.
+    let public_routes = Router::new()
+        .route("/:uuid/availableDays", get(get_available_days))
+        .route("/:uuid/availableHours", get(get_available_hours))
+        .route("/:uuid/availabilityCounts", get(get_availability_counts))
+        .route("/:uuid/availableWeek", get(get_available_week))
+        .route("/:uuid/workingHours", get(get_working_hours))
+        .route("/:uuid/search", get(search::get_search))
+        .route("/:uuid/book/validate", get(get_book_validate))
+        // .route("/:uuid/book", post(post_booking))
+        .route("/:uuid/book/batch", post(bookings::post_batch_booking))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit,
+        ));
+
+    // Called server to server by the platform, from a few shared IPs, so limited per website.
+    let form_process_routes = Router::new()
+        .route("/form-process/before", post(post_form_process_before))
+        .route("/form-process/error", post(post_form_process_error))
+        .route("/form-process/after", post(post_form_process_after))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_website,
+        ));
+
+    let mut router = Router::new()
+        .nest("/registration", http::routes())
+        .merge(public_routes)
+        .merge(form_process_routes)
+        .merge(admin_routes(&state))
+        .route("/:uuid/services", get(services::get_services))
+        .route("/:uuid/service/:id", put(services::put_service))
+        .route("/:uuid/schedule/:id", put(services::put_schedule))
+        .route("/:uuid/block", post(bookings::post_block))
+        .route("/:uuid/block/range", post(bookings::post_block_range))
+        .route(
+            "/:uuid/block/range/:id",
+            delete(bookings::delete_block_range),
+        )
+        .route(
+            "/:uuid/bookings/export.csv",
+            get(bookings::get_bookings_export),
+        )
+        .route("/:uuid/form/regenerate", post(http::post_form_regenerate))
+        .route("/:uuid/email/preview", get(bookings::get_email_preview))
+        .route("/:uuid/booking/:id", get(bookings::get_booking))
+        .route(
+            "/:uuid/booking/:id/status",
+            put(bookings::put_booking_status),
+        )
+        .route(
+            "/:uuid/staff/:staffId/bookings",
+            get(bookings::get_staff_bookings),
+        )
+        .route(
+            "/:uuid/staff/:staffId/googleCalendar",
+            put(google_calendar::put_google_calendar)
+                .delete(google_calendar::delete_google_calendar),
+        )
+        .route(
+            "/:uuid/staff/:staffId/googleCalendar/authorize",
+            get(google_calendar::get_google_calendar_authorize),
+        )
+        .route(
+            "/googleCalendar/callback",
+            get(google_calendar::get_google_calendar_callback),
+        )
+        .route(
+            "/:uuid/staffSchedule",
+            post(staff_schedule::post_staff_schedule),
+        )
+        .route(
+            "/:uuid/staffSchedule/weekly",
+            post(staff_schedule::post_staff_schedule_weekly),
+        )
+        .route(
+            "/:uuid/staffSchedule/:id",
+            put(staff_schedule::put_staff_schedule),
+        )
+        .route(
+            "/:uuid/staffSchedule/:id/active",
+            put(staff_schedule::put_staff_schedule_active),
+        )
+        .route("/form-render", get(get_form_render))
+        .route("/openapi.json", get(openapi::get_openapi));
+
+    if state.config.metrics_enabled {
+        router = router.route("/metrics", get(metrics::get_metrics));
+    }
+
     axum::serve(
         listener,
-        Router::new()
-            .nest("/registration", http::routes())
-            .route("/:uuid/availableDays", get(get_available_days))
-            .route("/:uuid/availableHours", get(get_available_hours))
-            // .route("/:uuid/book", post(post_booking))
-            .route("/form-process/before", post(post_form_process_before))
-            .route("/form-process/error", post(post_form_process_error))
-            .route("/form-process/after", post(post_form_process_after))
-            .route("/form-render", get(get_form_render))
-            .layer(TraceLayer::new_for_http()),
+        router
+            .with_state(state)
+            .layer(TraceLayer::new_for_http())
+            .into_make_service_with_connect_info::<SocketAddr>(),
     )
+    .with_graceful_shutdown(shutdown_signal())
     .await?;
 
+    debug!("Addon Booking shut down");
+
     Ok(())
 }
 
+/// Routes for ops diagnosing stuck slots. They need the `BOOKING_ADMIN_TOKEN`.
+fn admin_routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/:uuid/lockStatus", get(get_lock_status))
+        .route("/:uuid/lock", delete(delete_lock))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin::require_admin,
+        ))
+}
+
+/// Resolves once SIGINT or SIGTERM is received.
+///
+/// In-flight requests are drained before the server exits so a booking isn't cut off mid-write.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    debug!("Shutdown signal received, draining active requests");
+}
+
+/// `type` of a booking made by a customer.
+const BOOKING_TYPE_BOOKING: &str = "booking";
+/// `type` of a booking which blocks a slot without a customer.
+const BOOKING_TYPE_BLOCK: &str = "block";
+/// `type` of a booking written by a batch which failed partway.
+/// The CMS can't delete rows, so they're kept but don't occupy their slot.
+const BOOKING_TYPE_VOID: &str = "void";
+
+/// `status` of a booking which takes up its slot. Bookings without a status count as confirmed.
+const BOOKING_STATUS_CONFIRMED: &str = "confirmed";
+/// Every `status` a booking can have. Only confirmed bookings occupy their slot.
+const BOOKING_STATUSES: [&str; 4] = [
+    BOOKING_STATUS_CONFIRMED,
+    "cancelled",
+    "completed",
+    "no_show",
+];
+
+/// `type` of a staff schedule which offers slots.
+const STAFF_SCHEDULE_TYPE_WORKING_HOURS: &str = "WORKING_HOURS";
+
+/// `recurrenceType` of a staff schedule which only runs once, on its `startDay`.
+const RECURRENCE_TYPE_INSTANCE: &str = "INSTANCE";
+/// `recurrenceType` of a staff schedule which repeats by its `recurrenceRule`.
+const RECURRENCE_TYPE_RECURRING: &str = "RECURRING";
+
+/// `source` of a booking made through the booking widget.
+const BOOKING_SOURCE_WIDGET: &str = "widget";
+/// `source` of a slot blocked by an admin.
+const BOOKING_SOURCE_ADMIN_BLOCK: &str = "admin-block";
+
 // TODO: Account for start time being larger than end time.
 // Eg: Start 11pm -> End 5am
 
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GetAvailableDaysQuery {
     year: usize,
     month: u8,
+    /// Number of consecutive months to return, starting at `year`/`month`. Defaults to 1.
+    months: Option<u8>,
+    /// Only include schedules of services in this category.
+    category: Option<String>,
+    /// Mark days on which every slot is occupied. Requires looking up the bookings.
+    #[serde(default)]
+    fully_booked: bool,
 }
 
-#[derive(serde::Deserialize)]
-struct RecurrenceRule {
-    days: Vec<String>,
-    frequency: String,
-    interval: usize,
+/// Most months `availableDays` returns in one request.
+const MAX_AVAILABLE_DAYS_MONTHS: u8 = 12;
+
+/// Most days checked for `fullyBooked` in one request. Later days are left unmarked.
+const MAX_FULLY_BOOKED_DAYS: usize = 62;
+
+/// Days looked through for another slot when the chosen one is already booked.
+const ALTERNATIVE_SEARCH_DAYS: i64 = 7;
+
+/// A window within a working day in which no slots can be booked (eg. lunch).
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BreakWindow {
+    /// Local start time (HH:MM:SS)
+    start: String,
+    /// Local end time (HH:MM:SS)
+    end: String,
+}
+
+impl BreakWindow {
+    fn parse(&self) -> Result<(Time, Time)> {
+        let time_format = format_description!("[hour]:[minute]:[second]");
+
+        let start = Time::parse(&self.start, &time_format)?;
+        let end = Time::parse(&self.end, &time_format)?;
+
+        if start >= end {
+            return Err(eyre::eyre!(
+                "Break start {} must be before break end {}",
+                self.start,
+                self.end
+            ))?;
+        }
+
+        Ok((start, end))
+    }
+}
+
+/// Start of the first month looked up and the first day after the last one.
+fn available_days_window(
+    year: usize,
+    month: u8,
+    months: Option<u8>,
+) -> Result<(PrimitiveDateTime, PrimitiveDateTime)> {
+    let start = Date::from_calendar_date(year as i32, Month::try_from(month)?, 1)?.midnight();
+
+    let months = months.unwrap_or(1).clamp(1, MAX_AVAILABLE_DAYS_MONTHS);
+
+    let end_month = year as i32 * 12 + month as i32 - 1 + months as i32;
+    let end = Date::from_calendar_date(
+        end_month.div_euclid(12),
+        Month::try_from(end_month.rem_euclid(12) as u8 + 1)?,
+        1,
+    )?
+    .midnight();
+
+    Ok((start, end))
 }
 
 async fn get_available_days(
+    State(state): State<AppState>,
     Path(uuid): Path<UuidType>,
     Query(query): Query<GetAvailableDaysQuery>,
 ) -> Result<JsonResponse<serde_json::Value>> {
-    // TODO: Remember Daylight Savings Time
-
-    let now =
-        Date::from_calendar_date(query.year as i32, Month::try_from(query.month)?, 1)?.midnight();
+    metrics::inc(&metrics::AVAILABILITY_REQUESTS);
+
+    // Slots use the time zone's fixed offset on every date, see `DstWarning`.
+
+    let (now, end) = available_days_window(query.year, query.month, query.months)?;
+
+    let staff_schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: None,
+                // filters: Some(vec![Filter {
+                //     name: String::from("startDay"),
+                //     cond: FilterConditionType::Lte,
+                //     value: FilterValue::Text(String::from("2024-12-12")),
+                // }]),
+                sort: None,
+                columns: None,
+                limit: None,
+                offset: None,
+                include_files: false,
+            },
+        )
+    })
+    .await?;
 
-    let staff_schedule_resp = query_cms_rows(
-        uuid,
-        CollectionName {
-            id: String::from("staffSchedule"),
-            ns: Some(String::from("@booking")),
-        },
-        CmsQuery {
-            filters: None,
-            // filters: Some(vec![Filter {
-            //     name: String::from("startDay"),
-            //     cond: FilterConditionType::Lte,
-            //     value: FilterValue::Text(String::from("2024-12-12")),
-            // }]),
-            sort: None,
-            columns: None,
-            limit: None,
-            offset: None,
-            include_files: false,
-        },
-    )
+    let schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
     .await?;
 
+    let schedule_repeats = schedule_repeats(&schedule_resp.items)?;
+
     // Example Schedule:
     // 2024-12-06 | 10:00:00 - 18:00:00 America/Los_Angeles | 1/wk
     // Fridays: Nov 1, 8, 15, 22, 29 | Dec 6, 13, 20, 27
 
-    let available_days = gather_available_days(now, staff_schedule_resp.items)?;
+    let mut staff_schedule_items = staff_schedule_resp.items;
+
+    if let Some(category) = query.category {
+        let services = retry::read(&state.config, || {
+            query_cms_rows(
+                uuid,
+                CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
+                },
+                CmsQuery {
+                    filters: services::service_filters(None, Some(category.clone())),
+                    ..CmsQuery::default()
+                },
+            )
+        })
+        .await?;
+
+        let service_ids = services
+            .items
+            .iter()
+            .map(row_id)
+            .collect::<Result<HashSet<_>>>()?;
+
+        let mut schedule_ids = HashSet::new();
+
+        for row in &schedule_resp.items {
+            if row_text(row, "service")?.is_some_and(|id| service_ids.contains(&id)) {
+                schedule_ids.insert(row_id(row)?);
+            }
+        }
+
+        let mut filtered = Vec::new();
+
+        for row in staff_schedule_items {
+            if row_text(&row, "schedule")?.is_some_and(|id| schedule_ids.contains(&id)) {
+                filtered.push(row);
+            }
+        }
+
+        staff_schedule_items = filtered;
+    }
+
+    let staff_time_zones = staff_time_zones(&state, uuid).await?;
+
+    for item in &mut staff_schedule_items {
+        apply_staff_time_zone(item, &staff_time_zones)?;
+    }
+
+    let fully_booked = if query.fully_booked {
+        fully_booked_days(
+            &state,
+            uuid,
+            &staff_schedule_items,
+            &schedule_resp.items,
+            (now.assume_utc(), end.assume_utc()),
+            &schedule_repeats,
+        )
+        .await?
+    } else {
+        HashMap::new()
+    };
+
+    let available_days = gather_available_days(
+        now,
+        end,
+        &staff_schedule_items,
+        &schedule_repeats,
+        &fully_booked,
+    )?;
 
     // TODO: Simplify
     Ok(Json(WrappingResponse::okay(serde_json::json!({
@@ -133,98 +496,380 @@ async fn get_available_days(
     }))))
 }
 
+/// How many times each schedule recurs, by schedule id. 0 means forever.
+fn schedule_repeats(schedules: &[CmsRowResponse]) -> Result<HashMap<String, usize>> {
+    schedules
+        .iter()
+        .map(|row| {
+            // Rows from before `repeats` was set can hold a null, which is forever like a missing one.
+            let repeats = match row
+                .fields
+                .get(&SchematicFieldKey::Other(String::from("repeats")))
+            {
+                None | Some(SimpleValue::ObjectUnknown(serde_json::Value::Null)) => None,
+                Some(v) => Some(v.try_as_number()?.convert_f64()),
+            };
+
+            Ok((row_id(row)?, repeats_limit(repeats)))
+        })
+        .collect()
+}
+
+/// A schedule's `repeats` as a cap on its occurrences, including the first. 0 means forever.
+fn repeats_limit(repeats: Option<f64>) -> usize {
+    repeats.filter(|v| *v >= 0.0).unwrap_or_default() as usize
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetAvailabilityCountsQuery {
+    year: usize,
+    month: u8,
+    service_id: String,
+}
+
+/// Total & remaining slots of every day a service runs on in a month, eg. to color a calendar.
+///
+/// A single month is counted per request to bound the work.
+async fn get_availability_counts(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(GetAvailabilityCountsQuery {
+        year,
+        month,
+        service_id,
+    }): Query<GetAvailabilityCountsQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    metrics::inc(&metrics::AVAILABILITY_REQUESTS);
+
+    let month_start = Date::from_calendar_date(year as i32, Month::try_from(month)?, 1)?;
+
+    if let Some(cached) = state
+        .availability_cache
+        .get_counts(uuid, &service_id, month_start)
+        .await
+    {
+        return Ok(Json(WrappingResponse::okay(cached)));
+    }
+
+    let days_in_month = i64::from(time::util::days_in_year_month(
+        month_start.year(),
+        month_start.month(),
+    ));
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            &service_id,
+        )
+    })
+    .await?;
+
+    let schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let mut staff_schedule_items = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?
+    .items;
+
+    let staff_time_zones = staff_time_zones(&state, uuid).await?;
+
+    for item in &mut staff_schedule_items {
+        apply_staff_time_zone(item, &staff_time_zones)?;
+    }
+
+    let schedule_repeats = schedule_repeats(&schedule_resp.items)?;
+
+    let mut service_schedules = HashMap::new();
+
+    for row in &schedule_resp.items {
+        if row_text(row, "service")?.as_deref() == Some(service_id.as_str()) {
+            service_schedules.insert(row_id(row)?, row);
+        }
+    }
+
+    // Occurrences are in UTC, so a day either side covers every local day of the month.
+    let lookup_time = month_start.midnight().assume_utc() - Duration::days(1);
+    let lookup_end = lookup_time + Duration::days(days_in_month + 2);
+
+    // Total & remaining slots by local day.
+    let mut counts: BTreeMap<Date, (usize, usize)> = BTreeMap::new();
+
+    for staff_schedule in &staff_schedule_items {
+        let Some(schedule) = row_text(staff_schedule, "schedule")?
+            .and_then(|id| service_schedules.get(&id).copied())
+        else {
+            continue;
+        };
+
+        if !is_staff_schedule_active(staff_schedule)? || !is_working_hours(staff_schedule)? {
+            continue;
+        }
+
+        let occurrences =
+            staff_schedule_occurrences(staff_schedule, lookup_time, lookup_end, &schedule_repeats)?;
+
+        let mut days = occurrences
+            .starts
+            .iter()
+            .map(|utc| utc.to_offset(occurrences.local_offset).date())
+            .filter(|day| day.year() == month_start.year() && day.month() == month_start.month())
+            .collect::<Vec<_>>();
+
+        days.dedup();
+
+        if days.is_empty() {
+            continue;
+        }
+
+        let filters = book_days_filters(month_start, days_in_month, occurrences.local_offset)?;
+
+        let bookings = retry::read(&state.config, || {
+            query_cms_rows(
+                uuid,
+                CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                CmsQuery {
+                    filters: Some(filters.clone()),
+                    ..CmsQuery::default()
+                },
+            )
+        })
+        .await?;
+
+        let occupied = Occupied::resolve(
+            &state,
+            uuid,
+            staff_schedule,
+            month_start,
+            days_in_month,
+            bookings.items,
+        )
+        .await?;
+
+        for day in days {
+            let found_hours = gather_available_hours(
+                day.midnight(),
+                &service,
+                schedule,
+                staff_schedule,
+                &occupied,
+                None,
+                true,
+            )?;
+
+            let (total, remaining) = counts.entry(day).or_default();
+
+            *total += found_hours.len();
+            *remaining += found_hours.iter().filter(|v| !v.is_booked).count();
+        }
+    }
+
+    let response = serde_json::json!({
+        "year": year,
+        "month": month,
+        "serviceId": service_id,
+        "days": counts
+            .into_iter()
+            .map(|(day, (total, remaining))| {
+                serde_json::json!({
+                    "day": day.day(),
+                    "total": total,
+                    "remaining": remaining,
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+
+    state
+        .availability_cache
+        .insert_counts(uuid, service_id, month_start, response.clone())
+        .await;
+
+    Ok(Json(WrappingResponse::okay(response)))
+}
+
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GetAvailableHoursQuery {
     day: u8,
     month: u8,
     year: usize,
-    schedule_ids: String,
+    /// Staff schedule to list the slots of.
+    schedule_ids: Option<String>,
+    /// Lists the slots of every staff member of the service instead when `scheduleIds` isn't sent.
+    service_id: Option<String>,
+    /// Return slot times with the schedule's real offset.
+    ///
+    /// By default the local wall clock time is relabeled as UTC (eg. 09:00 in `-08:00` is
+    /// returned as `09:00Z`) so JavaScript `Date` shows it unchanged in any browser time zone.
+    #[serde(default)]
+    raw_offsets: bool,
+    /// Only return slots starting at or after this local hour.
+    from_hour: Option<u8>,
+    /// Only return slots ending at or before this local hour.
+    to_hour: Option<u8>,
+    /// Length of the slots for services with `durationOptions`. Defaults to the schedule's duration.
+    duration_minutes: Option<f64>,
+    /// Embed the booking form's fields as `form`, for widgets rendering it on the same page.
+    #[serde(default)]
+    include_form: bool,
 }
 
 async fn get_available_hours(
+    State(state): State<AppState>,
     Path(uuid): Path<UuidType>,
     Query(GetAvailableHoursQuery {
         day,
         month,
         year,
         schedule_ids,
+        service_id,
+        raw_offsets,
+        from_hour,
+        to_hour,
+        duration_minutes,
+        include_form,
     }): Query<GetAvailableHoursQuery>,
 ) -> Result<JsonResponse<serde_json::Value>> {
-    // TODO: Remember Daylight Savings Time
+    metrics::inc(&metrics::AVAILABILITY_REQUESTS);
 
-    let list_date = Date::from_calendar_date(year as i32, Month::try_from(month)?, day)?.midnight();
+    // Slots use the time zone's fixed offset on every date, see `DstWarning`.
 
-    let staff_schedule = get_cms_row_by_id(
-        uuid,
-        CollectionName {
-            id: String::from("staffSchedule"),
-            ns: Some(String::from("@booking")),
-        },
-        // TODO: Multiple ids can be in here.
-        &schedule_ids,
-    )
-    .await?;
+    let list_date = calendar_date(year, month, day)?.midnight();
 
-    let schedule = get_cms_row_by_id(
-        uuid,
-        CollectionName {
-            id: String::from("schedule"),
-            ns: Some(String::from("@booking")),
-        },
-        &staff_schedule
-            .fields
-            .get(&SchematicFieldKey::Other(String::from("schedule")))
-            .context("Schedule ID")?
-            .any_as_text()?,
-    )
+    let from_hour = from_hour.unwrap_or(0);
+    let to_hour = to_hour.unwrap_or(24);
+
+    if from_hour >= to_hour || to_hour > 24 {
+        return Err(Error::Validation(vec![format!(
+            "Invalid hours: fromHour {from_hour} must be before toHour {to_hour} (0-24)"
+        )]));
+    }
+
+    // Local window the slots have to fit in.
+    let window_start = list_date + Duration::hours(from_hour as i64);
+    let window_end = list_date + Duration::hours(to_hour as i64);
+
+    let schedule_ids = match (schedule_ids, service_id) {
+        (Some(schedule_ids), _) => schedule_ids,
+        (None, Some(service_id)) => {
+            let mut response = search::service_available_hours(
+                &state,
+                uuid,
+                &service_id,
+                list_date,
+                (window_start, window_end),
+                duration_minutes,
+                raw_offsets,
+            )
+            .await?;
+
+            embed_form(&mut response, include_form);
+
+            return Ok(Json(WrappingResponse::okay(response)));
+        }
+        (None, None) => {
+            return Err(Error::Validation(vec![String::from(
+                "Either scheduleIds or serviceId is required",
+            )]))
+        }
+    };
+
+    // Only whole days at the schedule's duration are cached.
+    let is_cacheable = from_hour == 0 && to_hour == 24 && duration_minutes.is_none();
+
+    if is_cacheable {
+        if let Some(mut cached) = state
+            .availability_cache
+            .get(uuid, &schedule_ids, list_date.date(), raw_offsets)
+            .await
+        {
+            // Cached without the form, as it's the same for every slot.
+            embed_form(&mut cached, include_form);
+
+            return Ok(Json(WrappingResponse::okay(cached)));
+        }
+    }
+
+    // Stale ids from the widget would otherwise fail on a row lookup below.
+    bookability(&state, uuid)
+        .await?
+        .ensure_bookable(&schedule_ids)?;
+
+    let mut staff_schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            // TODO: Multiple ids can be in here.
+            &schedule_ids,
+        )
+    })
     .await?;
 
-    let service = get_cms_row_by_id(
-        uuid,
-        CollectionName {
-            id: String::from("services"),
-            ns: Some(String::from("@booking")),
-        },
-        &schedule
-            .fields
-            .get(&SchematicFieldKey::Other(String::from("service")))
-            .context("Service ID")?
-            .any_as_text()?,
-    )
+    apply_staff_time_zone(&mut staff_schedule, &staff_time_zones(&state, uuid).await?)?;
+
+    let schedule_id = staff_schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("schedule")))
+        .context("Schedule ID")?
+        .any_as_text()?;
+
+    let schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &schedule_id,
+        )
+    })
     .await?;
 
-    let bookings = query_cms_rows(
-        uuid,
-        CollectionName {
-            id: String::from("bookings"),
-            ns: Some(String::from("@booking")),
-        },
-        CmsQuery {
-            filters: Some(vec![
-                Filter {
-                    name: String::from("bookDate"),
-                    cond: FilterConditionType::Gte,
-                    value: FilterValue::Text(format!(
-                        "{year}-{month:02}-{day:02} 00:00:00.0 +00:00:00"
-                    )),
-                },
-                Filter {
-                    name: String::from("bookDate"),
-                    cond: FilterConditionType::Lte,
-                    value: FilterValue::Text(format!(
-                        "{year}-{month:02}-{day:02} 23:59:59.0 +00:00:00"
-                    )),
-                },
-            ]),
-            // sort: None,
-            // columns: None,
-            // limit: None,
-            // offset: None,
-            // include_files: false,
-            ..CmsQuery::default()
-        },
-    )
+    let service_id = schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("service")))
+        .context("Service ID")?
+        .any_as_text()?;
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            &service_id,
+        )
+    })
     .await?;
 
     let time_zone_str = staff_schedule
@@ -234,236 +879,789 @@ async fn get_available_hours(
         .context("Missing TimeZone")?
         .try_as_text()?;
 
-    let available_hours = gather_available_hours(
+    let filters = book_date_filters(list_date.date(), validate_time_zone(&time_zone_str)?)?;
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                // sort: None,
+                // columns: None,
+                // limit: None,
+                // offset: None,
+                // include_files: false,
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    // A service without a price is treated as free.
+    let price = ServicePrice::of(&service)?;
+    let currency = service_currency(&state.config, &service)?;
+    let payment = service_payment(&service)?;
+    let min_participants = service_min_participants(&service)?;
+
+    validate_duration_option(&service, duration_minutes)?;
+
+    // Every slot shares the staff member, so they're looked up once.
+    // A missing staff row only leaves out their name & image.
+    let staff = match row_text(&staff_schedule, "staff")? {
+        Some(staff_id) => retry::read(&state.config, || {
+            get_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("staff"),
+                    ns: Some(String::from("@booking")),
+                },
+                &staff_id,
+            )
+        })
+        .await
+        .inspect_err(|e| warn!("Unable to find staff {staff_id}: {e}"))
+        .ok(),
+        None => None,
+    };
+
+    let staff = StaffDetails::of(&state.config, staff.as_ref())?;
+
+    let is_active = is_staff_schedule_active(&staff_schedule)?;
+    let occupied = Occupied::resolve(
+        &state,
+        uuid,
+        &staff_schedule,
+        list_date.date(),
+        1,
+        bookings.items,
+    )
+    .await?;
+
+    let found_hours = gather_available_hours(
         list_date,
-        service
-            .fields
-            .get(&SchematicFieldKey::Id)
-            .unwrap()
-            .any_as_text()?,
+        &service,
         &schedule,
-        staff_schedule,
-        bookings,
-    )?
-    .into_iter()
-    .map(|v| {
-        serde_json::json!({
-            "start": v.start.format(&Iso8601::DEFAULT).unwrap(),
-            "end": v.end.format(&Iso8601::DEFAULT).unwrap(),
-            "isBooked": v.is_booked,
-            "serviceId": v.service_id,
-            "scheduleId": v.schedule_id,
-            "staffId": v.staff_id,
-            "staffScheduleId": v.staff_schedule_id,
-            "formId": service
-                .fields
-                .get(&SchematicFieldKey::OtherStatic("formId"))
-                .unwrap()
-                .any_as_text()
-                .unwrap(),
+        &staff_schedule,
+        &occupied,
+        duration_minutes,
+        raw_offsets,
+    )?;
+
+    let mut window_hours = found_hours
+        .iter()
+        // `start` & `end` hold the local wall clock time in both offset modes.
+        .filter(|v| {
+            PrimitiveDateTime::new(v.start.date(), v.start.time()) >= window_start
+                && PrimitiveDateTime::new(v.end.date(), v.end.time()) <= window_end
         })
-    })
-    .collect::<Vec<_>>();
+        .collect::<Vec<_>>();
 
-    Ok(Json(WrappingResponse::okay(serde_json::json!({
+    // Chronological and stable, whatever order the slots were generated in.
+    window_hours.sort_by(|a, b| a.cmp_chronological(b));
+
+    let reason = UnavailableReason::classify(is_active, &found_hours, &window_hours);
+
+    // Services without a form yet are left null.
+    let form_id = row_text(&service, "formId")?;
+
+    let available_hours = window_hours
+        .into_iter()
+        .map(|v| {
+            Ok(serde_json::json!({
+                "start": v.start.format(&Iso8601::DEFAULT)?,
+                "end": v.end.format(&Iso8601::DEFAULT)?,
+                "isBooked": v.is_booked,
+                "isBlocked": v.is_blocked,
+                "dstWarning": v.dst_warning,
+                "participants": v.participants,
+                "remaining": v.remaining,
+                "sessionStatus": SessionStatus::of(v.participants, min_participants),
+                "serviceId": v.service_id,
+                "scheduleId": v.schedule_id,
+                "staffId": v.staff_id,
+                "staffScheduleId": v.staff_schedule_id,
+                "slotId": v.slot_id().to_string(),
+                "formId": form_id,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Same for every slot.
+    let (slot_duration, slot_break) = slot_minutes(&schedule, duration_minutes)?;
+    let service_details = ServiceDetails::of(&state.config, &service)?;
+
+    let mut response = serde_json::json!({
         "timeZone": time_zone_str,
+        "priceAmount": price.amount.unwrap_or_default(),
+        "isFree": price.is_free(),
+        "depositAmount": payment.deposit_amount,
+        "balanceDue": payment.balance_due,
+        "currency": currency,
+        "paymentType": price.payment_type,
+        "serviceName": service_details.name,
+        "serviceDescription": service_details.description,
+        "serviceImage": service_details.image,
+        "serviceImageUrl": service_details.image_url,
+        "staffName": staff.name,
+        "staffImage": staff.image,
+        "staffImageUrl": staff.image_url,
+        "durationMinutes": slot_duration,
+        "breakMinutes": slot_break,
         "available": available_hours,
-    }))))
-}
+        "reason": reason,
+    });
+
+    if is_cacheable {
+        state
+            .availability_cache
+            .insert(
+                uuid,
+                schedule_ids,
+                list_date.date(),
+                raw_offsets,
+                response.clone(),
+            )
+            .await;
+    }
 
-//
+    embed_form(&mut response, include_form);
+
+    Ok(Json(WrappingResponse::okay(response)))
+}
 
-static PROCESSING_FORMS: LazyLock<Mutex<HashMap<(String, u8, u8, usize), String>>> =
-    LazyLock::new(|| Mutex::new(HashMap::new()));
+/// Days `availableWeek` covers.
+const WEEK_DAYS_COUNT: i64 = 7;
 
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct FormProcessQuery {
-    client_key: String,
-    uuid: UuidType,
-
-    // TODO: Replace w/ BookingId
-    staff_schedule_id: String,
+struct GetAvailableWeekQuery {
+    /// Staff schedule to list the slots of.
     schedule_id: String,
-    service_id: String,
-    staff_id: String,
-
-    day: u8,
-    month: u8,
     year: usize,
-    time: String,
+    month: u8,
+    day: u8,
+    /// Start the week at the given day instead of the Monday of its week.
+    #[serde(default)]
+    start_at_day: bool,
+    /// Return slot times with the schedule's real offset. See `availableHours`.
+    #[serde(default)]
+    raw_offsets: bool,
 }
 
-async fn post_form_process_before(
-    Query(FormProcessQuery {
-        client_key,
-        uuid,
-        staff_schedule_id,
+/// Slots of a staff schedule for the seven days of a week, for weekly agenda views.
+///
+/// Days the schedule doesn't run on are listed without slots.
+async fn get_available_week(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(GetAvailableWeekQuery {
         schedule_id,
-        service_id,
-        staff_id,
-        day,
-        month,
         year,
-        time,
-    }): Query<FormProcessQuery>,
-) -> Result<()> {
-    // TODO: make uuid be addon instance instead of website id
+        month,
+        day,
+        start_at_day,
+        raw_offsets,
+    }): Query<GetAvailableWeekQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    metrics::inc(&metrics::AVAILABILITY_REQUESTS);
 
-    //
+    let day = calendar_date(year, month, day)?;
 
-    // Check if the form is already being processed.
+    let first_day = week_start(day, start_at_day);
 
-    let schedule = get_cms_row_by_id(
-        uuid,
-        CollectionName {
-            id: String::from("schedule"),
-            ns: Some(String::from("@booking")),
-        },
-        &schedule_id,
-    )
+    let mut staff_schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &schedule_id,
+        )
+    })
+    .await?;
+
+    apply_staff_time_zone(&mut staff_schedule, &staff_time_zones(&state, uuid).await?)?;
+
+    let schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let staff_schedule_schedule_id =
+        row_text(&staff_schedule, "schedule")?.context("Schedule ID")?;
+
+    let schedule = schedule_resp
+        .items
+        .iter()
+        .find(|row| row_id(row).is_ok_and(|id| id == staff_schedule_schedule_id))
+        .with_context(|| format!("Schedule {staff_schedule_schedule_id} no longer exists"))?;
+
+    let service_id = row_text(schedule, "service")?.context("Service ID")?;
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            &service_id,
+        )
+    })
+    .await?;
+
+    let time_zone_str = row_text(&staff_schedule, "timeZone")?.context("Missing TimeZone")?;
+    let local_offset = validate_time_zone(&time_zone_str)?;
+
+    let lookup_time = first_day.midnight().assume_offset(local_offset);
+
+    let occurrences = staff_schedule_occurrences(
+        &staff_schedule,
+        lookup_time,
+        lookup_time + Duration::days(WEEK_DAYS_COUNT),
+        &schedule_repeats(&schedule_resp.items)?,
+    )?;
+
+    let run_days = occurrences
+        .starts
+        .iter()
+        .map(|utc| utc.to_offset(local_offset).date())
+        .collect::<HashSet<_>>();
+
+    let filters = book_days_filters(first_day, WEEK_DAYS_COUNT, local_offset)?;
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                ..CmsQuery::default()
+            },
+        )
+    })
     .await?;
 
-    let staff_schedule = get_cms_row_by_id(
+    // Blocks, time off & synced calendars of the whole week.
+    let occupied = Occupied::resolve(
+        &state,
         uuid,
-        CollectionName {
-            id: String::from("staffSchedule"),
-            ns: Some(String::from("@booking")),
-        },
-        // TODO: Multiple ids can be in here.
-        &staff_schedule_id,
+        &staff_schedule,
+        first_day,
+        WEEK_DAYS_COUNT,
+        bookings.items,
     )
     .await?;
 
-    // Some validations
+    let days = week_days(
+        first_day,
+        &run_days,
+        &service,
+        schedule,
+        &staff_schedule,
+        &occupied,
+        raw_offsets,
+    )?;
 
-    // TODO: Replace any_as_text() -> try_as_text()
-    if schedule
-        .fields
-        .get(&SchematicFieldKey::Other(String::from("service")))
-        .map(|v| v.any_as_text())
-        .transpose()?
-        .as_deref()
-        != Some(service_id.as_str())
-    {
-        return Err(eyre::eyre!("Service ID does not match schedule"))?;
-    }
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "staffScheduleId": schedule_id,
+        "serviceId": service_id,
+        "timeZone": time_zone_str,
+        "days": days,
+    }))))
+}
 
-    if staff_schedule
-        .fields
-        .get(&SchematicFieldKey::Other(String::from("schedule")))
-        .map(|v| v.any_as_text())
-        .transpose()?
-        .as_deref()
-        != Some(schedule_id.as_str())
-    {
-        return Err(eyre::eyre!("Schedule ID does not match staff schedule"))?;
+/// First day of the week containing `day`, its Monday unless the week starts at the day.
+fn week_start(day: Date, start_at_day: bool) -> Date {
+    if start_at_day {
+        day
+    } else {
+        day - Duration::days(day.weekday().number_days_from_monday() as i64)
     }
+}
 
-    if staff_schedule
-        .fields
-        .get(&SchematicFieldKey::Other(String::from("staff")))
-        .map(|v| v.any_as_text())
-        .transpose()?
-        .as_deref()
-        != Some(staff_id.as_str())
-    {
-        return Err(eyre::eyre!("Staff ID does not match staff schedule"))?;
+/// Slots of each day of the week, the same as listed for the day alone.
+fn week_days(
+    first_day: Date,
+    run_days: &HashSet<Date>,
+    service: &CmsRowResponse,
+    schedule: &CmsRowResponse,
+    staff_schedule: &CmsRowResponse,
+    occupied: &Occupied,
+    raw_offsets: bool,
+) -> Result<Vec<serde_json::Value>> {
+    let mut days = Vec::new();
+
+    for day in (0..WEEK_DAYS_COUNT).filter_map(|v| first_day.checked_add(Duration::days(v))) {
+        let found_hours = if run_days.contains(&day) {
+            gather_available_hours(
+                day.midnight(),
+                service,
+                schedule,
+                staff_schedule,
+                occupied,
+                None,
+                raw_offsets,
+            )?
+        } else {
+            Vec::new()
+        };
+
+        let available = found_hours
+            .iter()
+            .map(|v| {
+                Ok(serde_json::json!({
+                    "start": v.start.format(&Iso8601::DEFAULT)?,
+                    "end": v.end.format(&Iso8601::DEFAULT)?,
+                    "isBooked": v.is_booked,
+                    "isBlocked": v.is_blocked,
+                    "remaining": v.remaining,
+                    "dstWarning": v.dst_warning,
+                    "slotId": v.slot_id().to_string(),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        days.push(serde_json::json!({
+            "date": day,
+            "weekday": day.weekday().to_string().to_uppercase(),
+            "available": available,
+        }));
     }
 
-    // We lock here to ensure we don't have multiple of the same time form being processed at the same time.
-    let mut proc = PROCESSING_FORMS.lock().await;
+    Ok(days)
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetWorkingHoursQuery {
+    schedule_id: String,
+    year: usize,
+    month: u8,
+    day: u8,
+}
 
-    let key = (schedule_id, day, month, year);
+/// Why a staff schedule has no working hours on a day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ClosedReason {
+    /// The staff schedule is paused.
+    Inactive,
+    /// The schedule doesn't run on the day.
+    NotScheduled,
+    /// Every slot is blocked off by the staff.
+    Blocked,
+}
 
-    if proc.contains_key(&key) {
-        return Err(eyre::eyre!("Form already being processed"))?;
+impl ClosedReason {
+    /// Closed when every slot is blocked. Days which are merely fully booked are still open.
+    fn blocked(found_hours: &[FoundHour]) -> Option<Self> {
+        (!found_hours.is_empty() && found_hours.iter().all(|v| v.is_blocked))
+            .then_some(Self::Blocked)
     }
+}
 
-    let bookings = query_cms_rows(
-        uuid,
-        CollectionName {
-            id: String::from("bookings"),
-            ns: Some(String::from("@booking")),
-        },
-        CmsQuery {
-            filters: Some(vec![
-                Filter {
-                    name: String::from("bookDate"),
-                    cond: FilterConditionType::Gte,
-                    value: FilterValue::Text(format!(
-                        "{year}-{month:02}-{day:02} 00:00:00.0 +00:00:00"
-                    )),
-                },
-                Filter {
-                    name: String::from("bookDate"),
-                    cond: FilterConditionType::Lte,
-                    value: FilterValue::Text(format!(
-                        "{year}-{month:02}-{day:02} 23:59:59.0 +00:00:00"
-                    )),
-                },
-            ]),
-            // sort: None,
-            // columns: None,
-            // limit: None,
-            // offset: None,
-            // include_files: false,
-            ..CmsQuery::default()
-        },
-    )
+/// When working hours from `start_time` to `end_time` open & close on a day.
+/// Overnight schedules close the next day.
+fn working_day(
+    day: Date,
+    start_time: Time,
+    end_time: Time,
+    local_offset: UtcOffset,
+) -> Result<(OffsetDateTime, OffsetDateTime)> {
+    let end_day = if end_time <= start_time {
+        day.next_day().context("End day")?
+    } else {
+        day
+    };
+
+    Ok((
+        day.with_time(start_time).assume_offset(local_offset),
+        end_day.with_time(end_time).assume_offset(local_offset),
+    ))
+}
+
+/// Opening & closing times of a staff schedule on a day, eg. "Open 10–6 today".
+async fn get_working_hours(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(GetWorkingHoursQuery {
+        schedule_id,
+        year,
+        month,
+        day,
+    }): Query<GetWorkingHoursQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let day = calendar_date(year, month, day)?;
+
+    let mut staff_schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &schedule_id,
+        )
+    })
     .await?;
 
-    let found_hours = gather_available_hours(
-        Date::from_calendar_date(year as i32, Month::try_from(month)?, day)?.midnight(),
-        schedule
-            .fields
-            .get(&SchematicFieldKey::Other(String::from("service")))
-            .context("Service ID")?
-            .any_as_text()?,
-        &schedule,
-        staff_schedule,
-        bookings,
+    apply_staff_time_zone(&mut staff_schedule, &staff_time_zones(&state, uuid).await?)?;
+
+    let schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let time_zone_str = row_text(&staff_schedule, "timeZone")?.context("Missing TimeZone")?;
+    let local_offset = validate_time_zone(&time_zone_str)?;
+
+    let lookup_time = day.midnight().assume_offset(local_offset);
+
+    let occurrences = staff_schedule_occurrences(
+        &staff_schedule,
+        lookup_time,
+        lookup_time + Duration::days(1),
+        &schedule_repeats(&schedule_resp.items)?,
     )?;
 
-    // Find the hour and check to see if it's booked.
+    let closed = if !is_staff_schedule_active(&staff_schedule)? {
+        Some(ClosedReason::Inactive)
+    } else if occurrences.starts.is_empty() {
+        Some(ClosedReason::NotScheduled)
+    } else {
+        let schedule_id = row_text(&staff_schedule, "schedule")?.context("Schedule ID")?;
+
+        let schedule = schedule_resp
+            .items
+            .iter()
+            .find(|row| row_id(row).is_ok_and(|id| id == schedule_id))
+            .with_context(|| format!("Schedule {schedule_id} no longer exists"))?;
+
+        let filters = book_date_filters(day, local_offset)?;
+
+        let bookings = retry::read(&state.config, || {
+            query_cms_rows(
+                uuid,
+                CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                CmsQuery {
+                    filters: Some(filters.clone()),
+                    ..CmsQuery::default()
+                },
+            )
+        })
+        .await?;
+
+        let occupied =
+            Occupied::resolve(&state, uuid, &staff_schedule, day, 1, bookings.items).await?;
+
+        let service_id = row_text(schedule, "service")?.context("Service ID")?;
+
+        let service = retry::read(&state.config, || {
+            get_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
+                },
+                &service_id,
+            )
+        })
+        .await?;
+
+        let found_hours = gather_available_hours(
+            day.midnight(),
+            &service,
+            schedule,
+            &staff_schedule,
+            &occupied,
+            None,
+            true,
+        )?;
+
+        ClosedReason::blocked(&found_hours)
+    };
+
+    if let Some(reason) = closed {
+        return Ok(Json(WrappingResponse::okay(serde_json::json!({
+            "timeZone": time_zone_str,
+            "closed": true,
+            "reason": reason,
+        }))));
+    }
+
+    let (start, end) = working_day(
+        day,
+        occurrences.start_time,
+        occurrences.end_time,
+        local_offset,
+    )?;
 
     let time_format = format_description!("[hour]:[minute]:[second]");
 
-    let time = Time::parse(&time, &time_format)?;
+    let breaks = staff_schedule_breaks(&staff_schedule)?
+        .into_iter()
+        .map(|(start, end)| {
+            Ok(serde_json::json!({
+                "start": start.format(&time_format)?,
+                "end": end.format(&time_format)?,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    let found_hour = found_hours
-        .iter()
-        .find(|v| v.start.time() == time)
-        .ok_or_else(|| eyre::eyre!("Time not found"))?;
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "timeZone": time_zone_str,
+        "closed": false,
+        "start": {
+            "local": start.format(&Iso8601::DEFAULT)?,
+            "utc": start.to_offset(UtcOffset::UTC).format(&Iso8601::DEFAULT)?,
+        },
+        "end": {
+            "local": end.format(&Iso8601::DEFAULT)?,
+            "utc": end.to_offset(UtcOffset::UTC).format(&Iso8601::DEFAULT)?,
+        },
+        "breaks": breaks,
+    }))))
+}
 
-    if found_hour.is_booked {
-        return Err(eyre::eyre!("Time is already booked"))?;
+//
+
+/// State shared by every handler.
+#[derive(Clone)]
+pub struct AppState {
+    config: Arc<Config>,
+    /// Slots locked by `form-process/before` until their form is submitted.
+    slot_lock: Arc<dyn SlotLock>,
+    /// Completed form-process requests by their `Idempotency-Key`.
+    idempotency: Arc<IdempotencyStore>,
+    rate_limiter: Arc<RateLimiter>,
+    /// Limits the form-process routes per website rather than per IP.
+    website_rate_limiter: Arc<RateLimiter<UuidType>>,
+    availability_cache: Arc<AvailabilityCache>,
+    google_calendar: Arc<GoogleCalendar>,
+}
+
+impl AppState {
+    fn new(config: Config) -> Result<Self> {
+        Ok(Self {
+            slot_lock: slot_lock::from_config(&config)?,
+            idempotency: Arc::default(),
+            rate_limiter: Arc::new(RateLimiter::new(
+                config.rate_limit,
+                config.rate_limit_window,
+            )),
+            website_rate_limiter: Arc::new(RateLimiter::new(
+                config.website_rate_limit,
+                config.rate_limit_window,
+            )),
+            availability_cache: Arc::new(AvailabilityCache::new(config.availability_cache_ttl)),
+            google_calendar: Arc::default(),
+            config: Arc::new(config),
+        })
     }
+}
 
-    proc.insert(key, client_key);
+/// Whether a lock taken at `locked_at` has stopped holding its slot.
+///
+/// The only place the lock TTL is compared, so reclaiming a slot and rejecting a late
+/// `form-process/after` agree with the `lockExpiresAt` returned to the client.
+fn is_lock_expired(locked_at: OffsetDateTime, ttl: std::time::Duration) -> bool {
+    OffsetDateTime::now_utc() >= lock_expires_at(locked_at, ttl)
+}
 
-    Ok(())
+/// UTC time a lock taken at `locked_at` expires at.
+fn lock_expires_at(locked_at: OffsetDateTime, ttl: std::time::Duration) -> OffsetDateTime {
+    locked_at + ttl
 }
 
-async fn post_form_process_error(Query(query): Query<FormProcessQuery>) -> Result<()> {
-    // Remove the form from the processing list.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FormProcessQuery {
+    client_key: String,
+    uuid: UuidType,
 
-    let key = (query.schedule_id, query.day, query.month, query.year);
+    /// The `slotId` returned by `availableHours`. Replaces the individual slot fields below.
+    slot_id: Option<String>,
 
-    PROCESSING_FORMS.lock().await.remove(&key);
+    // Deprecated: Use `slotId` instead. Kept for backwards compatibility for one release.
+    staff_schedule_id: Option<String>,
+    schedule_id: Option<String>,
+    service_id: Option<String>,
+    staff_id: Option<String>,
 
-    Ok(())
+    day: Option<u8>,
+    month: Option<u8>,
+    year: Option<usize>,
+    time: Option<String>,
+
+    /// Length chosen by the customer for services with `durationOptions`.
+    duration_minutes: Option<f64>,
+}
+
+/// A [`FormProcessQuery`] resolved to the slot being processed.
+struct FormProcess {
+    client_key: String,
+    uuid: UuidType,
+
+    staff_schedule_id: String,
+    schedule_id: String,
+    service_id: String,
+    staff_id: String,
+
+    day: u8,
+    month: u8,
+    year: usize,
+    time: String,
+    duration_minutes: Option<f64>,
+}
+
+impl FormProcess {
+    /// Identifies the slot & client an `Idempotency-Key` was first used for.
+    fn fingerprint(&self) -> serde_json::Value {
+        serde_json::json!([
+            self.client_key,
+            self.staff_schedule_id,
+            self.schedule_id,
+            self.service_id,
+            self.staff_id,
+            self.day,
+            self.month,
+            self.year,
+            self.time,
+            self.duration_minutes,
+        ])
+    }
+}
+
+impl FormProcessQuery {
+    fn resolve(self) -> Result<FormProcess> {
+        let slot = SlotQuery {
+            slot_id: self.slot_id,
+            staff_schedule_id: self.staff_schedule_id,
+            schedule_id: self.schedule_id,
+            service_id: self.service_id,
+            staff_id: self.staff_id,
+            day: self.day,
+            month: self.month,
+            year: self.year,
+            time: self.time,
+            duration_minutes: self.duration_minutes,
+        }
+        .resolve()?;
+
+        Ok(FormProcess {
+            client_key: self.client_key,
+            uuid: self.uuid,
+            staff_schedule_id: slot.staff_schedule_id,
+            schedule_id: slot.schedule_id,
+            service_id: slot.service_id,
+            staff_id: slot.staff_id,
+            day: slot.day,
+            month: slot.month,
+            year: slot.year,
+            time: slot
+                .time
+                .format(&format_description!("[hour]:[minute]:[second]"))?,
+            duration_minutes: self.duration_minutes,
+        })
+    }
+}
+
+/// The website a request is for, eg. the one a form is rendered for.
+#[derive(serde::Deserialize)]
+struct WebsiteQuery {
+    uuid: UuidType,
 }
 
+/// The booking context a form is rendered for.
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct FormProcessJson {
-    contact_uuid: Uuid,
-    schema_data_uuid: Uuid,
+struct SlotQuery {
+    /// The `slotId` returned by `availableHours`. Replaces the individual slot fields below.
+    slot_id: Option<String>,
+
+    // Deprecated: Use `slotId` instead. Kept for backwards compatibility for one release.
+    staff_schedule_id: Option<String>,
+    schedule_id: Option<String>,
+    service_id: Option<String>,
+    staff_id: Option<String>,
+
+    day: Option<u8>,
+    month: Option<u8>,
+    year: Option<usize>,
+    time: Option<String>,
+
+    /// Length chosen by the customer for services with `durationOptions`.
+    duration_minutes: Option<f64>,
 }
 
-async fn post_form_process_after(
-    Query(FormProcessQuery {
+impl SlotQuery {
+    fn resolve(self) -> Result<SlotId> {
+        if let Some(slot_id) = self.slot_id {
+            let slot: SlotId = slot_id.parse()?;
+
+            calendar_date(slot.year, slot.month, slot.day)?;
+
+            return Ok(slot);
+        }
+
+        let slot = SlotId {
+            staff_schedule_id: self.staff_schedule_id.context("Missing staffScheduleId")?,
+            schedule_id: self.schedule_id.context("Missing scheduleId")?,
+            service_id: self.service_id.context("Missing serviceId")?,
+            staff_id: self.staff_id.context("Missing staffId")?,
+            day: self.day.context("Missing day")?,
+            month: self.month.context("Missing month")?,
+            year: self.year.context("Missing year")?,
+            time: Time::parse(
+                &self.time.context("Missing time")?,
+                &format_description!("[hour]:[minute]:[second]"),
+            )?,
+        };
+
+        // Rejected before any CMS calls are made.
+        calendar_date(slot.year, slot.month, slot.day)?;
+
+        Ok(slot)
+    }
+}
+
+async fn post_form_process_before(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<FormProcessQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    // TODO: make uuid be addon instance instead of website id
+
+    let process = query.resolve()?;
+    let fingerprint = process.fingerprint().to_string();
+
+    let FormProcess {
         client_key,
         uuid,
         staff_schedule_id,
@@ -474,575 +1672,5307 @@ async fn post_form_process_after(
         month,
         year,
         time,
-    }): Query<FormProcessQuery>,
-    Json(FormProcessJson {
-        contact_uuid,
-        schema_data_uuid,
-    }): Json<FormProcessJson>,
-) -> Result<()> {
-    // Remove the form from the processing list.
+        duration_minutes,
+    } = process;
 
-    let key = (schedule_id, day, month, year);
+    // We lock here to ensure no more forms for the slot are processed at the same time than it has places.
+    let key = (uuid, schedule_id.clone(), day, month, year, time.clone());
 
-    let mut processing = PROCESSING_FORMS.lock().await;
+    let idempotency_key = idempotency::key("before", uuid, &headers);
 
-    let schedule = get_cms_row_by_id(
+    // The original response is only replayed while the lock it returned still holds the slot.
+    if let Some(response) = state
+        .idempotency
+        .replay(idempotency_key.as_ref(), &fingerprint)
+        .await?
+    {
+        let held = state.slot_lock.holds(&key).await?.iter().any(|lock| {
+            lock.client_key == client_key && !is_lock_expired(lock.locked_at, state.config.lock_ttl)
+        });
+
+        if held {
+            return Ok(Json(WrappingResponse::okay(response)));
+        }
+    }
+
+    let (schedule, staff_schedule) = validate_slot_links(
+        &state,
         uuid,
-        CollectionName {
-            id: String::from("schedule"),
-            ns: Some(String::from("@booking")),
-        },
-        &key.0,
+        &staff_schedule_id,
+        &schedule_id,
+        &service_id,
+        &staff_id,
     )
     .await?;
 
-    let duration = schedule
-        .fields
-        .get(&SchematicFieldKey::Other(String::from("duration")))
-        .context("Service Duration")?
-        .try_as_number()?;
-
-    let _client_key = processing.remove(&key).context("Process not found")?;
+    // The slot is only locked if it can be booked.
+    let (mut response, places_left) = free_slot_response(
+        &state,
+        uuid,
+        schedule,
+        staff_schedule,
+        &service_id,
+        (day, month, year, &time, duration_minutes),
+    )
+    .await?;
 
-    if _client_key != client_key {
-        return Err(eyre::eyre!("Client key does not match"))?;
+    // Every place left may be held by a form already being submitted.
+    let Some(lock) = state
+        .slot_lock
+        .try_acquire(&key, &client_key, places_left)
+        .await?
+    else {
+        metrics::inc(&metrics::CONFLICTS_REJECTED);
+
+        return Err(Error::coded(
+            ErrorCode::AlreadyProcessing,
+            "Form already being processed",
+        ));
+    };
+
+    if let Some(response) = response.as_object_mut() {
+        // The client has to submit the form by then, after which its place can be held again.
+        response.insert(
+            String::from("lockExpiresAt"),
+            lock_expires_at(lock.locked_at, state.config.lock_ttl)
+                .format(&Iso8601::DEFAULT)?
+                .into(),
+        );
+        response.insert(
+            String::from("lockTtlSecs"),
+            state.config.lock_ttl.as_secs().into(),
+        );
     }
 
-    let time_format = format_description!("[hour]:[minute]:[second]");
-
-    let time = Time::parse(&time, &time_format)?;
+    state
+        .idempotency
+        .store(idempotency_key, fingerprint, response.clone())
+        .await;
 
-    let book_time =
-        Date::from_calendar_date(year as i32, Month::try_from(month)?, day)?.with_time(time);
+    Ok(Json(WrappingResponse::okay(response)))
+}
 
-    import_data_row(
+/// Checks the slot is free, responding with its times for the form and the places it has left.
+async fn free_slot_response(
+    state: &AppState,
+    uuid: UuidType,
+    schedule: CmsRowResponse,
+    staff_schedule: CmsRowResponse,
+    service_id: &str,
+    (day, month, year, time, duration_minutes): (u8, u8, usize, &str, Option<f64>),
+) -> Result<(serde_json::Value, usize)> {
+    let time_zone_str = row_text(&staff_schedule, "timeZone")?.context("Missing TimeZone")?;
+    let duration = duration_minutes.or(row_number(&schedule, "duration")?);
+
+    let (start, end, places_left) = match validate_slot_free(
+        state,
         uuid,
-        CollectionName {
-            id: String::from("bookings"),
-            ns: Some(String::from("@booking")),
+        schedule,
+        staff_schedule,
+        calendar_date(year, month, day)?,
+        time,
+        duration_minutes,
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            if matches!(
+                e,
+                Error::Coded {
+                    code: ErrorCode::AlreadyBooked,
+                    ..
+                }
+            ) {
+                metrics::inc(&metrics::CONFLICTS_REJECTED);
+            }
+
+            return Err(e);
+        }
+    };
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            service_id,
+        )
+    })
+    .await?;
+
+    let response = serde_json::json!({
+        "start": {
+            "utc": start.to_offset(UtcOffset::UTC).format(&Iso8601::DEFAULT)?,
+            "local": start.format(&Iso8601::DEFAULT)?,
         },
-        HashMap::from([
-            (
-                String::from("bookDate"),
-                format!("{year}-{month:02}-{day:02}T{time}").into(),
+        "end": {
+            "utc": end.to_offset(UtcOffset::UTC).format(&Iso8601::DEFAULT)?,
+            "local": end.format(&Iso8601::DEFAULT)?,
+        },
+        "timeZone": time_zone_str,
+        "duration": duration,
+        "serviceName": row_text(&service, "name")?,
+    });
+
+    Ok((response, places_left))
+}
+
+/// Checks that the service, schedule, staff schedule & staff member of a slot belong together.
+async fn validate_slot_links(
+    state: &AppState,
+    uuid: UuidType,
+    staff_schedule_id: &str,
+    schedule_id: &str,
+    service_id: &str,
+    staff_id: &str,
+) -> Result<(CmsRowResponse, CmsRowResponse)> {
+    bookability(state, uuid)
+        .await?
+        .ensure_owned(staff_schedule_id)?;
+
+    let schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            schedule_id,
+        )
+    })
+    .await?;
+
+    let mut staff_schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            // TODO: Multiple ids can be in here.
+            staff_schedule_id,
+        )
+    })
+    .await?;
+
+    if row_id(&schedule)? != schedule_id || row_id(&staff_schedule)? != staff_schedule_id {
+        return Err(Error::coded(
+            ErrorCode::NotOwned,
+            format!("Schedule {schedule_id} or staff schedule {staff_schedule_id} doesn't belong to this website"),
+        ));
+    }
+
+    apply_staff_time_zone(&mut staff_schedule, &staff_time_zones(state, uuid).await?)?;
+
+    ensure_slot_linked(&schedule, &staff_schedule, service_id, staff_id)?;
+
+    Ok((schedule, staff_schedule))
+}
+
+/// Checks the schedule is for the service, and the staff schedule is the staff member's under it.
+fn ensure_slot_linked(
+    schedule: &CmsRowResponse,
+    staff_schedule: &CmsRowResponse,
+    service_id: &str,
+    staff_id: &str,
+) -> Result<()> {
+    let schedule_id = row_id(schedule)?;
+    let staff_schedule_id = row_id(staff_schedule)?;
+
+    // TODO: Replace any_as_text() -> try_as_text()
+    let schedule_service_id = row_text(schedule, "service")?;
+
+    if schedule_service_id.as_deref() != Some(service_id) {
+        return Err(Error::coded(
+            ErrorCode::ServiceScheduleMismatch,
+            format!(
+                "Service ID {service_id} does not match schedule {schedule_id} (expected service {})",
+                schedule_service_id.as_deref().unwrap_or("<none>")
             ),
-            (
-                String::from("bookID"),
-                (book_time.assume_utc() - time::OffsetDateTime::UNIX_EPOCH)
-                    .whole_seconds()
-                    .to_string()
-                    .into(),
+        ));
+    }
+
+    let staff_schedule_schedule_id = row_text(staff_schedule, "schedule")?;
+
+    if staff_schedule_schedule_id.as_deref() != Some(schedule_id.as_str()) {
+        return Err(Error::coded(
+            ErrorCode::ScheduleMismatch,
+            format!(
+                "Schedule ID {schedule_id} does not match staff schedule {staff_schedule_id} (expected schedule {})",
+                staff_schedule_schedule_id.as_deref().unwrap_or("<none>")
             ),
-            (String::from("duration"), duration.into()),
-            (String::from("service"), service_id.into()),
-            (String::from("staffMember"), staff_id.into()),
-            (String::from("contactUuid"), contact_uuid.to_string().into()),
-            (
-                String::from("schemaDataUuid"),
-                schema_data_uuid.to_string().into(),
+        ));
+    }
+
+    let staff_schedule_staff_id = row_text(staff_schedule, "staff")?;
+
+    if staff_schedule_staff_id.as_deref() != Some(staff_id) {
+        return Err(Error::coded(
+            ErrorCode::StaffMismatch,
+            format!(
+                "Staff ID {staff_id} does not match staff schedule {staff_schedule_id} (expected staff {})",
+                staff_schedule_staff_id.as_deref().unwrap_or("<none>")
             ),
-        ]),
-    )
-    .await?;
+        ));
+    }
 
     Ok(())
 }
 
-async fn get_form_render(
-    Query(query): Query<HashMap<String, String>>,
+/// Checks that the slot exists in the staff schedule and has a place left.
+/// Returns the start & end of the slot in the schedule's local offset, and its places left.
+///
+/// The slot holds the service's `maxParticipants` for each of the schedule's `concurrency`.
+async fn validate_slot_free(
+    state: &AppState,
+    uuid: UuidType,
+    schedule: CmsRowResponse,
+    staff_schedule: CmsRowResponse,
+    date: Date,
+    time: &str,
+    duration_minutes: Option<f64>,
+) -> Result<(OffsetDateTime, OffsetDateTime, usize)> {
+    let service_id = row_text(&schedule, "service")?.context("Service ID")?;
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            &service_id,
+        )
+    })
+    .await?;
+
+    if duration_minutes.is_some() {
+        validate_duration_option(&service, duration_minutes)?;
+    }
+
+    let time_zone_str = row_text(&staff_schedule, "timeZone")?.context("Missing TimeZone")?;
+    let filters = book_date_filters(date, validate_time_zone(&time_zone_str)?)?;
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let occupied = Occupied::resolve(state, uuid, &staff_schedule, date, 1, bookings.items).await?;
+
+    let mut found_hours = gather_available_hours(
+        date.midnight(),
+        &service,
+        &schedule,
+        &staff_schedule,
+        &occupied,
+        duration_minutes,
+        true,
+    )?;
+
+    // Slots after midnight of an overnight schedule are listed under the day before.
+    let (open, close) = working_hours_bounds(date, &staff_schedule)?;
+
+    if let Some(previous_day) = date.previous_day().filter(|_| close.date() > open.date()) {
+        found_hours.extend(gather_available_hours(
+            previous_day.midnight(),
+            &service,
+            &schedule,
+            &staff_schedule,
+            &occupied,
+            duration_minutes,
+            true,
+        )?);
+    }
+
+    // Find the hour and check to see if it's booked.
+
+    let time_format = format_description!("[hour]:[minute]:[second]");
+
+    let time = Time::parse(time, &time_format)?;
+
+    // `start` holds the local date & time.
+    let found_hour = found_hours
+        .iter()
+        .find(|v| v.start.date() == date && v.start.time() == time)
+        .ok_or_else(|| Error::coded(ErrorCode::TimeNotFound, "Time not found"))?;
+
+    let places_left = slot_places_left(
+        slot_capacity(&service, &schedule)?,
+        found_hour.participants,
+        found_hour.is_blocked,
+    );
+
+    if places_left == 0 {
+        let alternative = next_available_slot(
+            state,
+            uuid,
+            &service,
+            &schedule,
+            &staff_schedule,
+            found_hour.start,
+            duration_minutes,
+        )
+        .await?;
+
+        return Err(booking_conflict(
+            found_hour,
+            alternative.as_ref(),
+            &time_zone_str,
+        )?);
+    }
+
+    Ok((found_hour.start, found_hour.end, places_left))
+}
+
+/// The `ALREADY_BOOKED` error of a full slot, with the alternative offered instead.
+fn booking_conflict(
+    slot: &FoundHour,
+    alternative: Option<&FoundHour>,
+    time_zone_str: &str,
+) -> Result<Error> {
+    // Only counts are given, the bookings themselves belong to other contacts.
+    Ok(Error::coded_with_details(
+        ErrorCode::AlreadyBooked,
+        "Time is already booked",
+        serde_json::json!({
+            "slot": conflict_slot_json(slot, time_zone_str)?,
+            "participants": slot.participants,
+            "blocked": slot.is_blocked,
+            "alternative": alternative
+                .map(|v| conflict_slot_json(v, time_zone_str))
+                .transpose()?,
+        }),
+    ))
+}
+
+fn conflict_slot_json(slot: &FoundHour, time_zone_str: &str) -> Result<serde_json::Value> {
+    Ok(serde_json::json!({
+        "start": slot.start.format(&Iso8601::DEFAULT)?,
+        "end": slot.end.format(&Iso8601::DEFAULT)?,
+        "timeZone": time_zone_str,
+        "serviceId": slot.service_id,
+        "scheduleId": slot.schedule_id,
+        "staffId": slot.staff_id,
+        "staffScheduleId": slot.staff_schedule_id,
+        "slotId": slot.slot_id().to_string(),
+    }))
+}
+
+/// The first free slot of the staff schedule after `after`, looking up to
+/// `ALTERNATIVE_SEARCH_DAYS` local days ahead. Offered when the chosen slot is taken.
+async fn next_available_slot(
+    state: &AppState,
+    uuid: UuidType,
+    service: &CmsRowResponse,
+    schedule: &CmsRowResponse,
+    staff_schedule: &CmsRowResponse,
+    after: OffsetDateTime,
+    duration_minutes: Option<f64>,
+) -> Result<Option<FoundHour>> {
+    let schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let schedule_repeats = schedule_repeats(&schedule_resp.items)?;
+
+    // `after` is in the schedule's local offset, so its date is the local day.
+    let first_day = after.date();
+
+    // Occurrences are in UTC, so a day either side covers every local day.
+    let lookup_time = first_day.midnight().assume_utc() - Duration::days(1);
+    let lookup_end = lookup_time + Duration::days(ALTERNATIVE_SEARCH_DAYS + 2);
+
+    let occurrences =
+        staff_schedule_occurrences(staff_schedule, lookup_time, lookup_end, &schedule_repeats)?;
+
+    let last_day = first_day + Duration::days(ALTERNATIVE_SEARCH_DAYS - 1);
+
+    let run_days = occurrences
+        .starts
+        .iter()
+        .map(|utc| utc.to_offset(occurrences.local_offset).date())
+        .filter(|day| (first_day..=last_day).contains(day))
+        .collect::<BTreeSet<_>>();
+
+    if run_days.is_empty() {
+        return Ok(None);
+    }
+
+    let filters = book_days_filters(first_day, ALTERNATIVE_SEARCH_DAYS, occurrences.local_offset)?;
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let occupied = Occupied::resolve(
+        state,
+        uuid,
+        staff_schedule,
+        first_day,
+        ALTERNATIVE_SEARCH_DAYS,
+        bookings.items,
+    )
+    .await?;
+
+    first_free_slot(
+        run_days,
+        service,
+        schedule,
+        staff_schedule,
+        &occupied,
+        duration_minutes,
+        after,
+    )
+}
+
+/// The earliest free slot on the days which starts after `after` and isn't in the past.
+fn first_free_slot(
+    run_days: BTreeSet<Date>,
+    service: &CmsRowResponse,
+    schedule: &CmsRowResponse,
+    staff_schedule: &CmsRowResponse,
+    occupied: &Occupied,
+    duration_minutes: Option<f64>,
+    after: OffsetDateTime,
+) -> Result<Option<FoundHour>> {
+    let now = OffsetDateTime::now_utc();
+
+    for day in run_days {
+        let found_hours = gather_available_hours(
+            day.midnight(),
+            service,
+            schedule,
+            staff_schedule,
+            occupied,
+            duration_minutes,
+            true,
+        )?;
+
+        if let Some(slot) = found_hours
+            .into_iter()
+            .filter(|v| !v.is_booked && v.start > after && v.start > now)
+            .min_by(|a, b| a.cmp_chronological(b))
+        {
+            return Ok(Some(slot));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Runs the checks of `form-process/before` without locking the slot.
+///
+/// Known booking conditions are returned as `valid: false` with the reason instead of an error.
+async fn get_book_validate(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(query): Query<SlotQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let duration_minutes = query.duration_minutes;
+
+    let SlotId {
+        staff_schedule_id,
+        schedule_id,
+        service_id,
+        staff_id,
+        day,
+        month,
+        year,
+        time,
+    } = query.resolve()?;
+
+    let time = time.format(&format_description!("[hour]:[minute]:[second]"))?;
+
+    let result = async {
+        let (schedule, staff_schedule) = validate_slot_links(
+            &state,
+            uuid,
+            &staff_schedule_id,
+            &schedule_id,
+            &service_id,
+            &staff_id,
+        )
+        .await?;
+
+        let (_, _, places_left) = validate_slot_free(
+            &state,
+            uuid,
+            schedule,
+            staff_schedule,
+            calendar_date(year, month, day)?,
+            &time,
+            duration_minutes,
+        )
+        .await?;
+
+        let held = state
+            .slot_lock
+            .holds(&(uuid, schedule_id.clone(), day, month, year, time.clone()))
+            .await?
+            .iter()
+            .filter(|lock| !is_lock_expired(lock.locked_at, state.config.lock_ttl))
+            .count();
+
+        if held >= places_left {
+            return Err(Error::coded(
+                ErrorCode::AlreadyProcessing,
+                "Form already being processed",
+            ));
+        }
+
+        Ok(())
+    }
+    .await;
+
+    Ok(Json(WrappingResponse::okay(validation_response(result)?)))
+}
+
+/// Why a dry-run booking would be rejected, or that it would be accepted.
+/// Errors without a code aren't the slot's fault and are returned as they are.
+fn validation_response(result: Result<()>) -> Result<serde_json::Value> {
+    match result {
+        Ok(()) => Ok(serde_json::json!({
+            "valid": true,
+            "reason": null,
+            "code": null,
+            "details": null,
+        })),
+        Err(Error::Coded {
+            code,
+            message,
+            details,
+        }) => Ok(serde_json::json!({
+            "valid": false,
+            "reason": message,
+            "code": code,
+            "details": details,
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+async fn post_form_process_error(
+    State(state): State<AppState>,
+    Query(query): Query<FormProcessQuery>,
+) -> Result<()> {
+    // Remove the form from the processing list.
+
+    let query = query.resolve()?;
+
+    let key = (
+        query.uuid,
+        query.schedule_id,
+        query.day,
+        query.month,
+        query.year,
+        query.time,
+    );
+
+    let released = state
+        .slot_lock
+        .release_if_owner(&key, &query.client_key)
+        .await?
+        .is_some();
+
+    if released {
+        metrics::inc(&metrics::LOCKS_RELEASED);
+    }
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LockStatusQuery {
+    schedule_id: String,
+    day: u8,
+    month: u8,
+    year: usize,
+    /// Local start time of the slot, eg. `09:00:00`.
+    time: String,
+}
+
+impl LockStatusQuery {
+    fn key(self, uuid: UuidType) -> LockKey {
+        (
+            uuid,
+            self.schedule_id,
+            self.day,
+            self.month,
+            self.year,
+            self.time,
+        )
+    }
+}
+
+/// Reports the holds `form-process/before` has on the slot. For diagnosing stuck locks.
+///
+/// Expired holds are still reported (with `expired`) until the next lock attempt clears them.
+async fn get_lock_status(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(query): Query<LockStatusQuery>,
 ) -> Result<JsonResponse<serde_json::Value>> {
+    let holds = state.slot_lock.holds(&query.key(uuid)).await?;
+    let now = OffsetDateTime::now_utc();
+
+    let holds = holds
+        .into_iter()
+        .map(
+            |HeldLock {
+                 client_key,
+                 locked_at,
+             }| {
+                serde_json::json!({
+                    "clientKey": client_key,
+                    "expired": is_lock_expired(locked_at, state.config.lock_ttl),
+                    "ageSecs": (now - locked_at).whole_seconds().max(0),
+                    "expiresInSecs": (lock_expires_at(locked_at, state.config.lock_ttl) - now).whole_seconds().max(0),
+                })
+            },
+        )
+        .collect::<Vec<_>>();
+
     Ok(Json(WrappingResponse::okay(serde_json::json!({
-        "data": {
-            "type": "contact",
-            "fields": [
-                {
-                    "contact_key": "firstName",
-                    "data": {
-                        "type": "input",
-                        "value": {
-                            "field_description": null,
-                            "field_title": null,
-                            "form_name": "firstName",
-                            "is_hidden": false,
-                            "is_read_only": false,
-                            "is_required": true,
-                            "placeholder": "First Name",
-                            "type_of": {
-                                "default": null,
-                                "long_text": false,
-                                "max": 30,
-                                "min": null,
-                                "personal_info": false,
-                                "type": "text",
-                                "validation": null
-                            }
-                        }
-                    },
-                    "guid": "019426bf-8acc-7636-8cad-e894caf12b6b",
-                    "id": "input1",
-                    "layer_index": 0,
-                    "offset": 0,
-                    "row_index": 0,
-                    "size": 8
-                },
-                {
-                    "contact_key": "lastName",
-                    "data": {
-                        "type": "input",
-                        "value": {
-                            "field_description": null,
-                            "field_title": null,
-                            "form_name": "lastName",
-                            "is_hidden": false,
-                            "is_read_only": false,
-                            "is_required": true,
-                            "placeholder": "Last Name",
-                            "type_of": {
-                                "default": null,
-                                "long_text": false,
-                                "max": 30,
-                                "min": null,
-                                "personal_info": false,
-                                "type": "text",
-                                "validation": null
-                            }
-                        }
-                    },
-                    "guid": "019426bf-8acc-7d7d-a789-9e82ec125a9c",
-                    "id": "input2",
-                    "layer_index": 0,
-                    "offset": 8,
-                    "row_index": 0,
-                    "size": 8
-                },
-                {
-                    "contact_key": "email",
-                    "data": {
-                        "type": "input",
-                        "value": {
-                            "field_description": null,
-                            "field_title": null,
-                            "form_name": "email",
-                            "is_hidden": false,
-                            "is_read_only": false,
-                            "is_required": true,
-                            "placeholder": "Email Address",
-                            "type_of": {
-                                "type": "email",
-                                "validation": null
-                            }
-                        }
-                    },
-                    "guid": "019426bf-8acc-712c-98fb-27e7d09e4109",
-                    "id": "input3",
-                    "layer_index": 0,
-                    "offset": 0,
-                    "row_index": 1,
-                    "size": 8
-                },
-                {
-                    "contact_key": "phone",
-                    "data": {
-                        "type": "input",
-                        "value": {
-                            "field_description": null,
-                            "field_title": null,
-                            "form_name": "phone",
-                            "is_hidden": false,
-                            "is_read_only": false,
-                            "is_required": false,
-                            "placeholder": "Phone Number",
-                            "type_of": {
-                                "format": {
-                                    "type": "default"
-                                },
-                                "type": "phone"
-                            }
-                        }
-                    },
-                    "guid": "019426bf-8acc-7338-96f9-e57e736a4131",
-                    "id": "input4",
-                    "layer_index": 0,
-                    "offset": 8,
-                    "row_index": 1,
-                    "size": 8
-                },
-                {
-                    "contact_key": null,
-                    "data": {
-                        "type": "input",
-                        "value": {
-                            "field_description": null,
-                            "field_title": null,
-                            "form_name": "message",
-                            "is_hidden": false,
-                            "is_read_only": false,
-                            "is_required": true,
-                            "placeholder": "Message",
-                            "type_of": {
-                                "default": null,
-                                "long_text": true,
-                                "max": 500,
-                                "min": null,
-                                "personal_info": false,
-                                "type": "text",
-                                "validation": null
-                            }
-                        }
-                    },
-                    "guid": "019426bf-8acc-79a7-9205-2e46b0143d3c",
-                    "id": "input5",
-                    "layer_index": 0,
-                    "offset": 0,
-                    "row_index": 2,
-                    "size": 16
-                }
-            ],
-            "store_in_submissions": false
+        "locked": holds.iter().any(|v| v["expired"] == false),
+        "holds": holds,
+    }))))
+}
+
+/// The booking already written for a form submission, if there is one.
+async fn find_submitted_booking(
+    state: &AppState,
+    uuid: UuidType,
+    schema_data_uuid: Uuid,
+    contact_uuid: Uuid,
+    client_key: &str,
+) -> Result<Option<CmsRowResponse>> {
+    let filters = vec![
+        Filter {
+            name: String::from("schemaDataUuid"),
+            cond: FilterConditionType::Eq,
+            value: FilterValue::Text(schema_data_uuid.to_string()),
+        },
+        Filter {
+            name: String::from("contactUuid"),
+            cond: FilterConditionType::Eq,
+            value: FilterValue::Text(contact_uuid.to_string()),
         },
-        "submitQuery": query
+    ];
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    submitted_booking(bookings.items, client_key)
+}
+
+/// Of the submission's bookings, the one still booked by the client.
+fn submitted_booking(
+    bookings: Vec<CmsRowResponse>,
+    client_key: &str,
+) -> Result<Option<CmsRowResponse>> {
+    for item in bookings {
+        // Bookings from before `clientKey` was stored can't be told apart by it.
+        let is_same_client = row_text(&item, "clientKey")?.is_none_or(|v| v == client_key);
+
+        if is_same_client && is_booking_occupying(&item)? {
+            return Ok(Some(item));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returned instead of booking a submission again.
+fn replayed_booking(booking: &CmsRowResponse) -> Result<serde_json::Value> {
+    Ok(serde_json::json!({
+        "reference": row_text(booking, "reference")?,
+        "replayed": true,
+    }))
+}
+
+/// A booking about to be written, checked by [`ensure_bookable`].
+struct NewBooking<'a> {
+    service: &'a CmsRowResponse,
+    schedule: &'a CmsRowResponse,
+    staff_id: &'a str,
+    contact_uuid: Uuid,
+    /// In the schedule's local offset, which picks the day of bookings checked.
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+}
+
+/// The checks every booking passes right before it's written, whether from `form-process/after`
+/// or a batch: the contact's bookings of the service on the day, then the slot's capacity.
+/// Returns the participants the slot already has.
+///
+/// `batched` are the service & start of the batch's earlier bookings, which aren't written yet.
+async fn ensure_bookable(
+    state: &AppState,
+    uuid: UuidType,
+    booking: &NewBooking<'_>,
+    batched: &[(String, OffsetDateTime)],
+) -> Result<usize> {
+    let service_id = row_id(booking.service)?;
+
+    // Other contacts are still free to book the slot for group services.
+    let mut filters = book_date_filters(booking.start.date(), booking.start.offset())?;
+
+    filters.push(Filter {
+        name: String::from("service"),
+        cond: FilterConditionType::Eq,
+        value: FilterValue::Text(service_id.clone()),
+    });
+
+    filters.push(Filter {
+        name: String::from("contactUuid"),
+        cond: FilterConditionType::Eq,
+        value: FilterValue::Text(booking.contact_uuid.to_string()),
+    });
+
+    let contact_bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let mut booked = Vec::new();
+
+    for item in &contact_bookings.items {
+        if is_booking_occupying(item)? {
+            booked.push(booking_start(item, booking.start.offset())?);
+        }
+    }
+
+    if let Err(e) = check_contact_bookings(
+        &service_id,
+        booking.start,
+        row_number(booking.service, "maxPerContactPerDay")?,
+        &booked,
+        batched,
+    ) {
+        metrics::inc(&metrics::CONFLICTS_REJECTED);
+
+        return Err(e);
+    }
+
+    let booked_duration = booking.end - booking.start;
+
+    ensure_slot_capacity(
+        state,
+        uuid,
+        booking.service,
+        booking.schedule,
+        booking.staff_id,
+        (booking.start, booking.end),
+        row_number(booking.schedule, "duration")?
+            .map(minutes_to_duration)
+            .unwrap_or(booked_duration),
+    )
+    .await
+}
+
+/// Rejects a booking of the service at `start` if the contact already has one then, or already
+/// has the service's `maxPerContactPerDay` on the day. Unlimited when not set or `0`.
+///
+/// `booked` are the starts of the contact's occupying bookings of the service on the day.
+fn check_contact_bookings(
+    service_id: &str,
+    start: OffsetDateTime,
+    max_per_day: Option<f64>,
+    booked: &[OffsetDateTime],
+    batched: &[(String, OffsetDateTime)],
+) -> Result<()> {
+    let batched = batched
+        .iter()
+        .filter(|(id, batched_start)| {
+            id == service_id && batched_start.to_offset(start.offset()).date() == start.date()
+        })
+        .map(|(_, batched_start)| *batched_start);
+
+    let day_starts = booked.iter().copied().chain(batched).collect::<Vec<_>>();
+
+    if day_starts.contains(&start) {
+        return Err(Error::coded(
+            ErrorCode::DuplicateBooking,
+            "Contact has already booked this time",
+        ));
+    }
+
+    if let Some(max_per_day) = max_per_day.filter(|v| *v > 0.0) {
+        if day_starts.len() as f64 >= max_per_day {
+            return Err(Error::coded(
+                ErrorCode::ContactDailyLimitReached,
+                format!(
+                    "Contact has already made {} bookings of this service on this day",
+                    day_starts.len()
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Rejects the booking if the slot filled up since `form-process/before` checked it.
+/// Returns the participants the slot already has.
+///
+/// The lock in [`AppState::slot_lock`] may only cover this instance, so another replica may have
+/// written a booking since. The slot holds the service's `maxParticipants` for each of the
+/// schedule's `concurrency`, 1 by default, and none if the staff blocked it.
+///
+/// `start` is in the schedule's local offset, which picks the day of bookings checked.
+async fn ensure_slot_capacity(
+    state: &AppState,
+    uuid: UuidType,
+    service: &CmsRowResponse,
+    schedule: &CmsRowResponse,
+    staff_id: &str,
+    (start, end): (OffsetDateTime, OffsetDateTime),
+    schedule_duration: Duration,
+) -> Result<usize> {
+    let filters = book_date_filters(start.date(), start.offset())?;
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let capacity = slot_capacity(service, schedule)?;
+
+    let mut occupying = Vec::new();
+
+    for item in &bookings.items {
+        if row_text(item, "staffMember")?.is_some_and(|id| id != staff_id)
+            || !is_booking_occupying(item)?
+        {
+            continue;
+        }
+
+        let booked_start = booking_start(item, start.offset())?;
+        let booked_end = booked_start
+            + row_number(item, "duration")?
+                .map(minutes_to_duration)
+                .filter(|v| v.is_positive())
+                .unwrap_or(schedule_duration);
+
+        occupying.push(OccupyingBooking {
+            start: booked_start,
+            end: booked_end,
+            is_block: row_text(item, "type")?.as_deref() == Some(BOOKING_TYPE_BLOCK),
+        });
+    }
+
+    check_slot_capacity((start, end), capacity, &occupying)
+}
+
+/// A booking or block of the staff member, as counted against a slot's capacity.
+struct OccupyingBooking {
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    is_block: bool,
+}
+
+/// Counts the bookings overlapping the slot, erroring when they fill its `capacity`.
+/// A block takes up every place.
+fn check_slot_capacity(
+    (start, end): (OffsetDateTime, OffsetDateTime),
+    capacity: usize,
+    occupying: &[OccupyingBooking],
+) -> Result<usize> {
+    let booked = occupying
+        .iter()
+        .filter(|v| v.start < end && start < v.end)
+        .map(|v| if v.is_block { capacity } else { 1 })
+        .sum::<usize>();
+
+    if booked >= capacity {
+        metrics::inc(&metrics::CONFLICTS_REJECTED);
+
+        return Err(Error::coded(
+            ErrorCode::AlreadyBooked,
+            "Time was booked while the form was being submitted",
+        ));
+    }
+
+    Ok(booked)
+}
+
+/// Forcibly releases a slot's holds, eg. ones abandoned by clients before the TTL releases them.
+///
+/// Responds with whether a hold was on the slot.
+async fn delete_lock(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(uuid): Path<UuidType>,
+    Query(query): Query<LockStatusQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let key = query.key(uuid);
+
+    let removed = state.slot_lock.release(&key).await?;
+
+    for HeldLock {
+        client_key,
+        locked_at,
+    } in &removed
+    {
+        metrics::inc(&metrics::LOCKS_RELEASED);
+
+        warn!(
+            "Lock on {key:?} held by {client_key} for {}s was cleared by {addr}",
+            (OffsetDateTime::now_utc() - *locked_at).whole_seconds()
+        );
+    }
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "cleared": !removed.is_empty(),
     }))))
 }
 
-//
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FormProcessJson {
+    contact_uuid: Uuid,
+    schema_data_uuid: Uuid,
+}
+
+/// Form field keys which are stored on the contact rather than the booking.
+const CONTACT_FIELD_KEYS: [&str; 4] = ["firstName", "lastName", "email", "phone"];
+
+/// Form field key of the free text message, stored as the booking `notes`.
+const MESSAGE_FIELD_KEY: &str = "message";
+
+/// Values of the form submission stored under `schema_data_uuid`, keyed by form field key.
+///
+/// Submissions are rows of the form's collection. Empty when the service has no form.
+async fn submission_values(
+    state: &AppState,
+    uuid: UuidType,
+    service: &CmsRowResponse,
+    schema_data_uuid: Uuid,
+) -> Result<HashMap<String, String>> {
+    let Some(form_id) = row_text(service, "formId")?.filter(|v| !v.is_empty()) else {
+        return Ok(HashMap::new());
+    };
+
+    let submission = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: form_id.clone(),
+                ns: None,
+            },
+            schema_data_uuid,
+        )
+    })
+    .await?;
+
+    submission
+        .fields
+        .iter()
+        .filter_map(|(key, value)| match key {
+            SchematicFieldKey::Other(key) => Some((key, value)),
+            _ => None,
+        })
+        .map(|(key, value)| Ok((key.clone(), value.any_as_text()?)))
+        .collect()
+}
+
+/// Splits submitted values into the booking `notes` & its custom fields.
+///
+/// The contact's own fields are left out, they're already stored on the contact.
+fn submission_details(
+    mut values: HashMap<String, String>,
+) -> (Option<String>, HashMap<String, String>) {
+    let notes = values
+        .remove(MESSAGE_FIELD_KEY)
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+
+    values.retain(|key, _| !CONTACT_FIELD_KEYS.contains(&key.as_str()));
+
+    (notes, values)
+}
+
+async fn post_form_process_after(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<FormProcessQuery>,
+    Json(FormProcessJson {
+        contact_uuid,
+        schema_data_uuid,
+    }): Json<FormProcessJson>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let process = query.resolve()?;
+    let fingerprint =
+        serde_json::json!([process.fingerprint(), contact_uuid, schema_data_uuid]).to_string();
+
+    let FormProcess {
+        client_key,
+        uuid,
+        staff_schedule_id,
+        schedule_id,
+        service_id,
+        staff_id,
+        day,
+        month,
+        year,
+        time,
+        duration_minutes,
+    } = process;
+
+    let idempotency_key = idempotency::key("after", uuid, &headers);
+
+    if let Some(response) = state
+        .idempotency
+        .replay(idempotency_key.as_ref(), &fingerprint)
+        .await?
+    {
+        return Ok(Json(WrappingResponse::okay(response)));
+    }
+
+    // The booking was written but the response was lost, eg. without an idempotency key.
+    // Its lock was already released, so the retry would otherwise fail or book twice.
+    if let Some(booking) =
+        find_submitted_booking(&state, uuid, schema_data_uuid, contact_uuid, &client_key).await?
+    {
+        let response = replayed_booking(&booking)?;
+
+        state
+            .idempotency
+            .store(idempotency_key, fingerprint, response.clone())
+            .await;
+
+        return Ok(Json(WrappingResponse::okay(response)));
+    }
+
+    // Remove the form from the processing list.
+
+    let key = (uuid, schedule_id, day, month, year, time.clone());
+
+    let schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &key.1,
+        )
+    })
+    .await?;
+
+    let duration = schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("duration")))
+        .context("Service Duration")?
+        .try_as_number()?;
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            &service_id,
+        )
+    })
+    .await?;
+
+    let currency = service_currency(&state.config, &service)?;
+
+    validate_duration_option(&service, duration_minutes)?;
+
+    let booked_duration = minutes_to_duration(
+        duration_minutes
+            .or(row_number(&schedule, "duration")?)
+            .unwrap_or_default(),
+    );
+
+    // The customer's chosen length replaces the schedule's.
+    let duration: SimpleValue = match duration_minutes {
+        Some(minutes) => serde_json::json!(minutes).into(),
+        None => duration.into(),
+    };
+
+    let HeldLock { locked_at, .. } = state
+        .slot_lock
+        .release_if_owner(&key, &client_key)
+        .await?
+        .context("Process not found")?;
+
+    if is_lock_expired(locked_at, state.config.lock_ttl) {
+        metrics::inc(&metrics::LOCKS_EXPIRED);
+
+        return Err(Error::coded(
+            ErrorCode::LockExpired,
+            "The slot lock expired before the form was submitted",
+        ));
+    }
+
+    let time_format = format_description!("[hour]:[minute]:[second]");
+
+    let time = Time::parse(&time, &time_format)?;
+
+    let book_time = calendar_date(year, month, day)?.with_time(time);
+
+    let mut staff_schedule = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &staff_schedule_id,
+        )
+    })
+    .await?;
+
+    apply_staff_time_zone(&mut staff_schedule, &staff_time_zones(&state, uuid).await?)?;
+
+    let time_zone_str = row_text(&staff_schedule, "timeZone")?.context("Missing TimeZone")?;
+    let local_offset = validate_time_zone(&time_zone_str)?;
+
+    // The requested day & time are in the schedule's local time.
+    let book_start = book_time.assume_offset(local_offset);
+
+    let staff = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staff"),
+                ns: Some(String::from("@booking")),
+            },
+            &staff_id,
+        )
+    })
+    .await?;
+
+    let submission_link = template::submission_link(
+        &state.config,
+        &schema_data_uuid.to_string(),
+        &contact_uuid.to_string(),
+    );
+
+    let calendar_staff_id = staff_id.clone();
+
+    // Values for the email placeholders. `book_time` is already in the schedule's local time.
+    let template_values = template::email_values(
+        template::format_booking_date_time(book_time, &time_zone_str)?,
+        row_text(&service, "name")?.unwrap_or_default(),
+        row_text(&staff, "staffName")?.unwrap_or_default(),
+        submission_link.clone(),
+    );
+
+    let (notes, fields) =
+        submission_details(submission_values(&state, uuid, &service, schema_data_uuid).await?);
+
+    let reference = unique_booking_reference(&state, uuid).await?;
+
+    let mut booking_fields = HashMap::from([
+        (
+            String::from("bookDate"),
+            book_date_value(book_start)?.into(),
+        ),
+        (String::from("bookID"), book_id_value(book_start).into()),
+        (String::from("reference"), reference.clone().into()),
+        (String::from("type"), BOOKING_TYPE_BOOKING.into()),
+        (String::from("status"), BOOKING_STATUS_CONFIRMED.into()),
+        (
+            String::from("createdAt"),
+            book_date_value(OffsetDateTime::now_utc())?.into(),
+        ),
+        (String::from("source"), BOOKING_SOURCE_WIDGET.into()),
+        (String::from("duration"), duration),
+        (String::from("currency"), currency.into()),
+        (String::from("service"), service_id.into()),
+        (String::from("staffMember"), staff_id.into()),
+        (String::from("contactUuid"), contact_uuid.to_string().into()),
+        (
+            String::from("schemaDataUuid"),
+            schema_data_uuid.to_string().into(),
+        ),
+        (String::from("submissionLink"), submission_link.into()),
+        (String::from("clientKey"), client_key.into()),
+    ]);
+
+    if let Some(notes) = notes {
+        booking_fields.insert(String::from("notes"), notes.into());
+    }
+
+    let payment = service_payment(&service)?;
+
+    if let Some(deposit_amount) = payment.deposit_amount {
+        booking_fields.insert(
+            String::from("depositAmount"),
+            serde_json::json!(deposit_amount).into(),
+        );
+    }
+
+    booking_fields.insert(
+        String::from("balanceDue"),
+        serde_json::json!(payment.balance_due).into(),
+    );
+
+    if !fields.is_empty() {
+        booking_fields.insert(
+            String::from("customFields"),
+            serde_json::to_value(&fields)?.into(),
+        );
+    }
+
+    let participants = ensure_bookable(
+        &state,
+        uuid,
+        &NewBooking {
+            service: &service,
+            schedule: &schedule,
+            staff_id: &calendar_staff_id,
+            contact_uuid,
+            start: book_start,
+            end: book_start + booked_duration,
+        },
+        &[],
+    )
+    .await?
+        + 1;
+
+    retry::write(&state.config, || {
+        import_data_row(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            booking_fields.clone(),
+        )
+    })
+    .await?;
+
+    metrics::inc(&metrics::BOOKINGS_CREATED);
+    state.availability_cache.invalidate(uuid).await;
+
+    google_calendar::push_booking(
+        &state,
+        uuid,
+        calendar_staff_id,
+        template_values["serviceName"].clone(),
+        template_values["submissionLink"].clone(),
+        book_start,
+        book_start + booked_duration,
+    );
+
+    let response = serde_json::json!({
+        "reference": reference,
+        "participants": participants,
+        "sessionStatus": SessionStatus::of(participants, service_min_participants(&service)?),
+    });
+
+    state
+        .idempotency
+        .store(idempotency_key, fingerprint, response.clone())
+        .await;
+
+    Ok(Json(WrappingResponse::okay(response)))
+}
+
+/// Length & following break of every slot of the schedule, in minutes.
+/// The chosen length replaces the schedule's.
+fn slot_minutes(
+    schedule: &CmsRowResponse,
+    duration_minutes: Option<f64>,
+) -> Result<(Option<f64>, f64)> {
+    Ok((
+        duration_minutes.or(row_number(schedule, "duration")?),
+        row_number(schedule, "break")?.unwrap_or_default(),
+    ))
+}
+
+/// Adds the booking form to an availability response when asked for with `includeForm`.
+fn embed_form(response: &mut serde_json::Value, include_form: bool) {
+    if include_form {
+        response["form"] = form_definition();
+    }
+}
+
+/// The booking form's fields, as rendered by `/form-render`.
+fn form_definition() -> serde_json::Value {
+    serde_json::json!({
+        "type": "contact",
+        "fields": [
+            {
+                "contact_key": "firstName",
+                "data": {
+                    "type": "input",
+                    "value": {
+                        "field_description": null,
+                        "field_title": null,
+                        "form_name": "firstName",
+                        "is_hidden": false,
+                        "is_read_only": false,
+                        "is_required": true,
+                        "placeholder": "First Name",
+                        "type_of": {
+                            "default": null,
+                            "long_text": false,
+                            "max": 30,
+                            "min": null,
+                            "personal_info": false,
+                            "type": "text",
+                            "validation": null
+                        }
+                    }
+                },
+                "guid": "019426bf-8acc-7636-8cad-e894caf12b6b",
+                "id": "input1",
+                "layer_index": 0,
+                "offset": 0,
+                "row_index": 0,
+                "size": 8
+            },
+            {
+                "contact_key": "lastName",
+                "data": {
+                    "type": "input",
+                    "value": {
+                        "field_description": null,
+                        "field_title": null,
+                        "form_name": "lastName",
+                        "is_hidden": false,
+                        "is_read_only": false,
+                        "is_required": true,
+                        "placeholder": "Last Name",
+                        "type_of": {
+                            "default": null,
+                            "long_text": false,
+                            "max": 30,
+                            "min": null,
+                            "personal_info": false,
+                            "type": "text",
+                            "validation": null
+                        }
+                    }
+                },
+                "guid": "019426bf-8acc-7d7d-a789-9e82ec125a9c",
+                "id": "input2",
+                "layer_index": 0,
+                "offset": 8,
+                "row_index": 0,
+                "size": 8
+            },
+            {
+                "contact_key": "email",
+                "data": {
+                    "type": "input",
+                    "value": {
+                        "field_description": null,
+                        "field_title": null,
+                        "form_name": "email",
+                        "is_hidden": false,
+                        "is_read_only": false,
+                        "is_required": true,
+                        "placeholder": "Email Address",
+                        "type_of": {
+                            "type": "email",
+                            "validation": null
+                        }
+                    }
+                },
+                "guid": "019426bf-8acc-712c-98fb-27e7d09e4109",
+                "id": "input3",
+                "layer_index": 0,
+                "offset": 0,
+                "row_index": 1,
+                "size": 8
+            },
+            {
+                "contact_key": "phone",
+                "data": {
+                    "type": "input",
+                    "value": {
+                        "field_description": null,
+                        "field_title": null,
+                        "form_name": "phone",
+                        "is_hidden": false,
+                        "is_read_only": false,
+                        "is_required": false,
+                        "placeholder": "Phone Number",
+                        "type_of": {
+                            "format": {
+                                "type": "default"
+                            },
+                            "type": "phone"
+                        }
+                    }
+                },
+                "guid": "019426bf-8acc-7338-96f9-e57e736a4131",
+                "id": "input4",
+                "layer_index": 0,
+                "offset": 8,
+                "row_index": 1,
+                "size": 8
+            },
+            {
+                "contact_key": null,
+                "data": {
+                    "type": "input",
+                    "value": {
+                        "field_description": null,
+                        "field_title": null,
+                        "form_name": "message",
+                        "is_hidden": false,
+                        "is_read_only": false,
+                        "is_required": true,
+                        "placeholder": "Message",
+                        "type_of": {
+                            "default": null,
+                            "long_text": true,
+                            "max": 500,
+                            "min": null,
+                            "personal_info": false,
+                            "type": "text",
+                            "validation": null
+                        }
+                    }
+                },
+                "guid": "019426bf-8acc-79a7-9205-2e46b0143d3c",
+                "id": "input5",
+                "layer_index": 0,
+                "offset": 0,
+                "row_index": 2,
+                "size": 16
+            }
+        ],
+        "store_in_submissions": false
+    })
+}
+
+async fn get_form_render(
+    Query(WebsiteQuery { uuid }): Query<WebsiteQuery>,
+    Query(query): Query<SlotQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let slot = query.resolve()?;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "data": form_definition(),
+        "submitQuery": {
+            "uuid": uuid,
+            "slotId": slot.to_string(),
+            "staffScheduleId": slot.staff_schedule_id,
+            "scheduleId": slot.schedule_id,
+            "serviceId": slot.service_id,
+            "staffId": slot.staff_id,
+            "day": slot.day,
+            "month": slot.month,
+            "year": slot.year,
+            "time": slot.time.format(&format_description!("[hour]:[minute]:[second]"))?,
+        }
+    }))))
+}
+
+//
+
+/// Whether a group session has enough participants to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SessionStatus {
+    /// At least the service's `minParticipants` have booked.
+    Confirmed,
+    /// Runs once more participants book.
+    Tentative,
+}
+
+impl SessionStatus {
+    fn of(participants: usize, min_participants: usize) -> Self {
+        if participants >= min_participants {
+            Self::Confirmed
+        } else {
+            Self::Tentative
+        }
+    }
+}
+
+/// How a local time falls on a daylight saving transition of its time zone.
+///
+/// Slots always use the time zone's fixed offset, so a flagged slot keeps its wall clock time
+/// at that offset rather than being moved, eg. a 02:30 slot in a spring forward gap stays at
+/// 02:30 in the standard offset. The widget can warn about or hide them.
+///
+/// The offset isn't resolved for each slot's date, so across the year the instants of the slots
+/// are off by the DST shift in the months the time zone observes it. Their wall clock times stay
+/// the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum DstWarning {
+    /// Skipped when the clocks spring forward, eg. 02:30 on the second Sunday of March in New York.
+    Gap,
+    /// Happens twice when the clocks fall back, eg. 01:30 on the first Sunday of November in New York.
+    Overlap,
+}
+
+impl DstWarning {
+    /// `None` when `local` happens exactly once in the time zone or the zone isn't known.
+    fn check(time_zone: &str, local: PrimitiveDateTime) -> Option<Self> {
+        let tz = time_tz::timezones::get_by_name(time_zone)?;
+
+        match local.assume_timezone(tz) {
+            OffsetResult::Some(_) => None,
+            OffsetResult::Ambiguous(..) => Some(Self::Overlap),
+            OffsetResult::None => Some(Self::Gap),
+        }
+    }
+}
+
+/// Why a day has no bookable slot, so the widget can explain it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum UnavailableReason {
+    /// The staff schedule is paused.
+    Inactive,
+    /// The working hours are too short for a single slot.
+    NoWorkingHours,
+    /// There are slots, but none between `fromHour` & `toHour`.
+    OutsideHours,
+    /// Every slot is blocked off by the staff.
+    Blocked,
+    /// Every slot is taken.
+    FullyBooked,
+}
+
+impl UnavailableReason {
+    /// `None` when at least one slot in the window can be booked.
+    fn classify(is_active: bool, found: &[FoundHour], window: &[&FoundHour]) -> Option<Self> {
+        if !is_active {
+            Some(Self::Inactive)
+        } else if found.is_empty() {
+            Some(Self::NoWorkingHours)
+        } else if window.is_empty() {
+            Some(Self::OutsideHours)
+        } else if window.iter().all(|v| v.is_blocked) {
+            Some(Self::Blocked)
+        } else if window.iter().all(|v| v.is_booked) {
+            Some(Self::FullyBooked)
+        } else {
+            None
+        }
+    }
+}
+
+/// What already takes up a staff member's time on the listed days.
+struct Occupied {
+    bookings: Vec<CmsRowResponse>,
+    /// Busy periods of the staff member's synced calendar.
+    busy: Vec<(OffsetDateTime, OffsetDateTime)>,
+}
+
+impl Occupied {
+    /// Looks up the busy periods of the staff schedule's staff member over `days` local days.
+    async fn resolve(
+        state: &AppState,
+        uuid: UuidType,
+        staff_schedule: &CmsRowResponse,
+        first_day: Date,
+        days: i64,
+        bookings: Vec<CmsRowResponse>,
+    ) -> Result<Self> {
+        let Some(staff_id) = row_text(staff_schedule, "staff")? else {
+            return Ok(Self {
+                bookings,
+                busy: Vec::new(),
+            });
+        };
+
+        let time_zone_str = row_text(staff_schedule, "timeZone")?.context("Missing TimeZone")?;
+        let start = first_day
+            .midnight()
+            .assume_offset(validate_time_zone(&time_zone_str)?);
+        let end = start + Duration::days(days);
+
+        let mut busy = google_calendar::busy_periods(state, uuid, &staff_id, start, end).await;
+
+        busy.extend(time_off_periods(state, uuid, &staff_id, start, end).await?);
+
+        Ok(Self { bookings, busy })
+    }
+}
+
+/// Occurrences of the staff member's non working hours schedules (eg. time off) between `start` & `end`.
+async fn time_off_periods(
+    state: &AppState,
+    uuid: UuidType,
+    staff_id: &str,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+) -> Result<Vec<(OffsetDateTime, OffsetDateTime)>> {
+    let staff_schedules = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(vec![Filter {
+                    name: String::from("staff"),
+                    cond: FilterConditionType::Eq,
+                    value: FilterValue::Text(staff_id.to_string()),
+                }]),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let staff_time_zones = staff_time_zones(state, uuid).await?;
+
+    let mut periods = Vec::new();
+
+    for mut row in staff_schedules.items {
+        if is_working_hours(&row)? || !is_staff_schedule_active(&row)? {
+            continue;
+        }
+
+        apply_staff_time_zone(&mut row, &staff_time_zones)?;
+
+        // Starting a day early catches time off running into the window from the day before.
+        let occurrences =
+            staff_schedule_occurrences(&row, start - Duration::DAY, end, &HashMap::new())?;
+
+        let mut length = occurrences.end_time - occurrences.start_time;
+
+        // Overnight time off ends the next day.
+        if !length.is_positive() {
+            length += Duration::DAY;
+        }
+
+        periods.extend(
+            occurrences
+                .starts
+                .into_iter()
+                .map(|utc| (utc, utc + length))
+                .filter(|(_, period_end)| *period_end > start),
+        );
+    }
+
+    Ok(periods)
+}
+
+#[derive(Debug)]
+struct FoundHour {
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    is_booked: bool,
+    /// Occupied by a staff block rather than a customer booking.
+    is_blocked: bool,
+    service_id: String,
+    schedule_id: String,
+    staff_id: String,
+    staff_schedule_id: String,
+    /// Set when the slot starts or ends on a DST transition of its time zone.
+    dst_warning: Option<DstWarning>,
+    /// Customer bookings overlapping the slot.
+    participants: usize,
+    /// More bookings the slot can take under the schedule's `concurrency`. 0 when blocked.
+    remaining: usize,
+}
+
+impl FoundHour {
+    /// Orders by `start`, then by staff member so slots at the same time keep a stable order.
+    fn cmp_chronological(&self, other: &Self) -> std::cmp::Ordering {
+        (self.start, &self.staff_id, &self.staff_schedule_id).cmp(&(
+            other.start,
+            &other.staff_id,
+            &other.staff_schedule_id,
+        ))
+    }
+
+    fn slot_id(&self) -> SlotId {
+        SlotId {
+            staff_schedule_id: self.staff_schedule_id.clone(),
+            schedule_id: self.schedule_id.clone(),
+            service_id: self.service_id.clone(),
+            staff_id: self.staff_id.clone(),
+            // `start` holds the local date & time relabelled as UTC.
+            day: self.start.day(),
+            month: self.start.month() as u8,
+            year: self.start.year() as usize,
+            time: self.start.time(),
+        }
+    }
+}
+
+fn gather_available_hours(
+    list_date: PrimitiveDateTime,
+    service: &CmsRowResponse,
+    schedule: &CmsRowResponse,
+    staff_schedule: &CmsRowResponse,
+    occupied: &Occupied,
+    duration_minutes: Option<f64>,
+    raw_offsets: bool,
+) -> Result<Vec<FoundHour>> {
+    // Paused schedules (eg. staff on leave) and time off have no slots.
+    if !is_staff_schedule_active(staff_schedule)? || !is_working_hours(staff_schedule)? {
+        return Ok(Vec::new());
+    }
+
+    let time_zone_str =
+        required_field(staff_schedule, "staffSchedule", "timeZone")?.any_as_text()?;
+    let local_offset = validate_time_zone(&time_zone_str)?;
+
+    let staff_id = required_field(staff_schedule, "staffSchedule", "staff")?.any_as_text()?;
+
+    let schedule_duration = minutes_to_duration(
+        required_field(schedule, "schedule", "duration")?
+            .try_as_number()?
+            .convert_f64(),
+    );
+
+    if !schedule_duration.is_positive() {
+        return Err(Error::coded(
+            ErrorCode::MissingField,
+            format!(
+                "schedule row {} has no positive `duration`",
+                row_id(schedule)?
+            ),
+        ));
+    }
+
+    let service_id = row_id(service)?;
+    let capacity = slot_capacity(service, schedule)?;
+
+    // Services with `durationOptions` let the customer choose the slot length.
+    let duration = duration_minutes
+        .map(minutes_to_duration)
+        .filter(|v| v.is_positive())
+        .unwrap_or(schedule_duration);
+
+    // Start & end of each booking and whether it's a block rather than a customer booking.
+    // A staff member can't be in two places at once, so their bookings under any schedule count.
+    // Bookings without a staff member are kept as we can't tell who they're with.
+    let mut booked_times = Vec::new();
+
+    for item in &occupied.bookings {
+        if row_text(item, "staffMember")?.is_some_and(|id| id != staff_id)
+            || !is_booking_occupying(item)?
+        {
+            continue;
+        }
+
+        let booked_start = booking_start(item, local_offset)?.to_offset(local_offset);
+
+        // Bookings made before durations could vary don't differ from the schedule's.
+        let booked_duration = row_number(item, "duration")?
+            .map(minutes_to_duration)
+            .filter(|v| v.is_positive())
+            .unwrap_or(schedule_duration);
+
+        booked_times.push((
+            booked_start,
+            booked_start + booked_duration,
+            row_text(item, "type")?.as_deref() == Some(BOOKING_TYPE_BLOCK),
+        ));
+    }
+
+    // Busy periods of a synced calendar block slots like a staff block does.
+    for (busy_start, busy_end) in &occupied.busy {
+        booked_times.push((
+            busy_start.to_offset(local_offset),
+            busy_end.to_offset(local_offset),
+            true,
+        ));
+    }
+
+    // println!("{bookings:#?}");
+    // println!("{booked_times:?}");
+
+    // No break between slots if it isn't set.
+    let break_duration = schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("break")))
+        .map(|v| v.try_as_number())
+        .transpose()?
+        .map(|v| minutes_to_duration(v.convert_f64()))
+        .unwrap_or(Duration::ZERO);
+
+    // Slots start on a fixed grid when set, otherwise back-to-back.
+    // Overlap checks still use the full `duration`.
+    let slot_step = schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("slotInterval")))
+        .map(|v| v.try_as_number())
+        .transpose()?
+        .map(|v| minutes_to_duration(v.convert_f64()))
+        .filter(|v| v.is_positive())
+        .unwrap_or(duration + break_duration);
+
+    // Slots start on clean boundaries when set, eg. 15 for :00, :15, :30 & :45.
+    // Applies to the start of the working hours and the end of each break. Off by default.
+    let round_to = schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("roundToMinutes")))
+        .map(|v| v.try_as_number())
+        .transpose()?
+        .map(|v| minutes_to_duration(v.convert_f64()))
+        .filter(|v| v.is_positive());
+
+    // schedule.fields.get(&SchematicFieldKey::Other(String::from("serviceSchedule"))) (not used yet)
+
+    // service.fields.get(&SchematicFieldKey::Other(String::from("priceAmount")))
+    // service.fields.get(&SchematicFieldKey::Other(String::from("paymentType")))
+    // service.fields.get(&SchematicFieldKey::Other(String::from("name")))
+    // service.fields.get(&SchematicFieldKey::Other(String::from("type")))
+
+    // println!("{staff_schedule:#?}");
+    // println!("{service:#?}");
+
+    let mut available_hours = Vec::new();
+
+    {
+        // let date_format = format_description!("[year]-[month]-[day]");
+
+        // let start_date = staff_schedule
+        //     .fields
+        //     .remove(&SchematicFieldKey::Other(String::from("startDay")))
+        //     .context("Missing startDay field")?;
+
+        let (open, close) = working_hours_bounds(list_date.date(), staff_schedule)?;
+
+        let schedule_id = row_id(schedule)?;
+        let staff_schedule_id = row_id(staff_schedule)?;
+
+        // Breaks before the opening time fall after midnight of an overnight schedule.
+        let breaks = staff_schedule_breaks(staff_schedule)?
+            .into_iter()
+            .map(|(start, end)| {
+                let day = if start < open.time() && close.date() > open.date() {
+                    close.date()
+                } else {
+                    open.date()
+                };
+
+                (day.with_time(start), day.with_time(end))
+            })
+            .collect::<Vec<_>>();
+
+        // Positions are in local time, so the bounds carry the date across midnight.
+        let mut current_pos = round_up_date_time(open, round_to);
+
+        while current_pos + duration + break_duration <= close {
+            let slot_start = current_pos;
+            let slot_end = slot_start + duration;
+
+            // Resume generating slots once the overlapping break is over.
+            if let Some((_, break_end)) = breaks
+                .iter()
+                .find(|(start, end)| slot_start < *end && *start < slot_end)
+            {
+                current_pos = round_up_date_time(*break_end, round_to);
+                continue;
+            }
+
+            // We don't convert to UTC since the working hours are in local offset time.
+            let current_time_pos = current_pos.assume_offset(local_offset);
+
+            // TODO: Replace w/ UTC offset temporarily to fix JavaScript Date
+            let slot_pos = if raw_offsets {
+                current_time_pos
+            } else {
+                current_time_pos.replace_offset(UtcOffset::UTC)
+            };
+
+            // Bookings overlapping the slot, for its full (possibly chosen) duration.
+            let overlapping = booked_times.iter().filter(|(booked_start, booked_end, _)| {
+                *booked_start < current_time_pos + duration && current_time_pos < *booked_end
+            });
+
+            let local_start = slot_start;
+
+            let is_blocked = overlapping.clone().any(|(_, _, is_block)| *is_block);
+            let participants = overlapping.filter(|(_, _, is_block)| !is_block).count();
+            let remaining = slot_places_left(capacity, participants, is_blocked);
+
+            available_hours.push(FoundHour {
+                start: slot_pos,
+                end: (slot_pos + duration),
+                dst_warning: DstWarning::check(&time_zone_str, local_start)
+                    .or_else(|| DstWarning::check(&time_zone_str, local_start + duration)),
+                is_booked: remaining == 0,
+                is_blocked,
+                participants,
+                remaining,
+                service_id: service_id.clone(),
+                schedule_id: schedule_id.clone(),
+                staff_id: staff_id.clone(),
+                staff_schedule_id: staff_schedule_id.clone(),
+            });
+
+            current_pos += slot_step;
+        }
+    }
+
+    Ok(available_hours)
+}
+
+/// Local opening & closing of a staff schedule's working hours on `day`.
+/// Overnight schedules, which end at or before they start, close the next day.
+fn working_hours_bounds(
+    day: Date,
+    staff_schedule: &CmsRowResponse,
+) -> Result<(PrimitiveDateTime, PrimitiveDateTime)> {
+    let start_time = parse_schedule_time(
+        &required_field(staff_schedule, "staffSchedule", "start")?.any_as_text()?,
+    )?;
+    let end_time = parse_schedule_time(
+        &required_field(staff_schedule, "staffSchedule", "end")?.any_as_text()?,
+    )?;
+
+    hours_bounds(day, start_time, end_time)
+}
+
+fn hours_bounds(
+    day: Date,
+    start_time: Time,
+    end_time: Time,
+) -> Result<(PrimitiveDateTime, PrimitiveDateTime)> {
+    let close_day = if end_time <= start_time {
+        day.next_day().context("End day")?
+    } else {
+        day
+    };
+
+    Ok((day.with_time(start_time), close_day.with_time(end_time)))
+}
+
+/// Rounds `pos` up like [`round_up_time`], moving on to the next midnight once it's passed.
+fn round_up_date_time(pos: PrimitiveDateTime, step: Option<Duration>) -> PrimitiveDateTime {
+    match round_up_time(pos.time(), step) {
+        Some(time) => pos.replace_time(time),
+        None => pos.date().midnight() + Duration::DAY,
+    }
+}
+
+/// A staff schedule's working hours and when it runs within a lookup window.
+struct ScheduleOccurrences {
+    time_zone_str: String,
+    local_offset: UtcOffset,
+    /// Local start of the working hours.
+    start_time: Time,
+    /// Local end of the working hours.
+    end_time: Time,
+    /// UTC start of every occurrence, in order.
+    starts: Vec<OffsetDateTime>,
+}
+
+fn staff_schedule_occurrences(
+    item: &CmsRowResponse,
+    lookup_time: OffsetDateTime,
+    lookup_end: OffsetDateTime,
+    schedule_repeats: &HashMap<String, usize>,
+) -> Result<ScheduleOccurrences> {
+    let date_format = format_description!("[year]-[month]-[day]");
+
+    let start_date = row_text(item, "startDay")?.context("Missing startDay field")?;
+    let start_time = row_text(item, "start")?.context("Missing start field")?;
+    let end_time = row_text(item, "end")?.context("Missing end field")?;
+
+    let start_date = Date::parse(&start_date, &date_format)?;
+    let start_time = parse_schedule_time(&start_time)?;
+    let end_time = parse_schedule_time(&end_time)?;
+
+    // TODO: Remove Hardcoding
+    let time_zone_str = row_text(item, "timeZone")?.context("Missing TimeZone")?;
+    let local_offset = find_offset_by_id(&time_zone_str).context("Invalid TimeZone")?;
+
+    let anchor = start_date
+        .with_time(start_time)
+        // America/Los_Angeles PST (UTC-7)
+        .assume_offset(local_offset);
+
+    // Last (local) day the schedule runs on.
+    let end_day = row_text(item, "endDay")?
+        .map(|v| Date::parse(&v, &date_format))
+        .transpose()?;
+
+    let repeats = row_text(item, "schedule")?.and_then(|id| schedule_repeats.get(&id).copied());
+
+    let is_instance =
+        row_text(item, "recurrenceType")?.as_deref() == Some(RECURRENCE_TYPE_INSTANCE);
+
+    // Occurrences within the window being looked up.
+    let mut starts: Vec<OffsetDateTime> = if is_instance {
+        std::iter::once(anchor.to_offset(UtcOffset::UTC))
+            .filter(|utc| *utc >= lookup_time && *utc < lookup_end)
+            .collect()
+    } else {
+        let rec_rules: RecurrenceRules = serde_json::from_value(serde_json::to_value(
+            item.fields
+                .get(&SchematicFieldKey::Other(String::from("recurrenceRule")))
+                .context("Missing recurrenceRule field")?,
+        )?)?;
+
+        rec_rules
+            .occurrences(anchor, repeats, end_day)?
+            .skip_while(|utc| *utc < lookup_time)
+            .take_while(|utc| *utc < lookup_end)
+            .collect()
+    };
+
+    // One-off days run with the same hours. Days the recurrence already covers aren't repeated.
+    for day in staff_schedule_ad_hoc_dates(item)? {
+        let utc = day
+            .with_time(start_time)
+            .assume_offset(local_offset)
+            .to_offset(UtcOffset::UTC);
+
+        if utc >= lookup_time && utc < lookup_end && !starts.contains(&utc) {
+            starts.push(utc);
+        }
+    }
+
+    starts.sort();
+
+    Ok(ScheduleOccurrences {
+        time_zone_str,
+        local_offset,
+        start_time,
+        end_time,
+        starts,
+    })
+}
+
+/// Whether every slot of a staff schedule is occupied, by staff schedule id & local day.
+///
+/// Only the first `MAX_FULLY_BOOKED_DAYS` days are checked.
+async fn fully_booked_days(
+    state: &AppState,
+    uuid: UuidType,
+    staff_schedule_items: &[CmsRowResponse],
+    schedules: &[CmsRowResponse],
+    (lookup_time, lookup_end): (OffsetDateTime, OffsetDateTime),
+    schedule_repeats: &HashMap<String, usize>,
+) -> Result<HashMap<(String, Date), bool>> {
+    let mut fully_booked = HashMap::new();
+
+    let services = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    for staff_schedule in staff_schedule_items {
+        if fully_booked.len() >= MAX_FULLY_BOOKED_DAYS {
+            break;
+        }
+
+        // Skipped with a warning by `gather_available_days`.
+        if !is_staff_schedule_active(staff_schedule)?
+            || !is_working_hours(staff_schedule)?
+            || !has_known_time_zone(staff_schedule)?
+        {
+            continue;
+        }
+
+        let Some(schedule_id) = row_text(staff_schedule, "schedule")? else {
+            continue;
+        };
+
+        let mut schedule = None;
+
+        for row in schedules {
+            if row_id(row)? == schedule_id {
+                schedule = Some(row);
+                break;
+            }
+        }
+
+        let Some(schedule) = schedule else {
+            continue;
+        };
+
+        let Some(service_id) = row_text(schedule, "service")? else {
+            continue;
+        };
+
+        let mut service = None;
+
+        for row in &services.items {
+            if row_id(row)? == service_id {
+                service = Some(row);
+                break;
+            }
+        }
+
+        let Some(service) = service else {
+            continue;
+        };
+
+        let occurrences =
+            staff_schedule_occurrences(staff_schedule, lookup_time, lookup_end, schedule_repeats)?;
+
+        let mut days = occurrences
+            .starts
+            .iter()
+            .map(|utc| utc.to_offset(occurrences.local_offset).date())
+            .take(MAX_FULLY_BOOKED_DAYS - fully_booked.len())
+            .collect::<Vec<_>>();
+
+        days.dedup();
+
+        let (Some(first_day), Some(last_day)) = (days.first().copied(), days.last().copied())
+        else {
+            continue;
+        };
+
+        let span = (last_day - first_day).whole_days() + 1;
+        let filters = book_days_filters(first_day, span, occurrences.local_offset)?;
+
+        let bookings = retry::read(&state.config, || {
+            query_cms_rows(
+                uuid,
+                CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                CmsQuery {
+                    filters: Some(filters.clone()),
+                    ..CmsQuery::default()
+                },
+            )
+        })
+        .await?;
+
+        let occupied =
+            Occupied::resolve(state, uuid, staff_schedule, first_day, span, bookings.items).await?;
+        let staff_schedule_id = row_id(staff_schedule)?;
+
+        for day in days {
+            let found_hours = gather_available_hours(
+                day.midnight(),
+                service,
+                schedule,
+                staff_schedule,
+                &occupied,
+                None,
+                true,
+            )?;
+
+            fully_booked.insert(
+                (staff_schedule_id.clone(), day),
+                found_hours.iter().all(|v| v.is_booked),
+            );
+        }
+    }
+
+    Ok(fully_booked)
+}
+
+fn gather_available_days(
+    lookup_time: PrimitiveDateTime,
+    lookup_end: PrimitiveDateTime,
+    staff_schedule_items: &[CmsRowResponse],
+    schedule_repeats: &HashMap<String, usize>,
+    fully_booked: &HashMap<(String, Date), bool>,
+) -> Result<Vec<serde_json::Value>> {
+    let lookup_time = lookup_time.assume_utc();
+    let lookup_end = lookup_end.assume_utc();
+
+    let mut available_days = Vec::new();
+
+    // 1st. Convert Date/Time to UTC
+    let time_format = format_description!("[hour]:[minute]:[second]");
+
+    for item in staff_schedule_items {
+        if !is_staff_schedule_active(item)? || !is_working_hours(item)? {
+            continue;
+        }
+
+        let staff_schedule_id = row_id(item)?;
+
+        // One bad row shouldn't leave the whole month empty.
+        if !has_known_time_zone(item)? {
+            warn!(
+                "Skipping staff schedule {staff_schedule_id} with unknown time zone {:?}",
+                row_text(item, "timeZone")?
+            );
+
+            continue;
+        }
+
+        let ScheduleOccurrences {
+            time_zone_str,
+            local_offset,
+            start_time,
+            end_time,
+            starts,
+        } = staff_schedule_occurrences(item, lookup_time, lookup_end, schedule_repeats)?;
+
+        let time_distance = end_time - start_time;
+
+        for utc in starts {
+            let local = utc.to_offset(local_offset);
+
+            // Start DateTime ID
+            // TODO: Chars [32 start time][1 version][3 duration][1 recurrence][3 original utc offset]
+            let start_id = Uuid::new_v7(uuid::Timestamp::from_unix(
+                uuid::NoContext,
+                utc.unix_timestamp() as u64,
+                0,
+            ));
+
+            let mut available_day = serde_json::json!({
+                // TODO: Add Duration, Recurrence, etc.. to it.
+                "id": start_id.as_simple(),
+                "staffScheduleId": item.fields.get(&SchematicFieldKey::Id).unwrap(),
+                "timeZone": time_zone_str,
+
+                "start": {
+                    "dateUtc": utc.date(),
+                    "timeUtc": utc.time().format(&time_format).unwrap(),
+                    "dateLocal": local.date(),
+                    "timeLocal": start_time.format(&time_format).unwrap(),
+                },
+
+                "end": {
+                    "dateUtc": (utc + time_distance).date(),
+                    "timeUtc": (utc.time() + time_distance).format(&time_format).unwrap(),
+                    "dateLocal": (local + time_distance).date(),
+                    "timeLocal": end_time.format(&time_format).unwrap(),
+                },
+
+                "monthUtc": utc.month() as u8,
+                "dayUtc": utc.day() as u8,
+
+                "monthLocal": local.month() as u8,
+                "dayLocal": local.day() as u8,
+
+                // Same day names as the recurrence rule, eg. MONDAY
+                "weekdayUtc": utc.weekday().to_string().to_uppercase(),
+                "weekdayLocal": local.weekday().to_string().to_uppercase(),
+                "isoWeek": local.iso_week(),
+            });
+
+            if let Some(fully_booked) = fully_booked.get(&(staff_schedule_id.clone(), local.date()))
+            {
+                available_day["fullyBooked"] = serde_json::json!(fully_booked);
+            }
+
+            available_days.push(available_day);
+        }
+    }
+
+    Ok(available_days)
+}
+
+/// Whether the staff schedule's `timeZone` is set and can be resolved.
+fn has_known_time_zone(staff_schedule: &CmsRowResponse) -> Result<bool> {
+    Ok(row_text(staff_schedule, "timeZone")?.is_some_and(|v| find_offset_by_id(&v).is_some()))
+}
+
+/// Returns the intra-day break windows of a staff schedule.
+fn staff_schedule_breaks(staff_schedule: &CmsRowResponse) -> Result<Vec<(Time, Time)>> {
+    let Some(breaks) = staff_schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("breaks")))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let breaks: Option<Vec<BreakWindow>> = serde_json::from_value(serde_json::to_value(breaks)?)?;
+
+    breaks
+        .unwrap_or_default()
+        .iter()
+        .map(BreakWindow::parse)
+        .collect()
+}
+
+/// Returns the one-off days a staff schedule runs on besides its recurrence.
+fn staff_schedule_ad_hoc_dates(staff_schedule: &CmsRowResponse) -> Result<Vec<Date>> {
+    let Some(dates) = staff_schedule
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("adHocDates")))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let dates: Option<Vec<Date>> = serde_json::from_value(serde_json::to_value(dates)?)?;
+
+    Ok(dates.unwrap_or_default())
+}
+
+/// Format of the stored `bookDate`, eg. `2025-01-02 12:00:00.0 +00:00:00`
+const BOOK_DATE_FORMAT: &[time::format_description::FormatItem<'static>] = format_description!(
+    "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
+);
+
+/// The `bookDate` stored for a booking starting at `start`. Always stored in UTC.
+fn book_date_value(start: OffsetDateTime) -> Result<String> {
+    Ok(start.to_offset(UtcOffset::UTC).format(BOOK_DATE_FORMAT)?)
+}
+
+/// The `bookID` stored for a booking starting at `start`.
+fn book_id_value(start: OffsetDateTime) -> String {
+    start.unix_timestamp().to_string()
+}
+
+/// Crockford's base32, which leaves out letters easily mistaken for digits.
+const REFERENCE_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Characters of a booking reference, 40 bits.
+const REFERENCE_LENGTH: usize = 8;
+/// References generated before giving up on finding one which isn't taken.
+const REFERENCE_ATTEMPTS: usize = 5;
+
+/// A short reference customers can quote for a booking, eg. `7K3QX9MD`.
+///
+/// Taken from the random bits at the end of a v7 uuid.
+fn booking_reference(id: Uuid) -> String {
+    let bits = id.as_u128();
+
+    (0..REFERENCE_LENGTH)
+        .rev()
+        .map(|i| REFERENCE_ALPHABET[((bits >> (i * 5)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+/// Normalizes a reference typed in by a customer, eg. `7k3q-x9md` or with `O` for `0`.
+fn normalize_booking_reference(reference: &str) -> String {
+    reference
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+/// The booking with the `reference`, if there is one.
+async fn find_booking_by_reference(
+    state: &AppState,
+    uuid: UuidType,
+    reference: &str,
+) -> Result<Option<CmsRowResponse>> {
+    let filters = vec![Filter {
+        name: String::from("reference"),
+        cond: FilterConditionType::Eq,
+        value: FilterValue::Text(normalize_booking_reference(reference)),
+    }];
+
+    let bookings = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("bookings"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: Some(filters.clone()),
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    Ok(bookings.items.into_iter().next())
+}
+
+/// A booking reference no other booking of the website has.
+async fn unique_booking_reference(state: &AppState, uuid: UuidType) -> Result<String> {
+    for _ in 0..REFERENCE_ATTEMPTS {
+        let reference = booking_reference(Uuid::now_v7());
+
+        if find_booking_by_reference(state, uuid, &reference)
+            .await?
+            .is_none()
+        {
+            return Ok(reference);
+        }
+    }
+
+    Err(eyre::eyre!(
+        "Unable to generate a unique booking reference after {REFERENCE_ATTEMPTS} attempts"
+    ))?
+}
+
+/// Filters bookings down to the ones which start on the given local day.
+///
+/// `bookDate` is stored in UTC so the local day's window is converted, eg. a day in `-10:00`
+/// runs from 10:00 UTC to 09:59:59 UTC the next day.
+fn book_date_filters(day: Date, local_offset: UtcOffset) -> Result<Vec<Filter>> {
+    book_days_filters(day, 1, local_offset)
+}
+
+/// Filters bookings down to the ones which start within `days` local days from `first_day`.
+fn book_days_filters(first_day: Date, days: i64, local_offset: UtcOffset) -> Result<Vec<Filter>> {
+    let start = first_day.midnight().assume_offset(local_offset);
+    let end = start + Duration::days(days) - Duration::seconds(1);
+
+    Ok(vec![
+        Filter {
+            name: String::from("bookDate"),
+            cond: FilterConditionType::Gte,
+            value: FilterValue::Text(book_date_value(start)?),
+        },
+        Filter {
+            name: String::from("bookDate"),
+            cond: FilterConditionType::Lte,
+            value: FilterValue::Text(book_date_value(end)?),
+        },
+    ])
+}
+
+/// `bookDate`s written before they carried an offset, eg. `2025-01-02T12:00:00`.
+const LEGACY_BOOK_DATE_FORMATS: [&[time::format_description::FormatItem<'static>]; 2] = [
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]]"),
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]]"),
+];
+
+/// Parses the `bookDate` of a booking row.
+///
+/// `local_offset` is the offset of the booking's schedule, which legacy values without an offset
+/// are in.
+fn booking_start(booking: &CmsRowResponse, local_offset: UtcOffset) -> Result<OffsetDateTime> {
+    parse_book_date(&book_date_text(booking)?, local_offset)
+}
+
+fn book_date_text(booking: &CmsRowResponse) -> Result<String> {
+    Ok(booking
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("bookDate")))
+        .context("Missing bookDate field")?
+        .any_as_text()?)
+}
+
+fn parse_book_date(value: &str, local_offset: UtcOffset) -> Result<OffsetDateTime> {
+    if let Ok(start) = OffsetDateTime::parse(value, BOOK_DATE_FORMAT) {
+        return Ok(start);
+    }
+
+    let local = LEGACY_BOOK_DATE_FORMATS
+        .iter()
+        .find_map(|format| PrimitiveDateTime::parse(value, format).ok())
+        .with_context(|| format!("Invalid bookDate: {value}"))?;
+
+    Ok(local.assume_offset(local_offset))
+}
+
+/// The local offset of each staff member's schedules, by staff id, to read the legacy `bookDate`s
+/// of `bookings` in. Nothing is looked up when every `bookDate` has an offset.
+async fn legacy_book_date_offsets(
+    state: &AppState,
+    uuid: UuidType,
+    bookings: &[CmsRowResponse],
+) -> Result<HashMap<String, UtcOffset>> {
+    let has_legacy = bookings.iter().any(|row| {
+        book_date_text(row).is_ok_and(|v| OffsetDateTime::parse(&v, BOOK_DATE_FORMAT).is_err())
+    });
+
+    if !has_legacy {
+        return Ok(HashMap::new());
+    }
+
+    let staff_schedules = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let time_zones = staff_time_zones(state, uuid).await?;
+
+    let mut offsets = HashMap::new();
+
+    for mut row in staff_schedules.items {
+        apply_staff_time_zone(&mut row, &time_zones)?;
+
+        let (Some(staff_id), Some(time_zone)) =
+            (row_text(&row, "staff")?, row_text(&row, "timeZone")?)
+        else {
+            continue;
+        };
+
+        if let Ok(offset) = validate_time_zone(&time_zone) {
+            offsets.entry(staff_id).or_insert(offset);
+        }
+    }
+
+    Ok(offsets)
+}
+
+/// The offset of the booking's staff member from [`legacy_book_date_offsets`]. UTC without one.
+fn booking_local_offset(
+    booking: &CmsRowResponse,
+    offsets: &HashMap<String, UtcOffset>,
+) -> Result<UtcOffset> {
+    Ok(row_text(booking, "staffMember")?
+        .and_then(|id| offsets.get(&id).copied())
+        .unwrap_or(UtcOffset::UTC))
+}
+
+/// Parses the `bookDate` of a single booking, looking up its schedule's offset if it's legacy.
+async fn resolve_booking_start(
+    state: &AppState,
+    uuid: UuidType,
+    booking: &CmsRowResponse,
+) -> Result<OffsetDateTime> {
+    let offsets = legacy_book_date_offsets(state, uuid, std::slice::from_ref(booking)).await?;
+
+    booking_start(booking, booking_local_offset(booking, &offsets)?)
+}
+
+const DEFAULT_PAGE_LIMIT: usize = 50;
+const MAX_PAGE_LIMIT: usize = 250;
+
+/// Resolves the requested page into an `(offset, limit)` pair.
+fn page_bounds(offset: Option<usize>, limit: Option<usize>) -> (usize, usize) {
+    (
+        offset.unwrap_or_default(),
+        limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT),
+    )
+}
+
+/// The date of a requested `day`, `month` & `year`, rejecting ones which don't exist (eg. 2025-02-30).
+fn calendar_date(year: usize, month: u8, day: u8) -> Result<Date> {
+    let invalid = || {
+        Error::coded(
+            ErrorCode::InvalidDate,
+            format!("{year:04}-{month:02}-{day:02} is not a valid date"),
+        )
+    };
+
+    let month = Month::try_from(month).map_err(|_| invalid())?;
+    let year = i32::try_from(year).map_err(|_| invalid())?;
+
+    Date::from_calendar_date(year, month, day).map_err(|_| invalid())
+}
+
+/// Returns the row's id.
+fn row_id(row: &CmsRowResponse) -> Result<String> {
+    Ok(row
+        .fields
+        .get(&SchematicFieldKey::Id)
+        .context("Missing row id")?
+        .any_as_text()?)
+}
+
+/// Returns a field the row can't be used without, naming the field & row when it's missing.
+fn required_field<'a>(
+    row: &'a CmsRowResponse,
+    collection: &str,
+    field: &str,
+) -> Result<&'a SimpleValue> {
+    match row.fields.get(&SchematicFieldKey::Other(field.to_string())) {
+        Some(value) => Ok(value),
+        None => Err(Error::coded(
+            ErrorCode::MissingField,
+            format!(
+                "{collection} row {} is missing the `{field}` field",
+                row_id(row).unwrap_or_else(|_| String::from("(unknown)"))
+            ),
+        )),
+    }
+}
+
+/// Returns a field of the row as text, if it exists.
+fn row_text(row: &CmsRowResponse, field: &str) -> Result<Option<String>> {
+    Ok(row
+        .fields
+        .get(&SchematicFieldKey::Other(field.to_string()))
+        .map(|v| v.any_as_text())
+        .transpose()?)
+}
+
+/// Rounds a local time up to the next multiple of `step` since midnight.
+///
+/// Unchanged without a step. `None` if rounding passes the end of the day.
+fn round_up_time(time: Time, step: Option<Duration>) -> Option<Time> {
+    let Some(step) = step.filter(|v| v.whole_seconds() > 0) else {
+        return Some(time);
+    };
+
+    let since_midnight = time - Time::MIDNIGHT;
+    let remainder = since_midnight.whole_seconds() % step.whole_seconds();
+
+    if remainder == 0 && since_midnight.subsec_nanoseconds() == 0 {
+        return Some(time);
+    }
+
+    let rounded = Duration::seconds(since_midnight.whole_seconds() - remainder) + step;
+
+    (rounded < Duration::DAY).then(|| Time::MIDNIGHT + rounded)
+}
+
+/// Parses a stored schedule time such as `10:00:00`.
+///
+/// The CMS may return subseconds (`10:00:00.5`), which are dropped so slots start on a whole second.
+fn parse_schedule_time(value: &str) -> Result<Time> {
+    let time = Time::parse(
+        value.trim(),
+        &format_description!("[hour]:[minute]:[second][optional [.[subsecond]]]"),
+    )?;
+
+    Ok(time.replace_nanosecond(0)?)
+}
+
+/// Returns a boolean field of the row, if it exists.
+fn row_bool(row: &CmsRowResponse, field: &str) -> Result<Option<bool>> {
+    let Some(value) = row.fields.get(&SchematicFieldKey::Other(field.to_string())) else {
+        return Ok(None);
+    };
+
+    Ok(serde_json::to_value(value)?.as_bool())
+}
+
+/// Whether a booking takes up its slot. Voided and cancelled (or otherwise closed) bookings don't.
+fn is_booking_occupying(booking: &CmsRowResponse) -> Result<bool> {
+    Ok(
+        row_text(booking, "type")?.as_deref() != Some(BOOKING_TYPE_VOID)
+            && row_text(booking, "status")?.is_none_or(|status| status == BOOKING_STATUS_CONFIRMED),
+    )
+}
+
+/// Whether a staff schedule offers availability. Rows from before `active` existed are active.
+fn is_staff_schedule_active(staff_schedule: &CmsRowResponse) -> Result<bool> {
+    Ok(row_bool(staff_schedule, "active")?.unwrap_or(true))
+}
+
+/// Whether a staff schedule offers slots. Other types (eg. time off) block the staff member instead.
+/// Rows without a `type` predate it and are working hours.
+fn is_working_hours(staff_schedule: &CmsRowResponse) -> Result<bool> {
+    Ok(row_text(staff_schedule, "type")?
+        .is_none_or(|type_of| type_of == STAFF_SCHEDULE_TYPE_WORKING_HOURS))
+}
+
+/// Returns a numeric field of the row, if it exists.
+fn row_number(row: &CmsRowResponse, field: &str) -> Result<Option<f64>> {
+    Ok(row
+        .fields
+        .get(&SchematicFieldKey::Other(field.to_string()))
+        .map(|v| v.try_as_number())
+        .transpose()?
+        .map(|v| v.convert_f64()))
+}
+
+/// Which services can currently be booked.
+struct Bookability {
+    /// Service id of every staff schedule whose schedule still exists.
+    staff_schedules: HashMap<String, String>,
+    /// Services with at least one active staff schedule.
+    services: HashSet<String>,
+}
+
+impl Bookability {
+    /// Resolves every staff schedule to its service through its schedule.
+    fn of(schedules: &[CmsRowResponse], staff_schedules: &[CmsRowResponse]) -> Result<Self> {
+        let mut schedule_services = HashMap::new();
+
+        for row in schedules {
+            if let Some(service_id) = row_text(row, "service")?.filter(|v| !v.is_empty()) {
+                schedule_services.insert(row_id(row)?, service_id);
+            }
+        }
+
+        let mut bookability = Self {
+            staff_schedules: HashMap::new(),
+            services: HashSet::new(),
+        };
+
+        for row in staff_schedules {
+            let Some(service_id) = row_text(row, "schedule")?
+                .and_then(|schedule_id| schedule_services.get(&schedule_id))
+            else {
+                continue;
+            };
+
+            if is_staff_schedule_active(row)? && is_working_hours(row)? {
+                bookability.services.insert(service_id.clone());
+            }
+
+            bookability
+                .staff_schedules
+                .insert(row_id(row)?, service_id.clone());
+        }
+
+        Ok(bookability)
+    }
+
+    /// Checks the staff schedule is listed under the website.
+    ///
+    /// Ids are sent by the client and could be guessed, so only rows listed under the website
+    /// are trusted. A staff schedule is listed once its schedule is.
+    fn ensure_owned(&self, staff_schedule_id: &str) -> Result<()> {
+        if !self.staff_schedules.contains_key(staff_schedule_id) {
+            return Err(Error::coded(
+                ErrorCode::NotOwned,
+                format!("Staff schedule {staff_schedule_id} doesn't belong to this website"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the staff schedule still exists and its service has an active schedule.
+    fn ensure_bookable(&self, staff_schedule_id: &str) -> Result<()> {
+        let not_bookable = match self.staff_schedules.get(staff_schedule_id) {
+            Some(service_id) if self.services.contains(service_id) => None,
+            Some(service_id) => Some(format!("Service {service_id} has no active schedule")),
+            None => Some(format!(
+                "Staff schedule {staff_schedule_id} no longer exists"
+            )),
+        };
+
+        match not_bookable {
+            Some(message) => Err(Error::coded(ErrorCode::ServiceNotBookable, message)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Loads which of the website's services can currently be booked.
+async fn bookability(state: &AppState, uuid: UuidType) -> Result<Bookability> {
+    let schedules = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let staff_schedules = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    Bookability::of(&schedules.items, &staff_schedules.items)
+}
+
+/// Time zones of staff members who have their own, by staff id.
+///
+/// Invalid time zones are skipped so the staff member falls back to their schedule's.
+async fn staff_time_zones(state: &AppState, uuid: UuidType) -> Result<HashMap<String, String>> {
+    let staff = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staff"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let mut time_zones = HashMap::new();
+
+    for row in &staff.items {
+        let Some(time_zone) = row_text(row, "timeZone")?.filter(|v| !v.trim().is_empty()) else {
+            continue;
+        };
+
+        let staff_id = row_id(row)?;
+
+        if let Err(e) = validate_time_zone(&time_zone) {
+            warn!("Ignoring time zone of staff {staff_id}: {e}");
+            continue;
+        }
+
+        time_zones.insert(staff_id, time_zone);
+    }
+
+    Ok(time_zones)
+}
+
+/// Uses the staff member's own time zone for their staff schedule instead of the schedule's.
+///
+/// For staff working remotely for a business in another zone.
+fn apply_staff_time_zone(
+    staff_schedule: &mut CmsRowResponse,
+    time_zones: &HashMap<String, String>,
+) -> Result<()> {
+    let Some(time_zone) = row_text(staff_schedule, "staff")?.and_then(|id| time_zones.get(&id))
+    else {
+        return Ok(());
+    };
+
+    staff_schedule.fields.insert(
+        SchematicFieldKey::Other(String::from("timeZone")),
+        time_zone.as_str().into(),
+    );
+
+    Ok(())
+}
+
+/// Resolves a time zone id, failing early with a clear message for unknown zones.
+fn validate_time_zone(time_zone: &str) -> Result<UtcOffset> {
+    Ok(find_offset_by_id(time_zone).with_context(|| format!("Invalid TimeZone: {time_zone}"))?)
+}
+
+/// Basic shape check of an email address. Deliverability is left to the mail provider.
+fn validate_email(email: &str) -> Result<()> {
+    let valid = email.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && !domain.contains('@')
+            && domain.split('.').count() >= 2
+            && domain.split('.').all(|part| !part.is_empty())
+            && !email.chars().any(char::is_whitespace)
+    });
+
+    if !valid {
+        return Err(eyre::eyre!("Invalid email address: {email}"))?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the currency is an ISO 4217 alphabetic code (eg. `USD`).
+fn validate_currency(currency: &str) -> Result<()> {
+    if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(eyre::eyre!(
+            "Invalid currency: {currency}. Expected an ISO 4217 code such as USD"
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// `paymentType` of services paid online when booking.
+const PAYMENT_TYPE_ONLINE: &str = "online";
+
+/// The price of a service shown with its availability.
+struct ServicePrice {
+    amount: Option<f64>,
+    payment_type: Option<String>,
+}
+
+impl ServicePrice {
+    fn of(service: &CmsRowResponse) -> Result<Self> {
+        Ok(Self {
+            amount: row_number(service, "priceAmount")?,
+            payment_type: row_text(service, "paymentType")?,
+        })
+    }
+
+    /// Services without a price are free.
+    fn is_free(&self) -> bool {
+        self.amount.is_none_or(|v| v <= 0.0)
+    }
+}
+
+/// The staff member shown with their availability. Left empty without a staff row.
+struct StaffDetails {
+    name: Option<String>,
+    image: Option<SimpleValue>,
+    image_url: Option<String>,
+}
+
+impl StaffDetails {
+    fn of(config: &Config, staff: Option<&CmsRowResponse>) -> Result<Self> {
+        let Some(staff) = staff else {
+            return Ok(Self {
+                name: None,
+                image: None,
+                image_url: None,
+            });
+        };
+
+        Ok(Self {
+            name: row_text(staff, "staffName")?,
+            image: staff
+                .fields
+                .get(&SchematicFieldKey::Other(String::from("staffImage")))
+                .cloned(),
+            image_url: row_image_url(config, staff, "staffImage")?,
+        })
+    }
+}
+
+/// The service every slot of an availability listing is of. Missing fields are left empty.
+struct ServiceDetails {
+    name: Option<String>,
+    description: Option<String>,
+    image: Option<SimpleValue>,
+    image_url: Option<String>,
+}
+
+impl ServiceDetails {
+    fn of(config: &Config, service: &CmsRowResponse) -> Result<Self> {
+        Ok(Self {
+            name: row_text(service, "name")?,
+            description: row_text(service, "description")?,
+            image: service
+                .fields
+                .get(&SchematicFieldKey::Other(String::from("image")))
+                .cloned(),
+            image_url: row_image_url(config, service, "image")?,
+        })
+    }
+}
+
+/// What a customer pays for a service when booking and what's left to pay.
+struct ServicePayment {
+    /// Charged when booking instead of the full price. Only applies to online payments.
+    deposit_amount: Option<f64>,
+    /// Left to pay after booking.
+    balance_due: f64,
+}
+
+fn service_payment(service: &CmsRowResponse) -> Result<ServicePayment> {
+    let price = row_number(service, "priceAmount")?.unwrap_or_default();
+
+    if row_text(service, "paymentType")?.as_deref() != Some(PAYMENT_TYPE_ONLINE) {
+        return Ok(ServicePayment {
+            deposit_amount: None,
+            balance_due: price,
+        });
+    }
+
+    let deposit_amount = row_number(service, "depositAmount")?
+        .filter(|v| *v > 0.0)
+        .map(|v| v.min(price));
+
+    Ok(ServicePayment {
+        deposit_amount,
+        balance_due: price - deposit_amount.unwrap_or(price),
+    })
+}
+
+/// The URL of an image field, eg. a service's `image`.
+///
+/// Files are only hydrated when rows are queried with `include_files`. Otherwise the field
+/// holds the file's name, which is resolved the same way.
+fn row_image_url(config: &Config, row: &CmsRowResponse, field: &str) -> Result<Option<String>> {
+    let Some(value) = row.fields.get(&SchematicFieldKey::Other(field.to_string())) else {
+        return Ok(None);
+    };
+
+    let value = serde_json::to_value(value)?;
+
+    // Hydrated files are objects, eg. `{ "name": "..", "url": ".." }`.
+    let file = match &value {
+        serde_json::Value::String(v) => Some(v.as_str()),
+        serde_json::Value::Object(v) => ["url", "name", "path"]
+            .into_iter()
+            .find_map(|key| v.get(key).and_then(|v| v.as_str())),
+        _ => None,
+    };
+
+    Ok(file
+        .filter(|v| !v.is_empty())
+        .map(|v| template::file_url(config, v)))
+}
+
+/// Participants a slot of the service holds. 1 unless it's a group service.
+fn service_max_participants(service: &CmsRowResponse) -> Result<usize> {
+    Ok(row_number(service, "maxParticipants")?
+        .filter(|v| *v >= 1.0)
+        .map_or(1, |v| v as usize))
+}
+
+/// Bookings a staff member serves in parallel under the schedule, eg. a barber with two chairs.
+/// 1 by default. Unlike the service's `maxParticipants` these are separate sessions.
+fn schedule_concurrency(schedule: &CmsRowResponse) -> Result<usize> {
+    Ok(row_number(schedule, "concurrency")?
+        .filter(|v| *v >= 1.0)
+        .map_or(1, |v| v as usize))
+}
+
+/// Places a slot of the service holds under the schedule, eg. 2 chairs of a 3 person class hold 6.
+fn slot_capacity(service: &CmsRowResponse, schedule: &CmsRowResponse) -> Result<usize> {
+    Ok(service_max_participants(service)? * schedule_concurrency(schedule)?)
+}
+
+/// Places a slot of `capacity` has left once `participants` booked it. None once blocked.
+fn slot_places_left(capacity: usize, participants: usize, is_blocked: bool) -> usize {
+    if is_blocked {
+        0
+    } else {
+        capacity.saturating_sub(participants)
+    }
+}
+
+/// Participants a session of the service needs to run. 1 by default, so any booking confirms it.
+fn service_min_participants(service: &CmsRowResponse) -> Result<usize> {
+    Ok(row_number(service, "minParticipants")?
+        .filter(|v| *v >= 1.0)
+        .map_or(1, |v| v as usize))
+}
+
+/// Returns the service's currency, falling back to the default currency.
+fn service_currency(config: &Config, service: &CmsRowResponse) -> Result<String> {
+    let currency = service
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("currency")))
+        .map(|v| v.any_as_text())
+        .transpose()?
+        .unwrap_or_else(|| config.default_currency.clone());
+
+    validate_currency(&currency)?;
+
+    Ok(currency)
+}
+
+/// Converts a (possibly fractional) number of minutes into a Duration.
+///
+/// Rounded to the nearest second so a 7.5 minute break stays 7m 30s.
+fn minutes_to_duration(minutes: f64) -> Duration {
+    Duration::seconds((minutes * 60.0).round() as i64)
+}
+
+/// Lengths in minutes a customer can choose between when booking the service.
+/// Empty when the service only has its schedule's duration.
+fn service_duration_options(service: &CmsRowResponse) -> Result<Vec<f64>> {
+    let Some(options) = service
+        .fields
+        .get(&SchematicFieldKey::Other(String::from("durationOptions")))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let options: Option<Vec<f64>> = serde_json::from_value(serde_json::to_value(options)?)?;
+
+    Ok(options.unwrap_or_default())
+}
+
+/// Checks a duration chosen by the customer is one the service allows.
+fn validate_duration_option(service: &CmsRowResponse, duration_minutes: Option<f64>) -> Result<()> {
+    let Some(duration_minutes) = duration_minutes else {
+        return Ok(());
+    };
+
+    let options = service_duration_options(service)?;
+
+    if !options.contains(&duration_minutes) {
+        return Err(Error::coded(
+            ErrorCode::DurationNotAllowed,
+            format!(
+                "durationMinutes {duration_minutes} is not allowed. Expected one of [{}]",
+                options
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+// Start DateTime ID
+// TODO: Chars [32 start time][1 version][3 duration][1 recurrence][3 original utc offset]
+struct BookingId {
+    /// Stores the DateTime in which the booking starts in UTC format.
+    start_time: Uuid,
+    /// Version of the BookingID
+    version: u8,
+    /// The duration of the booking in minutes.
+    duration: u32,
+    /// The recurrence of the booking.
+    recurrence: u8,
+    /// The original offset for the start time.
+    /// Since its' currently stored as UTC.
+    utc_offset: u32,
+}
+
+// let start_id = Uuid::new_v7(uuid::Timestamp::from_unix(
+//     uuid::NoContext,
+//     utc.unix_timestamp() as u64,
+//     0,
+// ));
+
+// impl serde::Serialize for BookingId {
+//     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+//     where
+//         S: serde::Serializer,
+//     {
+//         let mut this = self.start_time.simple().to_string();
+
+//         // format!("{:X}", 42)
+//         // i64::from_str_radix("1f", 16);
+
+//         // self.version
+//         // self.duration
+//         // self.recurrence
+//         // self.utc_offset
+
+//         Ok(this)
+//     }
+// }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::slot_lock::MemorySlotLock;
+
+    const WEBSITE: &str = "01938f4f-f50c-7203-9f89-b367e9d49efb";
+
+    /// A CMS row with the `id` and `fields`.
+    pub(crate) fn test_row<K: Into<String>>(
+        id: &str,
+        fields: impl IntoIterator<Item = (K, SimpleValue)>,
+    ) -> CmsRowResponse {
+        let mut fields = fields
+            .into_iter()
+            .map(|(name, value)| (SchematicFieldKey::Other(name.into()), value))
+            .collect::<HashMap<_, _>>();
+
+        fields.insert(SchematicFieldKey::Id, id.into());
+
+        CmsRowResponse { fields }
+    }
+
+    /// The slots on 2025-03-14 of a staff schedule working 09:00 to 12:00 UTC, under a schedule of
+    /// 60 minute slots. Either is changed by the given fields.
+    fn slots_with(
+        schedule_fields: Vec<(&str, SimpleValue)>,
+        staff_schedule_fields: Vec<(&str, SimpleValue)>,
+        bookings: Vec<CmsRowResponse>,
+    ) -> Vec<FoundHour> {
+        try_slots_with(schedule_fields, staff_schedule_fields, bookings).unwrap()
+    }
+
+    /// [`slots_with`], keeping the error of a schedule which can't produce slots.
+    fn try_slots_with(
+        schedule_fields: Vec<(&str, SimpleValue)>,
+        staff_schedule_fields: Vec<(&str, SimpleValue)>,
+        bookings: Vec<CmsRowResponse>,
+    ) -> Result<Vec<FoundHour>> {
+        let schedule = test_row(
+            "schedule",
+            [("duration", serde_json::json!(60).into())]
+                .into_iter()
+                .chain(schedule_fields),
+        );
+        let staff_schedule = test_row(
+            "staffSchedule",
+            [
+                ("staff", "staff".into()),
+                ("timeZone", "UTC".into()),
+                ("start", "09:00:00".into()),
+                ("end", "12:00:00".into()),
+            ]
+            .into_iter()
+            .chain(staff_schedule_fields),
+        );
+
+        gather_available_hours(
+            time::macros::datetime!(2025-03-14 00:00),
+            &test_row("service", HashMap::<String, _>::new()),
+            &schedule,
+            &staff_schedule,
+            &Occupied {
+                bookings,
+                busy: Vec::new(),
+            },
+            None,
+            true,
+        )
+    }
+
+    /// A booking row starting at `start`, with the given fields.
+    fn booking_row(
+        id: &str,
+        start: OffsetDateTime,
+        fields: Vec<(&str, SimpleValue)>,
+    ) -> CmsRowResponse {
+        test_row(
+            id,
+            [("bookDate", book_date_value(start).unwrap().into())]
+                .into_iter()
+                .chain(fields),
+        )
+    }
+
+    /// Local start times of the slots.
+    fn starts(slots: &[FoundHour]) -> Vec<Time> {
+        slots.iter().map(|v| v.start.time()).collect()
+    }
+
+    /// Days in March 2025 a staff schedule working Mondays from 2025-03-03 (in UTC) runs on.
+    /// Changed by the given fields.
+    fn occurrence_days(fields: Vec<(&str, SimpleValue)>) -> Vec<Date> {
+        let staff_schedule = test_row(
+            "staffSchedule",
+            [
+                ("schedule", "schedule".into()),
+                ("timeZone", "UTC".into()),
+                ("startDay", "2025-03-03".into()),
+                ("start", "09:00:00".into()),
+                ("end", "17:00:00".into()),
+                (
+                    "recurrenceRule",
+                    serde_json::json!({ "days": ["MONDAY"], "frequency": "WEEKLY", "interval": 1 })
+                        .into(),
+                ),
+            ]
+            .into_iter()
+            .chain(fields),
+        );
+
+        staff_schedule_occurrences(
+            &staff_schedule,
+            time::macros::datetime!(2025-03-01 00:00 UTC),
+            time::macros::datetime!(2025-04-01 00:00 UTC),
+            &HashMap::new(),
+        )
+        .unwrap()
+        .starts
+        .iter()
+        .map(|v| v.date())
+        .collect()
+    }
+
+    fn key() -> LockKey {
+        (
+            serde_json::from_value(serde_json::json!(WEBSITE)).unwrap(),
+            String::from("schedule"),
+            14,
+            3,
+            2025,
+            String::from("09:00:00"),
+        )
+    }
+
+    #[tokio::test]
+    async fn a_second_chair_is_held_next_to_a_booking() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+
+        // A concurrency of 2 with one booking already made.
+        let places_left = slot_places_left(2, 1, false);
+
+        assert_eq!(places_left, 1);
+        assert!(locks
+            .try_acquire(&key(), "a", places_left)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(locks
+            .try_acquire(&key(), "b", places_left)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn a_slot_with_every_chair_booked_isnt_held() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+
+        // A concurrency of 2 with two bookings already made.
+        let places_left = slot_places_left(2, 2, false);
+
+        assert_eq!(places_left, 0);
+        assert!(locks
+            .try_acquire(&key(), "a", places_left)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    fn at(hour: u8) -> OffsetDateTime {
+        time::macros::datetime!(2025-03-14 00:00 +1)
+            .replace_hour(hour)
+            .unwrap()
+    }
+
+    fn is_code(result: Result<()>, expected: ErrorCode) -> bool {
+        matches!(result, Err(Error::Coded { code, .. }) if code == expected)
+    }
+
+    #[test]
+    fn a_batch_of_two_services_is_checked_per_service() {
+        let batched = [(String::from("haircut"), at(9))];
+
+        // The beard trim's daily limit doesn't count the haircut before it.
+        assert!(check_contact_bookings("beard", at(10), Some(1.0), &[], &batched).is_ok());
+        assert!(is_code(
+            check_contact_bookings("haircut", at(10), Some(1.0), &[], &batched),
+            ErrorCode::ContactDailyLimitReached
+        ));
+    }
+
+    #[test]
+    fn a_contact_cant_book_the_same_time_twice() {
+        assert!(is_code(
+            check_contact_bookings("haircut", at(9), None, &[at(9)], &[]),
+            ErrorCode::DuplicateBooking
+        ));
+        assert!(is_code(
+            check_contact_bookings(
+                "haircut",
+                at(9),
+                None,
+                &[],
+                &[(String::from("haircut"), at(9))]
+            ),
+            ErrorCode::DuplicateBooking
+        ));
+        assert!(check_contact_bookings("haircut", at(10), None, &[at(9)], &[]).is_ok());
+    }
+
+    #[test]
+    fn the_daily_limit_counts_written_and_batched_bookings() {
+        let batched = [(String::from("haircut"), at(11))];
+
+        assert!(check_contact_bookings("haircut", at(10), Some(2.0), &[at(9)], &[]).is_ok());
+        assert!(is_code(
+            check_contact_bookings("haircut", at(10), Some(2.0), &[at(9)], &batched),
+            ErrorCode::ContactDailyLimitReached
+        ));
+        // `0` is unlimited.
+        assert!(check_contact_bookings("haircut", at(10), Some(0.0), &[at(9)], &batched).is_ok());
+    }
+
+    #[test]
+    fn overnight_hours_close_the_next_day() {
+        let day = time::macros::date!(2025 - 03 - 14);
+
+        assert_eq!(
+            hours_bounds(day, time::macros::time!(22:00), time::macros::time!(02:00)).unwrap(),
+            (
+                time::macros::datetime!(2025-03-14 22:00),
+                time::macros::datetime!(2025-03-15 02:00)
+            )
+        );
+        assert_eq!(
+            hours_bounds(day, time::macros::time!(09:00), time::macros::time!(17:00)).unwrap(),
+            (
+                time::macros::datetime!(2025-03-14 09:00),
+                time::macros::datetime!(2025-03-14 17:00)
+            )
+        );
+    }
+
+    #[test]
+    fn rounding_past_midnight_moves_to_the_next_day() {
+        let step = Some(time::Duration::minutes(15));
+
+        assert_eq!(
+            round_up_date_time(time::macros::datetime!(2025-03-14 23:50), step),
+            time::macros::datetime!(2025-03-15 00:00)
+        );
+        assert_eq!(
+            round_up_date_time(time::macros::datetime!(2025-03-14 09:05), step),
+            time::macros::datetime!(2025-03-14 09:15)
+        );
+    }
+
+    #[test]
+    fn book_dates_with_an_offset_are_kept() {
+        let offset = time::macros::offset!(-7);
+
+        assert_eq!(
+            parse_book_date("2025-01-02 17:00:00.0 +00:00:00", offset).unwrap(),
+            time::macros::datetime!(2025-01-02 17:00 UTC)
+        );
+    }
+
+    #[test]
+    fn legacy_book_dates_are_read_in_the_local_offset() {
+        let offset = time::macros::offset!(-7);
+
+        for value in ["2025-01-02T10:00:00", "2025-01-02 10:00:00.0"] {
+            assert_eq!(
+                parse_book_date(value, offset).unwrap(),
+                time::macros::datetime!(2025-01-02 17:00 UTC)
+            );
+        }
+
+        assert!(parse_book_date("tomorrow", offset).is_err());
+    }
+
+    #[test]
+    fn missing_repeats_are_unlimited() {
+        assert_eq!(repeats_limit(None), 0);
+        assert_eq!(repeats_limit(Some(0.0)), 0);
+        assert_eq!(repeats_limit(Some(4.0)), 4);
+    }
+
+    #[test]
+    fn a_single_repeat_occurs_once() {
+        let rule = recurrence::RecurrenceRule {
+            days: Vec::new(),
+            frequency: String::from("weekly"),
+            interval: 1,
+        };
+
+        let occurrences = rule
+            .occurrences(at(9), Some(repeats_limit(Some(1.0))), None)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(occurrences, [at(9)]);
+    }
+
+    #[test]
+    fn the_submitted_message_is_kept_as_notes() {
+        let (notes, fields) = submission_details(HashMap::from([
+            (String::from("firstName"), String::from("Ana")),
+            (String::from("email"), String::from("ana@example.com")),
+            (
+                String::from("message"),
+                String::from("  Window seat please "),
+            ),
+            (String::from("company"), String::from("Acme")),
+        ]));
+
+        assert_eq!(notes.as_deref(), Some("Window seat please"));
+        assert_eq!(
+            fields,
+            HashMap::from([(String::from("company"), String::from("Acme"))])
+        );
+    }
+
+    #[test]
+    fn a_blank_or_missing_message_has_no_notes() {
+        let (notes, _) = submission_details(HashMap::from([(
+            String::from("message"),
+            String::from("  "),
+        )]));
+
+        assert!(notes.is_none());
+        assert!(submission_details(HashMap::new()).0.is_none());
+    }
+
+    fn booking(hour: u8) -> OccupyingBooking {
+        OccupyingBooking {
+            start: at(hour),
+            end: at(hour + 1),
+            is_block: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_booking_made_between_before_and_after_is_caught() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+        let slot = (at(9), at(10));
+
+        // `before` sees one of two chairs booked & holds the other.
+        let mut occupying = vec![booking(9)];
+        let places_left =
+            slot_places_left(2, check_slot_capacity(slot, 2, &occupying).unwrap(), false);
+
+        assert!(locks
+            .try_acquire(&key(), "a", places_left)
+            .await
+            .unwrap()
+            .is_some());
+
+        // Another replica books the last chair before the form is submitted.
+        occupying.push(booking(9));
+
+        assert!(matches!(
+            check_slot_capacity(slot, 2, &occupying),
+            Err(Error::Coded {
+                code: ErrorCode::AlreadyBooked,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn only_overlapping_bookings_and_blocks_fill_a_slot() {
+        let slot = (at(9), at(10));
+
+        assert_eq!(
+            check_slot_capacity(slot, 2, &[booking(8), booking(9), booking(10)]).unwrap(),
+            1
+        );
+        assert!(check_slot_capacity(
+            slot,
+            2,
+            &[OccupyingBooking {
+                is_block: true,
+                ..booking(9)
+            }]
+        )
+        .is_err());
+    }
+
+    fn found_hour(is_booked: bool, is_blocked: bool) -> FoundHour {
+        FoundHour {
+            start: at(9),
+            end: at(10),
+            is_booked,
+            is_blocked,
+            service_id: String::from("service"),
+            schedule_id: String::from("schedule"),
+            staff_id: String::from("staff"),
+            staff_schedule_id: String::from("staffSchedule"),
+            dst_warning: None,
+            participants: usize::from(is_booked),
+            remaining: usize::from(!is_booked && !is_blocked),
+        }
+    }
+
+    #[test]
+    fn unavailable_days_are_classified() {
+        let free = found_hour(false, false);
+        let booked = found_hour(true, false);
+        let blocked = found_hour(true, true);
+
+        assert_eq!(
+            UnavailableReason::classify(false, &[], &[]),
+            Some(UnavailableReason::Inactive)
+        );
+        assert_eq!(
+            UnavailableReason::classify(true, &[], &[]),
+            Some(UnavailableReason::NoWorkingHours)
+        );
+        assert_eq!(
+            UnavailableReason::classify(true, &[found_hour(false, false)], &[]),
+            Some(UnavailableReason::OutsideHours)
+        );
+        assert_eq!(
+            UnavailableReason::classify(true, &[found_hour(true, true)], &[&blocked]),
+            Some(UnavailableReason::Blocked)
+        );
+        assert_eq!(
+            UnavailableReason::classify(true, &[found_hour(true, false)], &[&booked, &blocked]),
+            Some(UnavailableReason::FullyBooked)
+        );
+        assert_eq!(
+            UnavailableReason::classify(true, &[found_hour(false, false)], &[&booked, &free]),
+            None
+        );
+    }
+
+    #[test]
+    fn a_service_without_a_price_is_free() {
+        let price = ServicePrice::of(&test_row("service", HashMap::<String, _>::new())).unwrap();
+
+        assert_eq!(price.amount, None);
+        assert!(price.is_free());
+
+        let price = ServicePrice::of(&test_row(
+            "service",
+            [
+                ("priceAmount", serde_json::json!(25.5).into()),
+                ("paymentType", PAYMENT_TYPE_ONLINE.into()),
+            ],
+        ))
+        .unwrap();
+
+        assert_eq!(price.amount, Some(25.5));
+        assert_eq!(price.payment_type.as_deref(), Some(PAYMENT_TYPE_ONLINE));
+        assert!(!price.is_free());
+
+        let price = ServicePrice::of(&test_row(
+            "service",
+            [("priceAmount", serde_json::json!(0).into())],
+        ))
+        .unwrap();
+
+        assert!(price.is_free());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sigterm_starts_the_shutdown() {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        // Keeps SIGTERM from ending the test process before `shutdown_signal` listens for it.
+        let _terminate = signal(SignalKind::terminate()).unwrap();
+
+        let mut shutdown = tokio::spawn(shutdown_signal());
+
+        let terminated = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                std::process::Command::new("kill")
+                    .args(["-TERM", &std::process::id().to_string()])
+                    .status()
+                    .unwrap();
+
+                if tokio::time::timeout(Duration::from_millis(50), &mut shutdown)
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        assert!(terminated.is_ok());
+    }
+
+    #[test]
+    fn schedule_times_drop_their_subseconds() {
+        for value in ["10:00:00", "10:00:00.0", "10:00:00.5", " 10:00:00.999999 "] {
+            assert_eq!(
+                parse_schedule_time(value).unwrap(),
+                time::macros::time!(10:00),
+                "{value}"
+            );
+        }
+
+        assert!(parse_schedule_time("10:00").is_err());
+    }
+
+    #[test]
+    fn fractional_minutes_are_kept_to_the_second() {
+        assert_eq!(minutes_to_duration(7.5), time::Duration::seconds(450));
+        assert_eq!(minutes_to_duration(0.25), time::Duration::seconds(15));
+
+        let slots = slots_with(
+            vec![
+                ("duration", serde_json::json!(22.5).into()),
+                ("break", serde_json::json!(7.5).into()),
+            ],
+            vec![("end", "10:00:00".into())],
+            Vec::new(),
+        );
+
+        assert_eq!(
+            starts(&slots),
+            [time::macros::time!(9:00), time::macros::time!(9:30)]
+        );
+        assert_eq!(slots[0].end - slots[0].start, time::Duration::seconds(1350));
+    }
+
+    #[test]
+    fn slots_are_relabeled_as_utc_unless_raw_offsets_are_asked_for() {
+        let schedule = test_row("schedule", [("duration", serde_json::json!(60).into())]);
+        let staff_schedule = test_row(
+            "staffSchedule",
+            [
+                ("staff", "staff".into()),
+                ("timeZone", "Asia/Tokyo".into()),
+                ("start", "09:00:00".into()),
+                ("end", "10:00:00".into()),
+            ],
+        );
+
+        let first_start = |raw_offsets| {
+            gather_available_hours(
+                time::macros::datetime!(2025-03-14 00:00),
+                &test_row("service", HashMap::<String, _>::new()),
+                &schedule,
+                &staff_schedule,
+                &Occupied {
+                    bookings: Vec::new(),
+                    busy: Vec::new(),
+                },
+                None,
+                raw_offsets,
+            )
+            .unwrap()[0]
+                .start
+        };
+
+        assert_eq!(
+            first_start(true),
+            time::macros::datetime!(2025-03-14 09:00 +9)
+        );
+        // The same wall clock time, so a browser in any time zone shows 09:00.
+        assert_eq!(
+            first_start(false),
+            time::macros::datetime!(2025-03-14 09:00 UTC)
+        );
+    }
+
+    #[test]
+    fn available_days_span_the_requested_months() {
+        let window = |year, month, months| {
+            let (start, end) = available_days_window(year, month, months).unwrap();
+
+            (start.date(), end.date())
+        };
+
+        assert_eq!(
+            window(2025, 3, None),
+            (
+                time::macros::date!(2025 - 03 - 01),
+                time::macros::date!(2025 - 04 - 01)
+            )
+        );
+        assert_eq!(
+            window(2025, 11, Some(3)),
+            (
+                time::macros::date!(2025 - 11 - 01),
+                time::macros::date!(2026 - 02 - 01)
+            )
+        );
+        // At most a year, and at least a month.
+        assert_eq!(
+            window(2025, 1, Some(40)).1,
+            time::macros::date!(2026 - 01 - 01)
+        );
+        assert_eq!(
+            window(2025, 1, Some(0)).1,
+            time::macros::date!(2025 - 02 - 01)
+        );
+
+        assert!(available_days_window(2025, 13, None).is_err());
+    }
+
+    #[test]
+    fn only_checked_days_are_marked_fully_booked() {
+        let staff_schedule = test_row(
+            "ana-mondays",
+            [
+                ("timeZone", SimpleValue::from("UTC")),
+                ("startDay", "2025-03-03".into()),
+                ("start", "09:00:00".into()),
+                ("end", "17:00:00".into()),
+                (
+                    "recurrenceRule",
+                    serde_json::json!({ "days": ["MONDAY"], "frequency": "WEEKLY", "interval": 1 })
+                        .into(),
+                ),
+            ],
+        );
+
+        // Days which weren't checked, eg. past the first 62, are left unmarked.
+        let fully_booked = HashMap::from([(
+            (
+                String::from("ana-mondays"),
+                time::macros::date!(2025 - 03 - 03),
+            ),
+            true,
+        )]);
+
+        let days = gather_available_days(
+            time::macros::datetime!(2025-03-01 00:00),
+            time::macros::datetime!(2025-03-15 00:00),
+            &[staff_schedule],
+            &HashMap::new(),
+            &fully_booked,
+        )
+        .unwrap();
+
+        assert_eq!(
+            days.iter()
+                .map(|v| (
+                    v["start"]["dateLocal"].clone(),
+                    v.get("fullyBooked").cloned()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    serde_json::json!(time::macros::date!(2025 - 03 - 03)),
+                    Some(serde_json::json!(true))
+                ),
+                (serde_json::json!(time::macros::date!(2025 - 03 - 10)), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn available_days_name_the_weekday_on_each_side_of_utc() {
+        let staff_schedule = test_row(
+            "staffSchedule",
+            [
+                ("schedule", "schedule".into()),
+                ("timeZone", "Asia/Tokyo".into()),
+                ("startDay", "2025-03-03".into()),
+                ("start", "08:00:00".into()),
+                ("end", "17:00:00".into()),
+                (
+                    "recurrenceRule",
+                    serde_json::json!({ "days": ["MONDAY"], "frequency": "WEEKLY", "interval": 1 })
+                        .into(),
+                ),
+            ],
+        );
+
+        let days = gather_available_days(
+            time::macros::datetime!(2025-03-01 00:00),
+            time::macros::datetime!(2025-03-08 00:00),
+            &[staff_schedule],
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(days.len(), 1);
+        // 08:00 on Monday in Tokyo is still Sunday in UTC.
+        assert_eq!(days[0]["weekdayLocal"], "MONDAY");
+        assert_eq!(days[0]["weekdayUtc"], "SUNDAY");
+        assert_eq!(days[0]["isoWeek"], 10);
+    }
+
+    #[test]
+    fn a_staff_schedule_with_an_unknown_time_zone_is_skipped_from_days() {
+        let staff_schedule = |id: &str, time_zone: &str| {
+            test_row(
+                id,
+                [
+                    ("schedule", "schedule".into()),
+                    ("timeZone", time_zone.into()),
+                    ("startDay", "2025-03-03".into()),
+                    ("start", "09:00:00".into()),
+                    ("end", "17:00:00".into()),
+                    (
+                        "recurrenceRule",
+                        serde_json::json!({ "days": ["MONDAY"], "frequency": "WEEKLY", "interval": 1 })
+                            .into(),
+                    ),
+                ],
+            )
+        };
+
+        let days = gather_available_days(
+            time::macros::datetime!(2025-03-01 00:00),
+            time::macros::datetime!(2025-03-08 00:00),
+            &[
+                staff_schedule("bad", "Mars/Base"),
+                staff_schedule("good", "UTC"),
+            ],
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0]["timeZone"], "UTC");
+
+        // A single schedule's hours still fail on it.
+        assert!(try_slots_with(
+            Vec::new(),
+            vec![("timeZone", "Mars/Base".into())],
+            Vec::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn an_impossible_date_is_a_bad_request() {
+        assert_eq!(
+            calendar_date(2024, 2, 29).unwrap(),
+            time::macros::date!(2024 - 02 - 29)
+        );
+
+        for (year, month, day) in [(2025, 2, 29), (2025, 13, 1), (2025, 4, 31), (2025, 1, 0)] {
+            let error = calendar_date(year, month, day).unwrap_err();
+
+            assert!(
+                matches!(
+                    error,
+                    Error::Coded {
+                        code: ErrorCode::InvalidDate,
+                        ..
+                    }
+                ),
+                "{year}-{month}-{day}"
+            );
+            assert_eq!(
+                axum::response::IntoResponse::into_response(error).status(),
+                axum::http::StatusCode::BAD_REQUEST
+            );
+        }
+    }
+
+    #[test]
+    fn a_lock_expires_its_ttl_after_it_was_taken() {
+        let ttl = Duration::from_secs(600);
+
+        assert_eq!(
+            lock_expires_at(time::macros::datetime!(2025-03-14 09:00 UTC), ttl),
+            time::macros::datetime!(2025-03-14 09:10 UTC)
+        );
+
+        let now = OffsetDateTime::now_utc();
+
+        assert!(!is_lock_expired(now, ttl));
+        assert!(is_lock_expired(now - ttl, ttl));
+    }
+
+    #[test]
+    fn a_mismatched_slot_names_the_expected_id() {
+        let schedule = test_row("weekdays", [("service", SimpleValue::from("haircut"))]);
+        let staff_schedule = test_row(
+            "ana-weekdays",
+            [
+                ("schedule", SimpleValue::from("weekdays")),
+                ("staff", "ana".into()),
+            ],
+        );
+
+        assert!(ensure_slot_linked(&schedule, &staff_schedule, "haircut", "ana").is_ok());
+
+        let error = ensure_slot_linked(&schedule, &staff_schedule, "beard", "ana").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            Error::coded(
+                ErrorCode::ServiceScheduleMismatch,
+                "Service ID beard does not match schedule weekdays (expected service haircut)"
+            )
+            .to_string()
+        );
+
+        let error = ensure_slot_linked(&schedule, &staff_schedule, "haircut", "sam").unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Coded {
+                code: ErrorCode::StaffMismatch,
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("(expected staff ana)"));
+
+        let unlinked = test_row("ana-weekends", [("staff", SimpleValue::from("ana"))]);
+        let error = ensure_slot_linked(&schedule, &unlinked, "haircut", "ana").unwrap_err();
+        assert!(error.to_string().contains("(expected schedule <none>)"));
+    }
+
+    #[test]
+    fn a_rejected_dry_run_gives_the_reason() {
+        assert_eq!(
+            validation_response(Ok(())).unwrap()["valid"],
+            serde_json::json!(true)
+        );
+
+        let response = validation_response(Err(Error::coded(
+            ErrorCode::AlreadyProcessing,
+            "Form already being processed",
+        )))
+        .unwrap();
+
+        assert_eq!(response["valid"], serde_json::json!(false));
+        assert_eq!(
+            response["reason"],
+            serde_json::json!("Form already being processed")
+        );
+        assert_eq!(
+            response["code"],
+            serde_json::to_value(ErrorCode::AlreadyProcessing).unwrap()
+        );
+
+        // Failing to reach the CMS isn't an answer about the slot.
+        assert!(validation_response(Err(eyre::eyre!("CMS unavailable").into())).is_err());
+    }
+
+    #[test]
+    fn a_schedule_without_a_positive_duration_is_named() {
+        let error = try_slots_with(
+            vec![("duration", serde_json::json!(0).into())],
+            Vec::new(),
+            Vec::new(),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::Coded {
+                code: ErrorCode::MissingField,
+                ..
+            }
+        ));
+        assert!(error
+            .to_string()
+            .contains("schedule row schedule has no positive `duration`"));
+    }
+
+    #[test]
+    fn a_missing_field_names_the_row() {
+        let row = test_row("weekdays", HashMap::<String, _>::new());
+        let error = required_field(&row, "schedule", "duration").unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("schedule row weekdays is missing the `duration` field"));
+        assert_eq!(
+            axum::response::IntoResponse::into_response(error).status(),
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+
+    #[test]
+    fn the_staff_members_bookings_under_any_schedule_count() {
+        let slots = slots_with(
+            Vec::new(),
+            Vec::new(),
+            vec![
+                // Under another schedule of the same staff member.
+                booking_row(
+                    "other-schedule",
+                    time::macros::datetime!(2025-03-14 09:00 UTC),
+                    vec![
+                        ("staffMember", "staff".into()),
+                        ("schedule", "other".into()),
+                    ],
+                ),
+                // With someone else.
+                booking_row(
+                    "other-staff",
+                    time::macros::datetime!(2025-03-14 10:00 UTC),
+                    vec![("staffMember", "someone-else".into())],
+                ),
+                // Nobody in particular, so it can't be ruled out.
+                booking_row(
+                    "unassigned",
+                    time::macros::datetime!(2025-03-14 11:00 UTC),
+                    Vec::new(),
+                ),
+            ],
+        );
+
+        assert_eq!(
+            slots.iter().map(|v| v.is_booked).collect::<Vec<_>>(),
+            [true, false, true]
+        );
+    }
+
+    #[test]
+    fn a_paused_staff_schedule_has_no_availability() {
+        for active in [true, false] {
+            let fields = || vec![("active", serde_json::json!(active).into())];
+
+            assert_eq!(
+                slots_with(Vec::new(), fields(), Vec::new()).is_empty(),
+                !active
+            );
+
+            let days = gather_available_days(
+                time::macros::datetime!(2025-03-01 00:00),
+                time::macros::datetime!(2025-04-01 00:00),
+                &[test_row(
+                    "staffSchedule",
+                    [
+                        ("timeZone", SimpleValue::from("UTC")),
+                        ("startDay", "2025-03-03".into()),
+                        ("start", "09:00:00".into()),
+                        ("end", "17:00:00".into()),
+                        (
+                            "recurrenceRule",
+                            serde_json::json!({ "days": ["MONDAY"], "frequency": "WEEKLY", "interval": 1 })
+                                .into(),
+                        ),
+                    ]
+                    .into_iter()
+                    .chain(fields()),
+                )],
+                &HashMap::new(),
+                &HashMap::new(),
+            )
+            .unwrap();
+
+            assert_eq!(days.is_empty(), !active);
+        }
+    }
+
+    #[test]
+    fn a_staff_members_own_time_zone_replaces_the_schedules() {
+        let time_zones = HashMap::from([(String::from("ana"), String::from("Europe/London"))]);
+
+        for (staff, expected) in [("ana", "Europe/London"), ("sam", "America/New_York")] {
+            let mut staff_schedule = test_row(
+                "staffSchedule",
+                [
+                    ("staff", SimpleValue::from(staff)),
+                    ("timeZone", "America/New_York".into()),
+                ],
+            );
+
+            apply_staff_time_zone(&mut staff_schedule, &time_zones).unwrap();
+
+            assert_eq!(
+                row_text(&staff_schedule, "timeZone").unwrap().as_deref(),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn a_service_without_an_active_schedule_isnt_bookable() {
+        let bookability = Bookability::of(
+            &[
+                test_row("weekdays", [("service", SimpleValue::from("haircut"))]),
+                test_row("weekends", [("service", SimpleValue::from("beard"))]),
+            ],
+            &[
+                test_row(
+                    "ana-weekdays",
+                    [("schedule", SimpleValue::from("weekdays"))],
+                ),
+                test_row(
+                    "ana-weekends",
+                    [
+                        ("schedule", SimpleValue::from("weekends")),
+                        ("active", serde_json::json!(false).into()),
+                    ],
+                ),
+                test_row("orphan", [("schedule", SimpleValue::from("removed"))]),
+            ],
+        )
+        .unwrap();
+
+        assert!(bookability.ensure_bookable("ana-weekdays").is_ok());
+
+        for (staff_schedule_id, message) in [
+            ("ana-weekends", "Service beard has no active schedule"),
+            ("orphan", "Staff schedule orphan no longer exists"),
+        ] {
+            let error = bookability.ensure_bookable(staff_schedule_id).unwrap_err();
+
+            assert!(matches!(
+                error,
+                Error::Coded {
+                    code: ErrorCode::ServiceNotBookable,
+                    ..
+                }
+            ));
+            assert!(error.to_string().contains(message), "{error}");
+        }
+    }
+
+    #[test]
+    fn another_websites_staff_schedule_isnt_trusted() {
+        let bookability = Bookability::of(
+            &[test_row(
+                "weekdays",
+                [("service", SimpleValue::from("haircut"))],
+            )],
+            &[test_row(
+                "ana-weekdays",
+                [("schedule", SimpleValue::from("weekdays"))],
+            )],
+        )
+        .unwrap();
+
+        assert!(bookability.ensure_owned("ana-weekdays").is_ok());
+
+        // Eg. a guessed id of a staff schedule under another website.
+        let error = bookability.ensure_owned("elsewhere").unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::Coded {
+                code: ErrorCode::NotOwned,
+                ..
+            }
+        ));
+        assert_eq!(
+            axum::response::IntoResponse::into_response(error).status(),
+            axum::http::StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn a_slot_on_a_dst_transition_is_flagged() {
+        use time::macros::datetime;
+
+        // Clocks spring forward at 02:00 & fall back at 02:00 in New York.
+        assert_eq!(
+            DstWarning::check("America/New_York", datetime!(2025-03-09 02:30)),
+            Some(DstWarning::Gap)
+        );
+        assert_eq!(
+            DstWarning::check("America/New_York", datetime!(2025-11-02 01:30)),
+            Some(DstWarning::Overlap)
+        );
+
+        assert_eq!(
+            DstWarning::check("America/New_York", datetime!(2025-03-09 03:30)),
+            None
+        );
+        assert_eq!(DstWarning::check("UTC", datetime!(2025-03-09 02:30)), None);
+        assert_eq!(
+            DstWarning::check("Mars/Base", datetime!(2025-03-09 02:30)),
+            None
+        );
+    }
+
+    #[test]
+    fn overnight_working_hours_close_the_next_day() {
+        let (start, end) = working_day(
+            time::macros::date!(2025 - 03 - 14),
+            time::macros::time!(22:00),
+            time::macros::time!(2:00),
+            time::macros::offset!(+1),
+        )
+        .unwrap();
+
+        assert_eq!(start, time::macros::datetime!(2025-03-14 22:00 +1));
+        assert_eq!(end, time::macros::datetime!(2025-03-15 02:00 +1));
+
+        let (_, end) = working_day(
+            time::macros::date!(2025 - 03 - 14),
+            time::macros::time!(9:00),
+            time::macros::time!(17:00),
+            time::macros::offset!(+1),
+        )
+        .unwrap();
+
+        assert_eq!(end, time::macros::datetime!(2025-03-14 17:00 +1));
+    }
+
+    #[test]
+    fn a_day_is_only_closed_when_every_slot_is_blocked() {
+        let every_slot = |type_of: &str| {
+            (9..12)
+                .map(|hour| {
+                    booking_row(
+                        &format!("{type_of}-{hour}"),
+                        time::macros::datetime!(2025-03-14 00:00 UTC)
+                            .replace_hour(hour)
+                            .unwrap(),
+                        vec![("type", type_of.into())],
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ClosedReason::blocked(&slots_with(
+                Vec::new(),
+                Vec::new(),
+                every_slot(BOOKING_TYPE_BLOCK)
+            )),
+            Some(ClosedReason::Blocked)
+        );
+        assert_eq!(
+            ClosedReason::blocked(&slots_with(
+                Vec::new(),
+                Vec::new(),
+                every_slot(BOOKING_TYPE_BOOKING)
+            )),
+            None
+        );
+        assert_eq!(ClosedReason::blocked(&[]), None);
+    }
+
+    #[test]
+    fn only_working_hours_offer_slots() {
+        for (type_of, has_slots) in [
+            (STAFF_SCHEDULE_TYPE_WORKING_HOURS, true),
+            ("TIME_OFF", false),
+            ("", false),
+        ] {
+            assert_eq!(
+                !slots_with(Vec::new(), vec![("type", type_of.into())], Vec::new()).is_empty(),
+                has_slots,
+                "{type_of:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn slots_start_on_the_slot_interval() {
+        let slots = slots_with(
+            vec![("slotInterval", serde_json::json!(30).into())],
+            Vec::new(),
+            Vec::new(),
+        );
+
+        // Each slot is still the full duration, so the last one ends with the working hours.
+        assert_eq!(
+            starts(&slots),
+            [
+                time::macros::time!(9:00),
+                time::macros::time!(9:30),
+                time::macros::time!(10:00),
+                time::macros::time!(10:30),
+                time::macros::time!(11:00),
+            ]
+        );
+        assert!(slots
+            .iter()
+            .all(|v| v.end - v.start == time::Duration::HOUR));
+
+        // A non-positive interval falls back to back-to-back slots.
+        let slots = slots_with(
+            vec![("slotInterval", serde_json::json!(0).into())],
+            Vec::new(),
+            Vec::new(),
+        );
+
+        assert_eq!(
+            starts(&slots),
+            [
+                time::macros::time!(9:00),
+                time::macros::time!(10:00),
+                time::macros::time!(11:00),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_slot_overlaps_a_break() {
+        let slots = slots_with(
+            Vec::new(),
+            vec![(
+                "breaks",
+                serde_json::json!([{ "start": "10:00:00", "end": "10:30:00" }]).into(),
+            )],
+            Vec::new(),
+        );
+
+        assert_eq!(
+            starts(&slots),
+            [time::macros::time!(9:00), time::macros::time!(10:30)]
+        );
+    }
+
+    #[test]
+    fn a_break_has_to_end_after_it_starts() {
+        for (start, end) in [
+            ("12:00:00", "12:00:00"),
+            ("13:00:00", "12:00:00"),
+            ("noon", "13:00:00"),
+        ] {
+            let window = BreakWindow {
+                start: String::from(start),
+                end: String::from(end),
+            };
+
+            assert!(window.parse().is_err(), "{start} - {end}");
+        }
+    }
+
+    #[test]
+    fn a_staff_schedule_stops_after_its_end_day() {
+        use time::macros::date;
+
+        assert_eq!(
+            occurrence_days(Vec::new()),
+            [
+                date!(2025 - 03 - 03),
+                date!(2025 - 03 - 10),
+                date!(2025 - 03 - 17),
+                date!(2025 - 03 - 24),
+                date!(2025 - 03 - 31),
+            ]
+        );
+        assert_eq!(
+            occurrence_days(vec![("endDay", "2025-03-17".into())]),
+            [
+                date!(2025 - 03 - 03),
+                date!(2025 - 03 - 10),
+                date!(2025 - 03 - 17)
+            ]
+        );
+    }
+
+    #[test]
+    fn ad_hoc_dates_are_merged_with_the_recurrence() {
+        use time::macros::date;
+
+        assert_eq!(
+            occurrence_days(vec![(
+                "adHocDates",
+                // A Friday, a Monday the recurrence covers & a day outside the lookup.
+                serde_json::json!(["2025-03-14", "2025-03-10", "2025-04-11"]).into(),
+            )]),
+            [
+                date!(2025 - 03 - 03),
+                date!(2025 - 03 - 10),
+                date!(2025 - 03 - 14),
+                date!(2025 - 03 - 17),
+                date!(2025 - 03 - 24),
+                date!(2025 - 03 - 31),
+            ]
+        );
+    }
 
-#[derive(Debug)]
-struct FoundHour {
-    start: OffsetDateTime,
-    end: OffsetDateTime,
-    is_booked: bool,
-    service_id: String,
-    schedule_id: String,
-    staff_id: String,
-    staff_schedule_id: String,
-}
+    #[test]
+    fn a_reference_is_short_and_unambiguous() {
+        let id = Uuid::parse_str("01938f4f-f50c-7203-9f89-b367e9d49efb").unwrap();
+        let reference = booking_reference(id);
+
+        assert_eq!(reference.len(), REFERENCE_LENGTH);
+        assert!(
+            reference.bytes().all(|c| REFERENCE_ALPHABET.contains(&c)),
+            "{reference}"
+        );
+        // The same booking always gets the same reference.
+        assert_eq!(booking_reference(id), reference);
+        assert_ne!(booking_reference(Uuid::now_v7()), reference);
+    }
 
-fn gather_available_hours(
-    list_date: PrimitiveDateTime,
-    service_id: String,
-    schedule: &CmsRowResponse,
-    mut staff_schedule: CmsRowResponse,
-    bookings: ListResponse<CmsRowResponse>,
-) -> Result<Vec<FoundHour>> {
-    let time_zone_str = staff_schedule
-        .fields
-        .get(&SchematicFieldKey::Other(String::from("timeZone")))
-        .cloned()
-        .context("Missing TimeZone")?
-        .try_as_text()?;
-    let local_offset = find_offset_by_id(&time_zone_str).context("Invalid TimeZone")?;
+    #[test]
+    fn a_reference_is_found_however_its_typed() {
+        let reference = booking_reference(Uuid::now_v7());
+        let (head, tail) = reference.split_at(4);
+
+        assert_eq!(normalize_booking_reference(&reference), reference);
+        assert_eq!(
+            normalize_booking_reference(&format!("{head}-{tail}").to_lowercase()),
+            reference
+        );
+        assert_eq!(normalize_booking_reference("7k3q x9md"), "7K3QX9MD");
+        assert_eq!(normalize_booking_reference("O1IL"), "0111");
+    }
 
-    let booked_times = bookings
-        .items
-        .iter()
-        .map(|item| {
-            let start_time = item
-                .fields
-                .get(&SchematicFieldKey::Other(String::from("bookDate"))).unwrap()
-                .any_as_text().unwrap();
-
-            // Parse start_time value of 2025-01-02 12:00:00.0 +00:00:00
-            let start_time = time::OffsetDateTime::parse(
-                &start_time,
-                &format_description!(
-                    "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
-                ),
+    #[test]
+    fn an_instance_only_runs_on_its_start_day() {
+        let instance = || {
+            (
+                "recurrenceType",
+                SimpleValue::from(RECURRENCE_TYPE_INSTANCE),
             )
-            .unwrap()
-            .replace_offset(local_offset);
+        };
+
+        assert_eq!(
+            occurrence_days(vec![instance()]),
+            [time::macros::date!(2025 - 03 - 03)]
+        );
+        // Outside of the days looked up.
+        assert!(occurrence_days(vec![instance(), ("startDay", "2025-04-07".into())]).is_empty());
+        // The recurrence rule isn't needed.
+        assert_eq!(
+            occurrence_days(vec![
+                instance(),
+                ("recurrenceRule", serde_json::Value::Null.into())
+            ]),
+            [time::macros::date!(2025 - 03 - 03)]
+        );
+    }
 
-            start_time
-        })
-        .collect::<Vec<_>>();
+    #[tokio::test]
+    async fn the_form_is_rendered_with_a_typed_submit_query() {
+        use tower::ServiceExt;
 
-    // println!("{bookings:#?}");
-    // println!("{booked_times:?}");
+        let response = Router::new()
+            .route("/form-render", get(get_form_render))
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(format!(
+                        "/form-render?uuid={WEBSITE}&slotId=a.b.c.d.20250314093000&extra=1"
+                    ))
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        // Only the slot is passed on, however it was given.
+        assert_eq!(
+            body["result"]["submitQuery"],
+            serde_json::json!({
+                "uuid": WEBSITE,
+                "slotId": "a.b.c.d.20250314093000",
+                "staffScheduleId": "a",
+                "scheduleId": "b",
+                "serviceId": "c",
+                "staffId": "d",
+                "day": 14,
+                "month": 3,
+                "year": 2025,
+                "time": "09:30:00",
+            })
+        );
+    }
 
-    // TODO: Get bookings for the start-end time for the staff schedule(s)
+    #[tokio::test]
+    async fn the_embedded_form_matches_the_rendered_one() {
+        use tower::ServiceExt;
 
-    let duration = Duration::minutes(
-        schedule
-            .fields
-            .get(&SchematicFieldKey::Other(String::from("duration")))
-            .context("Service Duration")?
-            .try_as_number()?
-            .convert_i64(),
-    );
+        let response = Router::new()
+            .route("/form-render", get(get_form_render))
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(format!(
+                        "/form-render?uuid={WEBSITE}&slotId=a.b.c.d.20250314093000"
+                    ))
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-    let break_duration = Duration::minutes(
-        schedule
-            .fields
-            .get(&SchematicFieldKey::Other(String::from("break")))
-            .context("Service Break")?
-            .try_as_number()?
-            .convert_f64() as i64,
-    );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
-    // schedule.fields.get(&SchematicFieldKey::Other(String::from("serviceSchedule"))) (not used yet)
-    // schedule.fields.get(&SchematicFieldKey::Other(String::from("repeats")))
+        let mut availability = serde_json::json!({ "available": [] });
 
-    // service.fields.get(&SchematicFieldKey::Other(String::from("maxParticipants")))
-    // service.fields.get(&SchematicFieldKey::Other(String::from("priceAmount")))
-    // service.fields.get(&SchematicFieldKey::Other(String::from("paymentType")))
-    // service.fields.get(&SchematicFieldKey::Other(String::from("name")))
-    // service.fields.get(&SchematicFieldKey::Other(String::from("type")))
+        embed_form(&mut availability, false);
+        assert!(availability.get("form").is_none());
 
-    // println!("{staff_schedule:#?}");
-    // println!("{service:#?}");
+        embed_form(&mut availability, true);
+        assert_eq!(availability["form"], body["result"]["data"]);
+        assert!(availability["form"]["fields"].is_array());
+    }
 
-    let mut available_hours = Vec::new();
+    /// The deposit & balance due of a service paid online.
+    fn online_payment(price: f64, deposit: Option<f64>) -> (Option<f64>, f64) {
+        let ServicePayment {
+            deposit_amount,
+            balance_due,
+        } = service_payment(&test_row(
+            "service",
+            [
+                ("paymentType", SimpleValue::from(PAYMENT_TYPE_ONLINE)),
+                ("priceAmount", serde_json::json!(price).into()),
+            ]
+            .into_iter()
+            .chain(deposit.map(|v| ("depositAmount", serde_json::json!(v).into()))),
+        ))
+        .unwrap();
+
+        (deposit_amount, balance_due)
+    }
 
-    {
-        // let date_format = format_description!("[year]-[month]-[day]");
-        let time_format = format_description!("[hour]:[minute]:[second]");
+    #[test]
+    fn only_the_services_duration_options_can_be_chosen() {
+        let service = test_row(
+            "service",
+            [("durationOptions", serde_json::json!([30, 90]).into())],
+        );
 
-        // let start_date = staff_schedule
-        //     .fields
-        //     .remove(&SchematicFieldKey::Other(String::from("startDay")))
-        //     .context("Missing startDay field")?;
+        assert!(validate_duration_option(&service, None).is_ok());
+        assert!(validate_duration_option(&service, Some(90.0)).is_ok());
 
-        // TODO: We're currently assuming that start time < end time. We need to add a date to both of them.
-        let start_time = staff_schedule
-            .fields
-            .remove(&SchematicFieldKey::Other(String::from("start")))
-            .context("Missing start field")?
-            .try_as_text()?
-            .replace(".0", "");
-
-        let end_time = staff_schedule
-            .fields
-            .remove(&SchematicFieldKey::Other(String::from("end")))
-            .context("Missing end field")?
-            .try_as_text()?
-            .replace(".0", "");
-
-        // let start_date = time::Date::parse(&start_date.try_as_text()?, &date_format).unwrap();
-        let start_time = Time::parse(&start_time, &time_format).unwrap();
-        let end_time = Time::parse(&end_time, &time_format).unwrap();
-
-        // We don't convert to UTC since start_time & end_time is in local offset time.
-        let mut current_time_pos = list_date
-            .replace_time(start_time)
-            .assume_offset(local_offset);
-
-        // Loop until we hit the end of time
-        while current_time_pos.time() + duration + break_duration <= end_time {
-            // TODO: Replace w/ UTC offset temporarily to fix JavaScript Date
-            let utc_time_pos = current_time_pos.replace_offset(UtcOffset::UTC);
+        let error = validate_duration_option(&service, Some(45.0)).unwrap_err();
 
-            available_hours.push(FoundHour {
-                start: utc_time_pos,
-                end: (utc_time_pos + duration),
-                is_booked: booked_times.iter().any(|booked_time| {
-                    *booked_time >= current_time_pos && *booked_time <= current_time_pos + duration
-                }),
-                service_id: service_id.clone(),
-                schedule_id: schedule
-                    .fields
-                    .get(&SchematicFieldKey::Id)
-                    .unwrap()
-                    .any_as_text()?,
-                staff_id: staff_schedule
-                    .fields
-                    .get(&SchematicFieldKey::OtherStatic("staff"))
-                    .unwrap()
-                    .any_as_text()?,
-                staff_schedule_id: staff_schedule
-                    .fields
-                    .get(&SchematicFieldKey::Id)
-                    .unwrap()
-                    .any_as_text()?,
-            });
+        assert!(matches!(
+            error,
+            Error::Coded {
+                code: ErrorCode::DurationNotAllowed,
+                ..
+            }
+        ));
+        assert!(error.to_string().contains("Expected one of [30, 90]"));
+    }
+
+    #[test]
+    fn slots_are_as_long_as_the_chosen_duration() {
+        let slots = gather_available_hours(
+            time::macros::datetime!(2025-03-14 00:00),
+            &test_row("service", HashMap::<String, _>::new()),
+            &test_row("schedule", [("duration", serde_json::json!(60).into())]),
+            &test_row(
+                "staffSchedule",
+                [
+                    ("staff", SimpleValue::from("staff")),
+                    ("timeZone", "UTC".into()),
+                    ("start", "09:00:00".into()),
+                    ("end", "10:00:00".into()),
+                ],
+            ),
+            &Occupied {
+                bookings: Vec::new(),
+                busy: Vec::new(),
+            },
+            Some(30.0),
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            starts(&slots),
+            [time::macros::time!(9:00), time::macros::time!(9:30)]
+        );
+    }
+
+    #[test]
+    fn the_slot_length_and_break_match_the_schedule() {
+        let schedule_fields = || {
+            vec![
+                ("duration", serde_json::json!(45).into()),
+                ("break", serde_json::json!(15).into()),
+            ]
+        };
+        let schedule = test_row("schedule", schedule_fields());
+
+        assert_eq!(slot_minutes(&schedule, None).unwrap(), (Some(45.0), 15.0));
+        assert_eq!(
+            slot_minutes(&schedule, Some(90.0)).unwrap(),
+            (Some(90.0), 15.0)
+        );
+        assert_eq!(
+            slot_minutes(&test_row("schedule", HashMap::<String, _>::new()), None).unwrap(),
+            (None, 0.0)
+        );
+
+        // The slots are laid out by the same values.
+        let slots = slots_with(schedule_fields(), Vec::new(), Vec::new());
+
+        assert_eq!(slots[0].end - slots[0].start, Duration::from_secs(45 * 60));
+        assert_eq!(slots[1].start - slots[0].end, Duration::from_secs(15 * 60));
+    }
+
+    #[test]
+    fn image_urls_are_given_for_included_files() {
+        let config = Config::from_lookup(|name| {
+            (name == "BOOKING_FILE_URL_TEMPLATE")
+                .then(|| String::from("https://cdn.example.com/{{FILE}}"))
+        })
+        .unwrap();
+
+        let image_url = |value: serde_json::Value| {
+            row_image_url(
+                &config,
+                &test_row("service", [("image", value.into())]),
+                "image",
+            )
+            .unwrap()
+        };
+
+        // Hydrated when queried with `include_files`.
+        assert_eq!(
+            image_url(
+                serde_json::json!({ "name": "cut.png", "url": "https://files.example.com/cut.png" })
+            ),
+            Some(String::from("https://files.example.com/cut.png"))
+        );
+        assert_eq!(
+            image_url(serde_json::json!({ "name": "cut.png" })),
+            Some(String::from("https://cdn.example.com/cut.png"))
+        );
+        assert_eq!(
+            image_url(serde_json::json!("cut.png")),
+            Some(String::from("https://cdn.example.com/cut.png"))
+        );
+
+        assert_eq!(image_url(serde_json::json!("")), None);
+        assert_eq!(
+            row_image_url(
+                &config,
+                &test_row("service", HashMap::<String, _>::new()),
+                "image"
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn a_weeks_days_match_the_days_listed_alone() {
+        use time::macros::{date, datetime};
+
+        assert_eq!(
+            week_start(date!(2025 - 03 - 14), false),
+            date!(2025 - 03 - 10)
+        );
+        assert_eq!(
+            week_start(date!(2025 - 03 - 10), false),
+            date!(2025 - 03 - 10)
+        );
+        assert_eq!(
+            week_start(date!(2025 - 03 - 14), true),
+            date!(2025 - 03 - 14)
+        );
+
+        let service = test_row("service", HashMap::<String, SimpleValue>::new());
+        let schedule = test_row("schedule", [("duration", serde_json::json!(60).into())]);
+        let staff_schedule = test_row(
+            "staffSchedule",
+            [
+                ("staff", SimpleValue::from("staff")),
+                ("timeZone", "UTC".into()),
+                ("start", "09:00:00".into()),
+                ("end", "12:00:00".into()),
+            ],
+        );
+        let occupied = Occupied {
+            bookings: vec![booking_row(
+                "booking",
+                datetime!(2025-03-14 10:00 UTC),
+                Vec::new(),
+            )],
+            busy: Vec::new(),
+        };
+
+        let days = week_days(
+            date!(2025 - 03 - 10),
+            &HashSet::from([date!(2025 - 03 - 14)]),
+            &service,
+            &schedule,
+            &staff_schedule,
+            &occupied,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(days.len(), 7);
+        assert_eq!(days[0]["weekday"], "MONDAY");
+
+        let friday = gather_available_hours(
+            datetime!(2025-03-14 00:00),
+            &service,
+            &schedule,
+            &staff_schedule,
+            &occupied,
+            None,
+            false,
+        )
+        .unwrap();
+
+        for (i, day) in days.iter().enumerate() {
+            let available = day["available"].as_array().unwrap();
+
+            if i != 4 {
+                assert!(available.is_empty(), "{day}");
+                continue;
+            }
+
+            assert_eq!(available.len(), friday.len());
 
-            current_time_pos += duration + break_duration;
+            for (slot, alone) in available.iter().zip(&friday) {
+                assert_eq!(slot["slotId"], alone.slot_id().to_string());
+                assert_eq!(slot["isBooked"], alone.is_booked);
+            }
         }
+
+        assert_eq!(days[4]["available"][1]["isBooked"], true);
     }
 
-    Ok(available_hours)
-}
+    #[test]
+    fn merged_slots_are_listed_by_start_then_staff() {
+        let staff_slots = |staff: &str, start: &str, end: &str| {
+            slots_with(
+                vec![("duration", serde_json::json!(30).into())],
+                vec![
+                    ("staff", staff.into()),
+                    ("start", start.into()),
+                    ("end", end.into()),
+                ],
+                Vec::new(),
+            )
+        };
+
+        let mut slots = staff_slots("ben", "09:30:00", "10:30:00");
+        slots.extend(staff_slots("ana", "09:00:00", "10:00:00"));
+
+        slots.sort_by(|a, b| a.cmp_chronological(b));
+
+        assert_eq!(
+            slots
+                .iter()
+                .map(|v| (v.start.time(), v.staff_id.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (time::macros::time!(9:00), "ana"),
+                (time::macros::time!(9:30), "ana"),
+                (time::macros::time!(9:30), "ben"),
+                (time::macros::time!(10:00), "ben"),
+            ]
+        );
+    }
 
-fn gather_available_days(
-    lookup_time: PrimitiveDateTime,
-    staff_schedule_items: Vec<CmsRowResponse>,
-) -> Result<Vec<serde_json::Value>> {
-    let lookup_time = lookup_time.assume_utc();
+    #[test]
+    fn a_retried_submission_returns_the_booking_already_written() {
+        let booking = |id: &str, client_key: Option<&str>, status: &str| {
+            booking_row(
+                id,
+                time::macros::datetime!(2025-03-14 09:00 UTC),
+                [
+                    ("reference", SimpleValue::from("7K3QX9MD")),
+                    ("status", status.into()),
+                ]
+                .into_iter()
+                .chain(client_key.map(|v| ("clientKey", v.into())))
+                .collect(),
+            )
+        };
+
+        // Written by the first `after`, whose response was lost.
+        let written = submitted_booking(
+            vec![
+                booking("cancelled", Some("client"), "cancelled"),
+                booking("other", Some("other-client"), BOOKING_STATUS_CONFIRMED),
+                booking("written", Some("client"), BOOKING_STATUS_CONFIRMED),
+            ],
+            "client",
+        )
+        .unwrap()
+        .expect("the retry should find the booking");
+
+        assert_eq!(row_id(&written).unwrap(), "written");
+        assert_eq!(
+            replayed_booking(&written).unwrap(),
+            serde_json::json!({ "reference": "7K3QX9MD", "replayed": true })
+        );
+
+        // Bookings from before `clientKey` still count.
+        assert!(submitted_booking(
+            vec![booking("legacy", None, BOOKING_STATUS_CONFIRMED)],
+            "client"
+        )
+        .unwrap()
+        .is_some());
 
-    let mut available_days = Vec::new();
+        assert!(submitted_booking(Vec::new(), "client").unwrap().is_none());
+    }
 
-    // 1st. Convert Date/Time to UTC
-    let date_format = format_description!("[year]-[month]-[day]");
-    let time_format = format_description!("[hour]:[minute]:[second]");
+    #[tokio::test]
+    async fn a_full_slot_suggests_the_next_free_one() {
+        use time::macros::{date, datetime};
+
+        let service = test_row("service", HashMap::<String, SimpleValue>::new());
+        let schedule = test_row("schedule", [("duration", serde_json::json!(60).into())]);
+        let staff_schedule = test_row(
+            "staffSchedule",
+            [
+                ("staff", SimpleValue::from("staff")),
+                ("timeZone", "UTC".into()),
+                ("start", "09:00:00".into()),
+                ("end", "11:00:00".into()),
+            ],
+        );
+
+        // Far enough ahead to never be in the past.
+        let first_free = |booked: Vec<OffsetDateTime>| {
+            first_free_slot(
+                BTreeSet::from([date!(2030 - 03 - 14), date!(2030 - 03 - 18)]),
+                &service,
+                &schedule,
+                &staff_schedule,
+                &Occupied {
+                    bookings: booked
+                        .into_iter()
+                        .map(|start| booking_row("booking", start, Vec::new()))
+                        .collect(),
+                    busy: Vec::new(),
+                },
+                None,
+                datetime!(2030-03-14 09:00 UTC),
+            )
+            .unwrap()
+            .map(|v| v.start)
+        };
+
+        assert_eq!(
+            first_free(vec![datetime!(2030-03-14 09:00 UTC)]),
+            Some(datetime!(2030-03-14 10:00 UTC))
+        );
+        // The rest of the day is taken, so the next day the schedule runs on is offered.
+        assert_eq!(
+            first_free(vec![
+                datetime!(2030-03-14 09:00 UTC),
+                datetime!(2030-03-14 10:00 UTC)
+            ]),
+            Some(datetime!(2030-03-18 09:00 UTC))
+        );
+
+        let slots = gather_available_hours(
+            datetime!(2030-03-14 00:00),
+            &service,
+            &schedule,
+            &staff_schedule,
+            &Occupied {
+                bookings: vec![booking_row(
+                    "booking",
+                    datetime!(2030-03-14 09:00 UTC),
+                    Vec::new(),
+                )],
+                busy: Vec::new(),
+            },
+            None,
+            true,
+        )
+        .unwrap();
+
+        let response = axum::response::IntoResponse::into_response(
+            booking_conflict(&slots[0], Some(&slots[1]), "UTC").unwrap(),
+        );
+
+        assert_eq!(response.status(), axum::http::StatusCode::CONFLICT);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["code"], "ALREADY_BOOKED");
+        assert_eq!(body["details"]["participants"], 1);
+        assert_eq!(
+            body["details"]["slot"]["slotId"],
+            slots[0].slot_id().to_string()
+        );
+        assert_eq!(
+            body["details"]["alternative"]["slotId"],
+            slots[1].slot_id().to_string()
+        );
+    }
 
-    for mut item in staff_schedule_items {
-        let start_date = item
-            .fields
-            .remove(&SchematicFieldKey::Other(String::from("startDay")))
-            .context("Missing startDay field")?;
-
-        let start_time = item
-            .fields
-            .remove(&SchematicFieldKey::Other(String::from("start")))
-            .context("Missing start field")?
-            .try_as_text()?
-            .replace(".0", "");
-
-        let end_time = item
-            .fields
-            .remove(&SchematicFieldKey::Other(String::from("end")))
-            .context("Missing end field")?
-            .try_as_text()?
-            .replace(".0", "");
-
-        let rec_rule: RecurrenceRule = serde_json::from_value(serde_json::to_value(
-            item.fields
-                .remove(&SchematicFieldKey::Other(String::from("recurrenceRule")))
-                .context("Missing end field")?,
-        )?)?;
+    #[test]
+    fn bookings_are_filtered_by_the_schedules_local_day() {
+        let filters = book_date_filters(
+            time::macros::date!(2025 - 03 - 14),
+            time::macros::offset!(-8),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|filter| match (filter.cond, filter.value) {
+            (FilterConditionType::Gte, FilterValue::Text(v)) => ("from", v),
+            (FilterConditionType::Lte, FilterValue::Text(v)) => ("to", v),
+            _ => panic!("unexpected {} filter", filter.name),
+        })
+        .collect::<Vec<_>>();
+
+        // The local day in -08:00, stored as UTC.
+        assert_eq!(
+            filters,
+            [
+                (
+                    "from",
+                    book_date_value(time::macros::datetime!(2025-03-14 08:00 UTC)).unwrap()
+                ),
+                (
+                    "to",
+                    book_date_value(time::macros::datetime!(2025-03-15 07:59:59 UTC)).unwrap()
+                ),
+            ]
+        );
+    }
 
-        let start_date = Date::parse(&start_date.try_as_text()?, &date_format).unwrap();
-        let start_time = Time::parse(&start_time, &time_format).unwrap();
-        let end_time = Time::parse(&end_time, &time_format).unwrap();
+    #[test]
+    fn slots_show_their_staff_member_when_found() {
+        let config = Config::from_lookup(|_| None).unwrap();
+
+        let staff = StaffDetails::of(
+            &config,
+            Some(&test_row(
+                "ana",
+                [
+                    ("staffName", SimpleValue::from("Ana")),
+                    ("staffImage", "ana.png".into()),
+                ],
+            )),
+        )
+        .unwrap();
 
-        let time_distance = end_time - start_time;
+        assert_eq!(staff.name.as_deref(), Some("Ana"));
+        assert_eq!(
+            staff.image.map(|v| v.any_as_text().unwrap()).as_deref(),
+            Some("ana.png")
+        );
+        assert_eq!(staff.image_url.as_deref(), Some("/files/ana.png"));
 
-        // TODO: Remove Hardcoding
-        let time_zone_str = item
-            .fields
-            .get(&SchematicFieldKey::Other(String::from("timeZone")))
-            .cloned()
-            .context("Missing TimeZone")?
-            .try_as_text()?;
-        let local_offset = find_offset_by_id(&time_zone_str).context("Invalid TimeZone")?;
+        // A missing staff row doesn't fail the slots.
+        let missing = StaffDetails::of(&config, None).unwrap();
 
-        let curr_dt = start_date
-            .with_time(start_time)
-            // America/Los_Angeles PST (UTC-7)
-            .assume_offset(local_offset)
-            .to_offset(UtcOffset::UTC);
+        assert!(missing.name.is_none() && missing.image.is_none() && missing.image_url.is_none());
+    }
+
+    #[test]
+    fn slots_show_their_services_details() {
+        let config = Config::from_lookup(|_| None).unwrap();
+
+        let service = ServiceDetails::of(
+            &config,
+            &test_row(
+                "haircut",
+                [
+                    ("name", SimpleValue::from("Haircut")),
+                    ("description", "Wash, cut & style".into()),
+                    ("image", "haircut.png".into()),
+                ],
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(service.name.as_deref(), Some("Haircut"));
+        assert_eq!(service.description.as_deref(), Some("Wash, cut & style"));
+        assert_eq!(
+            service.image.map(|v| v.any_as_text().unwrap()).as_deref(),
+            Some("haircut.png")
+        );
+        assert_eq!(service.image_url.as_deref(), Some("/files/haircut.png"));
+
+        // A service without a description or image still lists its slots.
+        let plain = ServiceDetails::of(
+            &config,
+            &test_row("plain", [("name", SimpleValue::from("Plain"))]),
+        )
+        .unwrap();
+
+        assert_eq!(plain.name.as_deref(), Some("Plain"));
+        assert!(plain.description.is_none() && plain.image.is_none() && plain.image_url.is_none());
+    }
+
+    #[test]
+    fn a_deposit_leaves_the_rest_of_the_price_due() {
+        assert_eq!(online_payment(50.0, Some(20.0)), (Some(20.0), 30.0));
+        // Never more than the price.
+        assert_eq!(online_payment(50.0, Some(80.0)), (Some(50.0), 0.0));
+        // Without a deposit the full price is paid when booking.
+        assert_eq!(online_payment(50.0, None), (None, 0.0));
+        assert_eq!(online_payment(50.0, Some(0.0)), (None, 0.0));
+    }
 
-        // TODO: Interval
-        let freq = frequency_str_to_duration(&rec_rule.frequency)?;
+    #[test]
+    fn deposits_only_apply_to_online_payments() {
+        let ServicePayment {
+            deposit_amount,
+            balance_due,
+        } = service_payment(&test_row(
+            "service",
+            [
+                ("paymentType", SimpleValue::from("in_person")),
+                ("priceAmount", serde_json::json!(50).into()),
+                ("depositAmount", serde_json::json!(20).into()),
+            ],
+        ))
+        .unwrap();
 
-        let mut found = Vec::new();
+        assert_eq!((deposit_amount, balance_due), (None, 50.0));
+    }
 
-        let mut pos = curr_dt.clone();
+    #[test]
+    fn a_service_without_a_currency_uses_the_default() {
+        let config = Config::from_lookup(|key| match key {
+            "BOOKING_DEFAULT_CURRENCY" => Some(String::from("EUR")),
+            _ => None,
+        })
+        .unwrap();
 
-        // TODO: Can be improved. This is a brute force method.
-        loop {
-            pos = pos.saturating_add(freq);
+        let service = test_row("service", HashMap::<String, _>::new());
 
-            // If we're in the current month, we can add it to the list.
-            if lookup_time.month() == pos.month() {
-                found.push(pos);
+        assert_eq!(service_currency(&config, &service).unwrap(), "EUR");
 
-                // There should only ever be a MAX of 5 weeks in a month.
-                // If we reach 5, we can stop to prevent another loop.
-                if found.len() == 5 {
-                    break;
-                }
-            }
-            // If we passed the current month, we can stop.
-            else if pos > lookup_time {
-                break;
-            }
-            // If we're still in the past, we can skip.
-            else if found.is_empty() {
-                continue;
-            } else {
-                break;
-            }
+        let service = test_row("service", [("currency", "GBP".into())]);
+
+        assert_eq!(service_currency(&config, &service).unwrap(), "GBP");
+
+        for currency in ["usd", "EURO", "€"] {
+            let service = test_row("service", [("currency", currency.into())]);
+
+            assert!(service_currency(&config, &service).is_err(), "{currency}");
         }
+    }
 
-        for utc in found {
-            let local = utc.to_offset(local_offset);
+    #[test]
+    fn session_status_needs_the_minimum_participants() {
+        assert_eq!(SessionStatus::of(1, 3), SessionStatus::Tentative);
+        assert_eq!(SessionStatus::of(3, 3), SessionStatus::Confirmed);
+        assert_eq!(SessionStatus::of(1, 0), SessionStatus::Confirmed);
+    }
 
-            // Start DateTime ID
-            // TODO: Chars [32 start time][1 version][3 duration][1 recurrence][3 original utc offset]
-            let start_id = Uuid::new_v7(uuid::Timestamp::from_unix(
-                uuid::NoContext,
-                utc.unix_timestamp() as u64,
-                0,
-            ));
+    #[test]
+    fn a_group_service_has_a_place_per_participant() {
+        assert_eq!(slot_places_left(8, 3, false), 5);
+        assert_eq!(slot_places_left(8, 3, true), 0);
+        assert_eq!(slot_places_left(2, 3, false), 0);
+    }
 
-            available_days.push(serde_json::json!({
-                // TODO: Add Duration, Recurrence, Week Day, etc.. to it.
-                "id": start_id.as_simple(),
-                "staffScheduleId": item.fields.get(&SchematicFieldKey::Id).unwrap(),
-                "timeZone": time_zone_str,
+    #[test]
+    fn availability_and_booking_agree_on_a_group_slot() {
+        // A `maxParticipants` of 3 under a single chair.
+        let capacity = 3;
+        let slot = (at(9), at(10));
+        let mut occupying = vec![booking(9), booking(9)];
 
-                "start": {
-                    "dateUtc": utc.date(),
-                    "timeUtc": utc.time().format(&time_format).unwrap(),
-                    "dateLocal": local.date(),
-                    "timeLocal": start_time.format(&time_format).unwrap(),
-                },
+        let participants = check_slot_capacity(slot, capacity, &occupying).unwrap();
 
-                "end": {
-                    "dateUtc": (utc + time_distance).date(),
-                    "timeUtc": (utc.time() + time_distance).format(&time_format).unwrap(),
-                    "dateLocal": (local + time_distance).date(),
-                    "timeLocal": end_time.format(&time_format).unwrap(),
-                },
+        assert_eq!(slot_places_left(capacity, participants, false), 1);
 
-                "monthUtc": utc.month() as u8,
-                "dayUtc": utc.day() as u8,
+        occupying.push(booking(9));
 
-                "monthLocal": local.month() as u8,
-                "dayLocal": local.day() as u8,
-            }));
+        assert!(matches!(
+            check_slot_capacity(slot, capacity, &occupying),
+            Err(Error::Coded {
+                code: ErrorCode::AlreadyBooked,
+                ..
+            })
+        ));
+        assert_eq!(slot_places_left(capacity, occupying.len(), false), 0);
+    }
+
+    fn test_state() -> AppState {
+        let config = Config::from_lookup(|key| match key {
+            "BOOKING_ADMIN_TOKEN" => Some(String::from("admin")),
+            _ => None,
+        })
+        .unwrap();
+
+        AppState::new(config).unwrap()
+    }
+
+    async fn lock_request(
+        state: &AppState,
+        method: &str,
+        path: &str,
+        authorization: Option<&str>,
+    ) -> axum::http::StatusCode {
+        use tower::ServiceExt;
+
+        let mut request = axum::http::Request::builder().method(method).uri(format!(
+            "/{WEBSITE}/{path}?scheduleId=schedule&day=14&month=3&year=2025&time=09:00:00"
+        ));
+
+        if let Some(authorization) = authorization {
+            request = request.header(axum::http::header::AUTHORIZATION, authorization);
         }
+
+        let mut request = request.body(axum::body::Body::empty()).unwrap();
+
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 1234))));
+
+        admin_routes(state)
+            .with_state(state.clone())
+            .oneshot(request)
+            .await
+            .unwrap()
+            .status()
     }
 
-    Ok(available_days)
-}
+    #[tokio::test]
+    async fn the_lock_status_needs_the_admin_token() {
+        let state = test_state();
 
-fn frequency_str_to_duration(frequency: &str) -> Result<Duration> {
-    Ok(match frequency {
-        "DAILY" => Duration::days(1),
-        "WEEKLY" => Duration::weeks(1),
-        "MONTHLY" => Duration::weeks(4),
-        "YEARLY" => Duration::weeks(52),
-        v => return Err(eyre::eyre!("Invalid frequency: {v}"))?,
-    })
-}
+        for authorization in [None, Some("Bearer other")] {
+            assert_eq!(
+                lock_request(&state, "GET", "lockStatus", authorization).await,
+                axum::http::StatusCode::UNAUTHORIZED
+            );
+        }
 
-// Start DateTime ID
-// TODO: Chars [32 start time][1 version][3 duration][1 recurrence][3 original utc offset]
-struct BookingId {
-    /// Stores the DateTime in which the booking starts in UTC format.
-    start_time: Uuid,
-    /// Version of the BookingID
-    version: u8,
-    /// The duration of the booking in minutes.
-    duration: u32,
-    /// The recurrence of the booking.
-    recurrence: u8,
-    /// The original offset for the start time.
-    /// Since its' currently stored as UTC.
-    utc_offset: u32,
-}
+        assert_eq!(
+            lock_request(&state, "GET", "lockStatus", Some("Bearer admin")).await,
+            axum::http::StatusCode::OK
+        );
+    }
 
-// let start_id = Uuid::new_v7(uuid::Timestamp::from_unix(
-//     uuid::NoContext,
-//     utc.unix_timestamp() as u64,
-//     0,
-// ));
+    #[tokio::test]
+    async fn clearing_a_lock_needs_the_admin_token() {
+        let state = test_state();
 
-// impl serde::Serialize for BookingId {
-//     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-//     where
-//         S: serde::Serializer,
-//     {
-//         let mut this = self.start_time.simple().to_string();
+        state.slot_lock.try_acquire(&key(), "a", 1).await.unwrap();
 
-//         // format!("{:X}", 42)
-//         // i64::from_str_radix("1f", 16);
+        for authorization in [None, Some("Bearer other")] {
+            assert_eq!(
+                lock_request(&state, "DELETE", "lock", authorization).await,
+                axum::http::StatusCode::UNAUTHORIZED
+            );
+        }
 
-//         // self.version
-//         // self.duration
-//         // self.recurrence
-//         // self.utc_offset
+        assert_eq!(state.slot_lock.holds(&key()).await.unwrap().len(), 1);
+        assert_eq!(
+            lock_request(&state, "DELETE", "lock", Some("Bearer admin")).await,
+            axum::http::StatusCode::OK
+        );
+        assert!(state.slot_lock.holds(&key()).await.unwrap().is_empty());
+    }
 
-//         Ok(this)
-//     }
-// }
+    async fn get_status(uri: &str) -> axum::http::StatusCode {
+        use tower::ServiceExt;
+
+        let state = test_state();
+
+        Router::new()
+            .route("/:uuid/availableHours", get(get_available_hours))
+            .with_state(state)
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(format!("/{WEBSITE}/{uri}"))
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn invalid_available_hours_queries_are_bad_requests() {
+        assert_eq!(
+            get_status(
+                "availableHours?day=14&month=3&year=2025&scheduleIds=a&fromHour=10&toHour=9"
+            )
+            .await,
+            axum::http::StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            get_status("availableHours?day=14&month=3&year=2025&scheduleIds=a&toHour=25").await,
+            axum::http::StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            get_status("availableHours?day=14&month=3&year=2025").await,
+            axum::http::StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::{http::header, response::IntoResponse};
+
+pub static BOOKINGS_CREATED: AtomicU64 = AtomicU64::new(0);
+pub static CONFLICTS_REJECTED: AtomicU64 = AtomicU64::new(0);
+pub static LOCKS_RELEASED: AtomicU64 = AtomicU64::new(0);
+pub static LOCKS_EXPIRED: AtomicU64 = AtomicU64::new(0);
+pub static AVAILABILITY_REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+/// Name, help text and counter of every exposed metric.
+static COUNTERS: [(&str, &str, &AtomicU64); 5] = [
+    (
+        "booking_bookings_created_total",
+        "Bookings created through the booking form.",
+        &BOOKINGS_CREATED,
+    ),
+    (
+        "booking_conflicts_rejected_total",
+        "Form submissions rejected because the slot was taken or being processed.",
+        &CONFLICTS_REJECTED,
+    ),
+    (
+        "booking_locks_released_total",
+        "Slot locks released because the form submission failed.",
+        &LOCKS_RELEASED,
+    ),
+    (
+        "booking_locks_expired_total",
+        "Slot locks which expired before their form was submitted.",
+        &LOCKS_EXPIRED,
+    ),
+    (
+        "booking_availability_requests_total",
+        "Requests for available days or hours.",
+        &AVAILABILITY_REQUESTS,
+    ),
+];
+
+pub fn inc(counter: &AtomicU64) {
+    add(counter, 1);
+}
+
+pub fn add(counter: &AtomicU64, value: u64) {
+    counter.fetch_add(value, Ordering::Relaxed);
+}
+
+/// Counters in the Prometheus text format.
+pub async fn get_metrics() -> impl IntoResponse {
+    let mut body = String::new();
+
+    for (name, help, counter) in &COUNTERS {
+        body.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} counter\n{name} {}\n",
+            counter.load(Ordering::Relaxed)
+        ));
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn metrics_text() -> String {
+        let response = get_metrics().await.into_response();
+
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; version=0.0.4"
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn every_counter_is_exposed() {
+        let text = metrics_text().await;
+
+        for (name, help, _) in &COUNTERS {
+            assert!(text.contains(&format!("# HELP {name} {help}\n# TYPE {name} counter\n")));
+        }
+    }
+
+    #[tokio::test]
+    async fn counters_only_go_up() {
+        // Other tests share the counters, so only a lower bound is known.
+        let before = LOCKS_EXPIRED.load(Ordering::Relaxed);
+
+        inc(&LOCKS_EXPIRED);
+        add(&LOCKS_EXPIRED, 2);
+
+        let exposed = metrics_text()
+            .await
+            .lines()
+            .find_map(|line| line.strip_prefix("booking_locks_expired_total "))
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+
+        assert!(exposed >= before + 3);
+    }
+}
@@ -0,0 +1,1146 @@
+use axum::Json;
+use serde_json::{json, Value};
+
+/// OpenAPI 3.0 description of the public API.
+///
+/// Hand written, so update it alongside the routes in `main`.
+pub async fn get_openapi() -> Json<Value> {
+    Json(json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Booking Addon",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/registration/": {
+                "post": {
+                    "summary": "Install the addon on a website",
+                    "description": "Creates the booking form, its email action and the CMS collections. Every input is checked first and the invalid ones are listed in a VALIDATION_FAILED error.",
+                    "parameters": [
+                        query("locale", "string", false, "Language of the seeded form and sample service (eg. es). Defaults to English"),
+                        query("timeZone", "string", false, "Time zone of the seeded staff schedules (eg. Europe/London). Defaults to the addon's configured time zone"),
+                    ],
+                    "requestBody": json_body(json!({ "type": "object", "description": "Install details sent by the platform" })),
+                    "responses": ok(json!({ "type": "object" })),
+                },
+            },
+            "/{uuid}/availableDays": {
+                "get": {
+                    "summary": "Days in one or more months with working hours",
+                    "parameters": [
+                        website(),
+                        query("year", "integer", true, "Year to list"),
+                        query("month", "integer", true, "Month to list (1-12)"),
+                        query("months", "integer", false, "Consecutive months to list, starting at year/month (1-12). Defaults to 1"),
+                        query("category", "string", false, "Only include services in this category"),
+                        query("fullyBooked", "boolean", false, "Mark days on which every slot is occupied. Only the first 62 days are checked"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "available": { "type": "array", "items": { "$ref": "#/components/schemas/AvailableDay" } },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/search": {
+                "get": {
+                    "summary": "The first open slots of a service within a date range",
+                    "parameters": [
+                        website(),
+                        query("serviceId", "string", true, "Service to search"),
+                        query("from", "string", true, "First day to search (YYYY-MM-DD)"),
+                        query("to", "string", true, "Last day to search (YYYY-MM-DD). At most 31 days in total"),
+                        query("limit", "integer", false, "Most slots to return (1-50). Defaults to 10"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "serviceId": { "type": "string" },
+                            "from": { "type": "string", "format": "date" },
+                            "to": { "type": "string", "format": "date" },
+                            "scannedTo": { "type": "string", "format": "date", "nullable": true, "description": "Last day looked at. Before to when the limit was hit early" },
+                            "items": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "start": { "type": "string", "format": "date-time" },
+                                        "end": { "type": "string", "format": "date-time" },
+                                        "timeZone": { "type": "string" },
+                                        "dstWarning": { "$ref": "#/components/schemas/DstWarning" },
+                                        "serviceId": { "type": "string" },
+                                        "scheduleId": { "type": "string" },
+                                        "staffId": { "type": "string" },
+                                        "staffScheduleId": { "type": "string" },
+                                        "slotId": { "type": "string" },
+                                        "formId": { "type": "string", "nullable": true },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/availableWeek": {
+                "get": {
+                    "summary": "Slots of a staff schedule for the seven days of a week",
+                    "parameters": [
+                        website(),
+                        query("scheduleId", "string", true, "Staff schedule id"),
+                        query("year", "integer", true, "Year"),
+                        query("month", "integer", true, "Month (1-12)"),
+                        query("day", "integer", true, "A day of the week"),
+                        query("startAtDay", "boolean", false, "Start the week at the given day instead of the Monday of its week"),
+                        query("rawOffsets", "boolean", false, "Return times with the schedule's real offset instead of relabeling them as UTC"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "staffScheduleId": { "type": "string" },
+                            "serviceId": { "type": "string" },
+                            "timeZone": { "type": "string" },
+                            "days": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "date": { "type": "string", "format": "date" },
+                                        "weekday": { "type": "string", "example": "MONDAY" },
+                                        "available": {
+                                            "type": "array",
+                                            "description": "Empty on days the schedule doesn't run",
+                                            "items": {
+                                                "type": "object",
+                                                "properties": {
+                                                    "start": { "type": "string", "format": "date-time" },
+                                                    "end": { "type": "string", "format": "date-time" },
+                                                    "isBooked": { "type": "boolean" },
+                                                    "isBlocked": { "type": "boolean" },
+                                                    "remaining": { "type": "integer", "description": "More bookings the slot can take" },
+                                                    "dstWarning": { "$ref": "#/components/schemas/DstWarning" },
+                                                    "slotId": { "type": "string" },
+                                                },
+                                            },
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/workingHours": {
+                "get": {
+                    "summary": "Opening & closing times of a staff schedule on a day",
+                    "parameters": [
+                        website(),
+                        query("scheduleId", "string", true, "Staff schedule id"),
+                        query("year", "integer", true, "Year"),
+                        query("month", "integer", true, "Month (1-12)"),
+                        query("day", "integer", true, "Day of the month"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "timeZone": { "type": "string" },
+                            "closed": { "type": "boolean" },
+                            "reason": { "type": "string", "enum": ["INACTIVE", "NOT_SCHEDULED", "BLOCKED"], "description": "Only set when closed" },
+                            "start": { "$ref": "#/components/schemas/LocalUtcTime" },
+                            "end": { "$ref": "#/components/schemas/LocalUtcTime" },
+                            "breaks": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "start": { "type": "string" },
+                                        "end": { "type": "string" },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/availableHours": {
+                "get": {
+                    "summary": "Bookable slots of a staff schedule on a day",
+                    "description": "Without scheduleIds, the open slots of every staff member of serviceId, grouped by time with the staff who can serve each",
+                    "parameters": [
+                        website(),
+                        query("day", "integer", true, "Day of the month"),
+                        query("month", "integer", true, "Month (1-12)"),
+                        query("year", "integer", true, "Year"),
+                        query("scheduleIds", "string", false, "Staff schedule id. Required unless serviceId is sent"),
+                        query("serviceId", "string", false, "Service to list the slots of across all staff when scheduleIds isn't sent"),
+                        query("rawOffsets", "boolean", false, "Return times with the schedule's real offset instead of relabeling them as UTC"),
+                        query("durationMinutes", "number", false, "Slot length chosen from the service's durationOptions"),
+                        query("includeForm", "boolean", false, "Embed the booking form's fields as form, the same as the data of /form-render"),
+                    ],
+                    "responses": ok(json!({
+                        "oneOf": [
+                            { "$ref": "#/components/schemas/AvailableHours" },
+                            { "$ref": "#/components/schemas/ServiceAvailableHours" },
+                        ],
+                    })),
+                },
+            },
+            "/{uuid}/availabilityCounts": {
+                "get": {
+                    "summary": "Total & remaining slots of each day a service runs on in a month",
+                    "parameters": [
+                        website(),
+                        query("year", "integer", true, "Year"),
+                        query("month", "integer", true, "Month (1-12)"),
+                        query("serviceId", "string", true, "Service id"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "year": { "type": "integer" },
+                            "month": { "type": "integer" },
+                            "serviceId": { "type": "string" },
+                            "days": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "day": { "type": "integer" },
+                                        "total": { "type": "integer" },
+                                        "remaining": { "type": "integer" },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/book/validate": {
+                "get": {
+                    "summary": "Check a slot is bookable without locking it",
+                    "parameters": slot_parameters(vec![website()]),
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "valid": { "type": "boolean" },
+                            "reason": { "type": "string", "nullable": true },
+                            "code": { "$ref": "#/components/schemas/ErrorCode" },
+                            "details": { "$ref": "#/components/schemas/BookingConflict" },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/book/batch": {
+                "post": {
+                    "summary": "Book several services back-to-back with the same staff member",
+                    "description": "Every slot is locked and checked before anything is written. Each slot has to start when the previous one ends",
+                    "parameters": [website()],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["contactUuid", "items"],
+                        "properties": {
+                            "contactUuid": { "type": "string", "format": "uuid" },
+                            "notes": { "type": "string" },
+                            "items": {
+                                "type": "array",
+                                "description": "Booked in this order",
+                                "items": {
+                                    "type": "object",
+                                    "required": ["slotId"],
+                                    "properties": {
+                                        "slotId": { "type": "string" },
+                                        "durationMinutes": { "type": "number" },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "batchId": { "type": "string" },
+                            "bookings": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "reference": { "type": "string" },
+                                        "slotId": { "type": "string" },
+                                        "start": { "type": "string", "format": "date-time" },
+                                        "end": { "type": "string", "format": "date-time" },
+                                        "serviceName": { "type": "string", "nullable": true },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                },
+            },
+            "/form-process/before": {
+                "post": {
+                    "summary": "Lock a slot while the form is submitted",
+                    "parameters": form_process_parameters(),
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "start": { "$ref": "#/components/schemas/SlotTime" },
+                            "end": { "$ref": "#/components/schemas/SlotTime" },
+                            "timeZone": { "type": "string" },
+                            "duration": { "type": "number", "nullable": true },
+                            "serviceName": { "type": "string", "nullable": true },
+                            "lockExpiresAt": { "type": "string", "format": "date-time", "description": "UTC time form-process/after has to be called by" },
+                            "lockTtlSecs": { "type": "integer" },
+                        },
+                    })),
+                },
+            },
+            "/form-process/error": {
+                "post": {
+                    "summary": "Release the lock of a failed submission",
+                    "parameters": form_process_parameters(),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/form-process/after": {
+                "post": {
+                    "summary": "Create the booking for a submitted form",
+                    "parameters": form_process_parameters(),
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["contactUuid", "schemaDataUuid"],
+                        "properties": {
+                            "contactUuid": { "type": "string", "format": "uuid" },
+                            "schemaDataUuid": { "type": "string", "format": "uuid" },
+                        },
+                    })),
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "reference": { "type": "string", "description": "Short code the customer can quote to look up the booking" },
+                            "participants": { "type": "integer", "description": "Customer bookings of the slot, including this one" },
+                            "replayed": { "type": "boolean", "description": "Set when the submission was already booked. Only reference is returned then" },
+                            "sessionStatus": { "$ref": "#/components/schemas/SessionStatus" },
+                        },
+                    })),
+                },
+            },
+            "/form-render": {
+                "get": {
+                    "summary": "Booking form for a slot",
+                    "parameters": slot_parameters(vec![query("uuid", "string", true, "Website id")]),
+                    "responses": ok(json!({ "type": "object" })),
+                },
+            },
+            "/{uuid}/services": {
+                "get": {
+                    "summary": "List services",
+                    "parameters": [
+                        website(),
+                        query("type", "string", false, "Only services of this type"),
+                        query("category", "string", false, "Only services in this category"),
+                        query("offset", "integer", false, "Items to skip"),
+                        query("limit", "integer", false, "Max items to return"),
+                    ],
+                    "responses": ok(page(json!({ "$ref": "#/components/schemas/Service" }))),
+                },
+            },
+            "/{uuid}/service/{id}": {
+                "put": {
+                    "summary": "Update the price of a service",
+                    "parameters": [website(), path("id", "Service id")],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "properties": {
+                            "priceAmount": { "type": "number", "minimum": 0 },
+                            "depositAmount": { "type": "number", "minimum": 0 },
+                            "durationOptions": { "type": "array", "items": { "type": "number", "exclusiveMinimum": 0 } },
+                            "currency": { "type": "string", "example": "USD" },
+                            "cancelCutoffHours": { "type": "number", "minimum": 0, "description": "Hours before the start in which bookings can't be cancelled. 0 turns it off" },
+                            "minParticipants": { "type": "integer", "minimum": 1, "description": "Participants a session needs to run. At most maxParticipants" },
+                            "maxPerContactPerDay": { "type": "number", "minimum": 0, "description": "Bookings a contact can make of the service on a single day. 0 for unlimited" },
+                        },
+                    })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/{uuid}/schedule/{id}": {
+                "put": {
+                    "summary": "Update the slot length of a schedule",
+                    "parameters": [website(), path("id", "Schedule id")],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "properties": {
+                            "duration": { "type": "number", "description": "Minutes, more than 0" },
+                            "break": { "type": "number", "description": "Minutes" },
+                            "slotInterval": { "type": "number", "description": "Minutes" },
+                            "roundToMinutes": { "type": "number", "description": "Minutes the first slot is rounded up to, eg. 15. 0 to turn off" },
+                            "concurrency": { "type": "integer", "minimum": 1, "description": "Bookings the staff member serves at once, eg. 2 for two chairs. 1 by default" },
+                        },
+                    })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/{uuid}/block": {
+                "post": {
+                    "summary": "Block a slot without a customer booking",
+                    "parameters": [website()],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["scheduleId", "staffId", "day", "month", "year", "time"],
+                        "properties": {
+                            "scheduleId": { "type": "string" },
+                            "staffId": { "type": "string" },
+                            "day": { "type": "integer" },
+                            "month": { "type": "integer" },
+                            "year": { "type": "integer" },
+                            "time": { "type": "string", "example": "13:00:00" },
+                        },
+                    })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/{uuid}/block/range": {
+                "post": {
+                    "summary": "Block every day in a range for a staff member, eg. for a vacation",
+                    "parameters": [website()],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["staffId", "from", "to"],
+                        "properties": {
+                            "staffId": { "type": "string" },
+                            "from": { "type": "string", "format": "date", "description": "First day to block" },
+                            "to": { "type": "string", "format": "date", "description": "Last day to block. At most 62 days in total" },
+                            "start": { "type": "string", "example": "09:00:00", "description": "Local start time blocked on each day. Defaults to the start of the day" },
+                            "end": { "type": "string", "example": "17:00:00", "description": "Local end time blocked on each day. Defaults to the end of the day" },
+                        },
+                    })),
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "rangeId": { "type": "string" },
+                            "created": { "type": "integer" },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/block/range/{id}": {
+                "delete": {
+                    "summary": "Lift every block of a range",
+                    "parameters": [website(), path("id", "The rangeId returned when blocking the range")],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "lifted": { "type": "integer" },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/bookings/export.csv": {
+                "get": {
+                    "summary": "Download the bookings in a date range as CSV",
+                    "parameters": [
+                        website(),
+                        query("from", "string", true, "First day (YYYY-MM-DD)"),
+                        query("to", "string", true, "Last day (YYYY-MM-DD)"),
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "Columns: date, service, staff, contact, duration, notes",
+                            "content": { "text/csv": { "schema": { "type": "string" } } },
+                        },
+                    },
+                },
+            },
+            "/{uuid}/lockStatus": {
+                "get": {
+                    "summary": "Whether a slot is locked by form-process/before",
+                    "security": [{ "adminToken": [] }],
+                    "parameters": [
+                        website(),
+                        query("scheduleId", "string", true, "Schedule id"),
+                        query("day", "integer", true, "Day of the month"),
+                        query("month", "integer", true, "Month (1-12)"),
+                        query("year", "integer", true, "Year"),
+                        query("time", "string", true, "Local start time of the slot, eg. 09:00:00"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "locked": { "type": "boolean", "description": "Whether an unexpired hold is on the slot" },
+                            "holds": {
+                                "type": "array",
+                                "description": "A slot is held by as many clients as it has places left",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "clientKey": { "type": "string" },
+                                        "expired": { "type": "boolean" },
+                                        "ageSecs": { "type": "integer" },
+                                        "expiresInSecs": { "type": "integer" },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/booking/{id}": {
+                "get": {
+                    "summary": "A single booking",
+                    "parameters": [website(), path("id", "Booking id or reference")],
+                    "responses": ok(json!({ "$ref": "#/components/schemas/Booking" })),
+                },
+            },
+            "/{uuid}/booking/{id}/status": {
+                "put": {
+                    "summary": "Move a booking to another status, eg. cancel it. Only confirmed bookings occupy their slot",
+                    "parameters": [website(), path("id", "Booking id")],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["status"],
+                        "properties": {
+                            "status": { "type": "string", "enum": ["confirmed", "cancelled", "completed", "no_show"] },
+                            "reason": { "type": "string", "description": "Why the booking was cancelled. Sent with the booking.cancelled webhook" },
+                        },
+                    })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/{uuid}/email/preview": {
+                "get": {
+                    "summary": "The booking email with its placeholders filled in",
+                    "parameters": [
+                        website(),
+                        query("bookingId", "string", false, "Booking to preview the email of. Sample values are used without one"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "subject": { "type": "string" },
+                            "body": { "type": "string" },
+                            "variables": { "type": "object", "additionalProperties": { "type": "string" } },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/lock": {
+                "delete": {
+                    "summary": "Forcibly release every hold form-process/before has on a slot",
+                    "security": [{ "adminToken": [] }],
+                    "parameters": [
+                        website(),
+                        query("scheduleId", "string", true, "Schedule id"),
+                        query("day", "integer", true, "Day of the month"),
+                        query("month", "integer", true, "Month (1-12)"),
+                        query("year", "integer", true, "Year"),
+                        query("time", "string", true, "Local start time of the slot, eg. 09:00:00"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": { "cleared": { "type": "boolean", "description": "Whether a hold was on the slot" } },
+                    })),
+                },
+            },
+            "/{uuid}/staff/{staffId}/bookings": {
+                "get": {
+                    "summary": "Bookings of a staff member",
+                    "parameters": [
+                        website(),
+                        path("staffId", "Staff member id"),
+                        query("from", "string", true, "First day (YYYY-MM-DD)"),
+                        query("to", "string", true, "Last day (YYYY-MM-DD)"),
+                        query("offset", "integer", false, "Items to skip"),
+                        query("limit", "integer", false, "Max items to return"),
+                    ],
+                    "responses": ok(page(json!({ "$ref": "#/components/schemas/Booking" }))),
+                },
+            },
+            "/{uuid}/staff/{staffId}/googleCalendar": {
+                "put": {
+                    "summary": "Turn the Google Calendar sync of a staff member on or off",
+                    "parameters": [website(), path("staffId", "Staff member id")],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["enabled"],
+                        "properties": {
+                            "enabled": { "type": "boolean" },
+                            "calendarId": { "type": "string", "description": "Defaults to the primary calendar" },
+                        },
+                    })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+                "delete": {
+                    "summary": "Disconnect the Google Calendar of a staff member",
+                    "parameters": [website(), path("staffId", "Staff member id")],
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/{uuid}/staff/{staffId}/googleCalendar/authorize": {
+                "get": {
+                    "summary": "Link for a staff member to connect their Google Calendar",
+                    "parameters": [website(), path("staffId", "Staff member id")],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": { "url": { "type": "string" } },
+                    })),
+                },
+            },
+            "/googleCalendar/callback": {
+                "get": {
+                    "summary": "OAuth redirect which stores the staff member's Google Calendar access",
+                    "parameters": [
+                        query("code", "string", true, "Authorization code from Google"),
+                        query("state", "string", true, "State of the authorize link"),
+                    ],
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "staffId": { "type": "string" },
+                            "connected": { "type": "boolean" },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/staffSchedule": {
+                "post": {
+                    "summary": "Create a staff schedule",
+                    "parameters": [website()],
+                    "requestBody": json_body(json!({ "$ref": "#/components/schemas/StaffSchedule" })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/{uuid}/staffSchedule/weekly": {
+                "post": {
+                    "summary": "Create a weekly staff schedule for each day with working hours",
+                    "parameters": [website()],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["staff", "schedule", "timeZone", "days"],
+                        "properties": {
+                            "name": { "type": "string", "example": "business" },
+                            "staff": { "type": "string" },
+                            "schedule": { "type": "string" },
+                            "timeZone": { "type": "string", "example": "America/Los_Angeles" },
+                            "startDay": { "type": "string", "format": "date" },
+                            "days": {
+                                "type": "object",
+                                "description": "Working hours by day of the week, eg. MONDAY",
+                                "additionalProperties": {
+                                    "type": "object",
+                                    "required": ["start", "end"],
+                                    "properties": {
+                                        "start": { "type": "string", "example": "10:00:00" },
+                                        "end": { "type": "string", "example": "18:00:00" },
+                                    },
+                                },
+                            },
+                        },
+                    })),
+                    "responses": ok(json!({
+                        "type": "object",
+                        "properties": {
+                            "created": { "type": "integer" },
+                            "days": { "type": "array", "items": { "type": "string" } },
+                        },
+                    })),
+                },
+            },
+            "/{uuid}/staffSchedule/{id}": {
+                "put": {
+                    "summary": "Replace a staff schedule",
+                    "parameters": [website(), path("id", "Staff schedule id")],
+                    "requestBody": json_body(json!({ "$ref": "#/components/schemas/StaffSchedule" })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+            "/{uuid}/staffSchedule/{id}/active": {
+                "put": {
+                    "summary": "Pause or resume a staff schedule",
+                    "description": "Paused schedules are kept but offer no available days or hours.",
+                    "parameters": [website(), path("id", "Staff schedule id")],
+                    "requestBody": json_body(json!({
+                        "type": "object",
+                        "required": ["active"],
+                        "properties": {
+                            "active": { "type": "boolean" },
+                        },
+                    })),
+                    "responses": ok(json!({ "type": "null" })),
+                },
+            },
+        },
+        "components": {
+            "securitySchemes": {
+                "adminToken": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "The BOOKING_ADMIN_TOKEN. Requests without it get a 401",
+                },
+            },
+            "schemas": {
+                "LocalUtcTime": {
+                    "type": "object",
+                    "properties": {
+                        "local": { "type": "string", "description": "ISO 8601 with the schedule's offset" },
+                        "utc": { "type": "string", "description": "ISO 8601 in UTC" },
+                    },
+                },
+                "SessionStatus": {
+                    "type": "string",
+                    "enum": ["confirmed", "tentative"],
+                    "description": "Tentative until the service's minParticipants have booked the slot",
+                },
+                "DstWarning": {
+                    "type": "string",
+                    "nullable": true,
+                    "enum": ["GAP", "OVERLAP"],
+                    "description": "Set when the slot starts or ends in a daylight saving gap (skipped time) or overlap (repeated time). The slot keeps its wall clock time at the time zone's fixed offset",
+                },
+                "ErrorCode": {
+                    "type": "string",
+                    "nullable": true,
+                    "enum": [
+                        "SERVICE_SCHEDULE_MISMATCH",
+                        "SCHEDULE_MISMATCH",
+                        "STAFF_MISMATCH",
+                        "ALREADY_PROCESSING",
+                        "TIME_NOT_FOUND",
+                        "ALREADY_BOOKED",
+                        "DUPLICATE_BOOKING",
+                        "LOCK_EXPIRED",
+                        "MISSING_FIELD",
+                        "DURATION_NOT_ALLOWED",
+                        "SERVICE_NOT_BOOKABLE",
+                        "NOT_CONTIGUOUS",
+                        "INVALID_STATUS_TRANSITION",
+                        "INVALID_DATE",
+                        "CANCELLATION_WINDOW_CLOSED",
+                        "VALIDATION_FAILED",
+                        "NOT_OWNED",
+                        "CONTACT_DAILY_LIMIT_REACHED",
+                        "IDEMPOTENCY_KEY_REUSED",
+                        "BLOCK_RANGE_NOT_FOUND",
+                    ],
+                },
+                "BookingConflict": {
+                    "type": "object",
+                    "nullable": true,
+                    "description": "Sent with ALREADY_BOOKED (409) from form-process/before and book/validate",
+                    "properties": {
+                        "slot": { "$ref": "#/components/schemas/ConflictSlot" },
+                        "participants": { "type": "integer", "description": "Customer bookings of the slot" },
+                        "blocked": { "type": "boolean", "description": "Taken by a staff block or calendar event" },
+                        "alternative": {
+                            "allOf": [{ "$ref": "#/components/schemas/ConflictSlot" }],
+                            "nullable": true,
+                            "description": "Next free slot of the same staff schedule within 7 days",
+                        },
+                    },
+                },
+                "ConflictSlot": {
+                    "type": "object",
+                    "properties": {
+                        "start": { "type": "string", "format": "date-time" },
+                        "end": { "type": "string", "format": "date-time" },
+                        "timeZone": { "type": "string" },
+                        "serviceId": { "type": "string" },
+                        "scheduleId": { "type": "string" },
+                        "staffId": { "type": "string" },
+                        "staffScheduleId": { "type": "string" },
+                        "slotId": { "type": "string" },
+                    },
+                },
+                "SlotTime": {
+                    "type": "object",
+                    "properties": {
+                        "utc": { "type": "string", "format": "date-time" },
+                        "local": { "type": "string", "format": "date-time" },
+                    },
+                },
+                "AvailableDay": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "staffScheduleId": { "type": "string" },
+                        "timeZone": { "type": "string" },
+                        "start": { "type": "object" },
+                        "end": { "type": "object" },
+                        "monthUtc": { "type": "integer" },
+                        "dayUtc": { "type": "integer" },
+                        "monthLocal": { "type": "integer" },
+                        "dayLocal": { "type": "integer" },
+                        "weekdayUtc": { "type": "string" },
+                        "weekdayLocal": { "type": "string" },
+                        "isoWeek": { "type": "integer" },
+                        "fullyBooked": { "type": "boolean", "description": "Only set when requested with fullyBooked" },
+                    },
+                },
+                "ServiceAvailableHours": {
+                    "type": "object",
+                    "properties": {
+                        "serviceId": { "type": "string" },
+                        "formId": { "type": "string", "nullable": true },
+                        "available": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "start": { "type": "string", "format": "date-time" },
+                                    "end": { "type": "string", "format": "date-time" },
+                                    "staff": {
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "properties": {
+                                                "staffId": { "type": "string" },
+                                                "staffScheduleId": { "type": "string" },
+                                                "scheduleId": { "type": "string" },
+                                                "slotId": { "type": "string" },
+                                                "timeZone": { "type": "string" },
+                                                "dstWarning": { "$ref": "#/components/schemas/DstWarning" },
+                                            },
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+                "AvailableHours": {
+                    "type": "object",
+                    "properties": {
+                        "timeZone": { "type": "string" },
+                        "priceAmount": { "type": "number" },
+                        "isFree": { "type": "boolean" },
+                        "depositAmount": { "type": "number", "nullable": true },
+                        "balanceDue": { "type": "number" },
+                        "currency": { "type": "string" },
+                        "paymentType": { "type": "string", "nullable": true },
+                        "serviceName": { "type": "string", "nullable": true },
+                        "serviceDescription": { "type": "string", "nullable": true },
+                        "serviceImage": { "nullable": true, "description": "Image of the service, as stored in the CMS" },
+                        "serviceImageUrl": { "type": "string", "nullable": true, "description": "Where the service's image is served from" },
+                        "staffName": { "type": "string", "nullable": true },
+                        "staffImage": { "nullable": true, "description": "Image of the staff member, as stored in the CMS" },
+                        "staffImageUrl": { "type": "string", "nullable": true, "description": "Where the staff member's image is served from" },
+                        "durationMinutes": { "type": "number", "description": "Length of every slot" },
+                        "breakMinutes": { "type": "number", "description": "Break after every slot. 0 when the schedule has none" },
+                        "available": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "start": { "type": "string", "format": "date-time" },
+                                    "end": { "type": "string", "format": "date-time" },
+                                    "isBooked": { "type": "boolean" },
+                                    "isBlocked": { "type": "boolean" },
+                                    "dstWarning": { "$ref": "#/components/schemas/DstWarning" },
+                                    "participants": { "type": "integer", "description": "Customer bookings of the slot" },
+                                    "remaining": { "type": "integer", "description": "More bookings the slot can take under the schedule's concurrency" },
+                                    "sessionStatus": { "$ref": "#/components/schemas/SessionStatus" },
+                                    "serviceId": { "type": "string" },
+                                    "scheduleId": { "type": "string" },
+                                    "staffId": { "type": "string" },
+                                    "staffScheduleId": { "type": "string" },
+                                    "slotId": { "type": "string" },
+                                    "formId": { "type": "string", "nullable": true, "description": "Null until the service has a form" },
+                                },
+                            },
+                        },
+                        "reason": {
+                            "type": "string",
+                            "nullable": true,
+                            "description": "Why no slot can be booked. Null when one can",
+                            "enum": ["INACTIVE", "NO_WORKING_HOURS", "OUTSIDE_HOURS", "BLOCKED", "FULLY_BOOKED"],
+                        },
+                    },
+                },
+                "Service": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "hasSchedule": { "type": "boolean" },
+                        "bookable": { "type": "boolean", "description": "Has at least one active staff schedule" },
+                        "name": { "type": "string", "nullable": true },
+                        "description": { "type": "string", "nullable": true },
+                        "type": { "type": "string", "nullable": true },
+                        "category": { "type": "string", "nullable": true },
+                        "priceAmount": { "type": "number", "nullable": true },
+                        "depositAmount": { "type": "number", "nullable": true },
+                        "durationOptions": { "type": "array", "items": { "type": "number" } },
+                        "currency": { "type": "string" },
+                        "paymentType": { "type": "string", "nullable": true },
+                        "maxParticipants": { "type": "number", "nullable": true },
+                        "minParticipants": { "type": "number", "description": "Participants a session needs to run. 1 by default" },
+                        "cancelCutoffHours": { "type": "number", "nullable": true, "description": "Hours before the start in which bookings can't be cancelled" },
+                        "maxPerContactPerDay": { "type": "number", "nullable": true, "description": "Bookings a contact can make of the service on a single day. Unlimited when not set" },
+                        "image": { "nullable": true },
+                        "imageUrl": { "type": "string", "nullable": true, "description": "Where the image is served from" },
+                        "formId": { "type": "string", "nullable": true },
+                    },
+                },
+                "Booking": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "bookID": { "type": "string", "nullable": true },
+                        "reference": { "type": "string", "nullable": true, "description": "Short code the customer can quote, eg. 7K3QX9MD" },
+                        "type": { "type": "string", "nullable": true },
+                        "status": { "type": "string", "enum": ["confirmed", "cancelled", "completed", "no_show"] },
+                        "bookDate": { "type": "string", "format": "date-time" },
+                        "duration": { "type": "number", "nullable": true },
+                        "currency": { "type": "string", "nullable": true },
+                        "depositAmount": { "type": "number", "nullable": true },
+                        "balanceDue": { "type": "number", "nullable": true },
+                        "serviceId": { "type": "string", "nullable": true },
+                        "serviceName": { "type": "string", "nullable": true },
+                        "cancelCutoffHours": { "type": "number", "nullable": true, "description": "Hours before the start in which the booking can't be cancelled" },
+                        "staffId": { "type": "string", "nullable": true },
+                        "contactUuid": { "type": "string", "nullable": true },
+                        "schemaDataUuid": { "type": "string", "nullable": true },
+                        "submissionLink": { "type": "string", "nullable": true },
+                        "notes": { "type": "string", "nullable": true },
+                        "createdAt": { "type": "string", "nullable": true },
+                        "source": { "type": "string", "nullable": true, "example": "widget" },
+                    },
+                },
+                "StaffSchedule": {
+                    "type": "object",
+                    "required": ["name", "staff", "schedule", "startDay", "start", "end", "timeZone", "recurrenceRule"],
+                    "properties": {
+                        "name": { "type": "string" },
+                        "staff": { "type": "string" },
+                        "schedule": { "type": "string" },
+                        "startDay": { "type": "string", "format": "date" },
+                        "endDay": { "type": "string", "format": "date" },
+                        "start": { "type": "string", "example": "10:00:00" },
+                        "end": { "type": "string", "example": "18:00:00" },
+                        "overnight": { "type": "boolean" },
+                        "breaks": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "start": { "type": "string" },
+                                    "end": { "type": "string" },
+                                },
+                            },
+                        },
+                        "adHocDates": {
+                            "type": "array",
+                            "items": { "type": "string", "format": "date" },
+                            "description": "One-off days the schedule also runs on, on top of its recurrence",
+                        },
+                        "timeZone": { "type": "string", "example": "America/Los_Angeles" },
+                        "recurrenceRule": {
+                            "description": "A single rule, or an array of rules whose occurrences are combined",
+                            "oneOf": [
+                                recurrence_rule(),
+                                { "type": "array", "items": recurrence_rule() },
+                            ],
+                        },
+                        "recurrenceType": { "type": "string", "enum": ["RECURRING", "INSTANCE"], "description": "INSTANCE only runs once, on startDay. Defaults to RECURRING" },
+                        "type": { "type": "string", "example": "WORKING_HOURS", "description": "Only WORKING_HOURS offers slots. Other types (eg. TIME_OFF) block the staff member" },
+                    },
+                },
+            },
+        },
+    }))
+}
+
+fn recurrence_rule() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "days": { "type": "array", "items": { "type": "string" } },
+            "frequency": { "type": "string", "example": "WEEKLY" },
+            "interval": { "type": "integer" },
+        },
+    })
+}
+
+fn website() -> Value {
+    path("uuid", "Website id")
+}
+
+fn path(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+fn query(name: &str, type_of: &str, required: bool, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": required,
+        "description": description,
+        "schema": { "type": type_of },
+    })
+}
+
+/// The slot parameters, either `slotId` or the deprecated individual fields.
+fn slot_parameters(mut parameters: Vec<Value>) -> Vec<Value> {
+    parameters.extend([
+        query(
+            "slotId",
+            "string",
+            false,
+            "The slotId returned by availableHours",
+        ),
+        query("staffScheduleId", "string", false, "Deprecated: Use slotId"),
+        query("scheduleId", "string", false, "Deprecated: Use slotId"),
+        query("serviceId", "string", false, "Deprecated: Use slotId"),
+        query("staffId", "string", false, "Deprecated: Use slotId"),
+        query("day", "integer", false, "Deprecated: Use slotId"),
+        query("month", "integer", false, "Deprecated: Use slotId"),
+        query("year", "integer", false, "Deprecated: Use slotId"),
+        query("time", "string", false, "Deprecated: Use slotId"),
+        query(
+            "durationMinutes",
+            "number",
+            false,
+            "Length chosen from the service's durationOptions",
+        ),
+    ]);
+
+    parameters
+}
+
+fn form_process_parameters() -> Vec<Value> {
+    slot_parameters(vec![
+        query("clientKey", "string", true, "Key of the submitting client"),
+        query("uuid", "string", true, "Website id"),
+        json!({
+            "name": "Idempotency-Key",
+            "in": "header",
+            "required": false,
+            "description": "Retries with the same key return the original response. Reusing a key for a different slot or contact fails with IDEMPOTENCY_KEY_REUSED (422)",
+            "schema": { "type": "string" },
+        }),
+    ])
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+/// Responses wrapped in the standard `WrappingResponse`.
+fn ok(result: Value) -> Value {
+    json!({
+        "200": {
+            "description": "Success",
+            "content": {
+                "application/json": {
+                    "schema": {
+                        "type": "object",
+                        "properties": { "result": result },
+                    },
+                },
+            },
+        },
+        "default": {
+            "description": "Error",
+            "content": {
+                "application/json": {
+                    "schema": {
+                        "type": "object",
+                        "properties": {
+                            "error": { "type": "string" },
+                            "code": { "$ref": "#/components/schemas/ErrorCode" },
+                            "errors": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Every invalid input. Only sent with VALIDATION_FAILED",
+                            },
+                            "details": { "$ref": "#/components/schemas/BookingConflict" },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn page(item: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "total": { "type": "integer" },
+            "offset": { "type": "integer" },
+            "limit": { "type": "integer" },
+            "items": { "type": "array", "items": item },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every `$ref` within the value.
+    fn refs(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(reference)) => found.push(reference.clone()),
+                        _ => refs(value, found),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|v| refs(v, found)),
+            _ => (),
+        }
+    }
+
+    #[tokio::test]
+    async fn every_reference_is_a_component() {
+        let Json(spec) = get_openapi().await;
+
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+
+        assert!(!found.is_empty());
+
+        for reference in found {
+            let pointer = reference.strip_prefix('#').unwrap();
+
+            assert!(spec.pointer(pointer).is_some(), "{reference}");
+        }
+    }
+
+    #[tokio::test]
+    async fn every_path_parameter_is_described() {
+        let Json(spec) = get_openapi().await;
+
+        for (path, operations) in spec["paths"].as_object().unwrap() {
+            let names = path
+                .split('/')
+                .filter_map(|v| v.strip_prefix('{')?.strip_suffix('}'))
+                .collect::<Vec<_>>();
+
+            for (method, operation) in operations.as_object().unwrap() {
+                assert!(operation["responses"].is_object(), "{method} {path}");
+
+                for name in &names {
+                    assert!(
+                        operation["parameters"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .any(|v| v["in"] == "path" && v["name"] == *name),
+                        "{method} {path} is missing {name}"
+                    );
+                }
+            }
+        }
+    }
+}
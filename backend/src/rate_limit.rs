@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use webby_addon_common::WrappingResponse;
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use hyper::StatusCode;
+use tokio::sync::Mutex;
+
+use crate::{AppState, WebsiteQuery};
+
+/// Fixed window request counts keyed by the client IP, or whatever else identifies the caller.
+pub struct RateLimiter<K = IpAddr> {
+    /// Max requests a single key can make within `window`.
+    limit: u32,
+    window: Duration,
+    /// Per key: when the current window started and how many requests were made in it.
+    requests: Mutex<HashMap<K, (Instant, u32)>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request from `key`. Returns whether it's within the limit.
+    async fn allow(&self, key: K) -> bool {
+        let now = Instant::now();
+        let mut requests = self.requests.lock().await;
+
+        let (started, count) = requests.entry(key).or_insert((now, 0));
+
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+
+        *count += 1;
+
+        *count <= self.limit
+    }
+
+    /// Drops expired windows so the map doesn't grow forever. Run periodically in the background.
+    pub async fn sweep(&self) {
+        let now = Instant::now();
+
+        self.requests
+            .lock()
+            .await
+            .retain(|_, (started, _)| now.duration_since(*started) < self.window);
+    }
+}
+
+/// Responds with 429 once the client IP exceeded the [`RateLimiter`] of the state.
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.rate_limiter.allow(addr.ip()).await {
+        return too_many_requests();
+    }
+
+    next.run(request).await
+}
+
+/// Responds with 429 once the website of the `uuid` query exceeded the website [`RateLimiter`].
+///
+/// For routes called server to server, where the few shared IPs of the platform would exceed an
+/// IP limit. Requests without a `uuid` are left for the route to reject.
+pub async fn rate_limit_website(
+    State(state): State<AppState>,
+    query: Option<Query<WebsiteQuery>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(Query(WebsiteQuery { uuid })) = query {
+        if !state.website_rate_limiter.allow(uuid).await {
+            return too_many_requests();
+        }
+    }
+
+    next.run(request).await
+}
+
+fn too_many_requests() -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(WrappingResponse::<()>::error(String::from(
+            "Too many requests",
+        ))),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn state(limit: u32) -> AppState {
+        let config = Config::from_lookup(|key| match key {
+            "BOOKING_RATE_LIMIT" => Some(limit.to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        AppState::new(config).unwrap()
+    }
+
+    async fn status(state: &AppState, ip: [u8; 4]) -> StatusCode {
+        let router = Router::new()
+            .route("/", get(|| async {}))
+            .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+            .with_state(state.clone());
+
+        let mut request = Request::new(Body::empty());
+
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((ip, 1234))));
+
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn limits_each_ip() {
+        let state = state(2);
+
+        assert_eq!(status(&state, [10, 0, 0, 1]).await, StatusCode::OK);
+        assert_eq!(status(&state, [10, 0, 0, 1]).await, StatusCode::OK);
+        assert_eq!(
+            status(&state, [10, 0, 0, 1]).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(status(&state, [10, 0, 0, 2]).await, StatusCode::OK);
+    }
+
+    async fn website_status(state: &AppState, query: &str) -> StatusCode {
+        let router = Router::new()
+            .route("/", axum::routing::post(|| async {}))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                rate_limit_website,
+            ))
+            .with_state(state.clone());
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/?{query}"))
+            .body(Body::empty())
+            .unwrap();
+
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn limits_each_website() {
+        let config = Config::from_lookup(|key| match key {
+            "BOOKING_WEBSITE_RATE_LIMIT" => Some(String::from("2")),
+            _ => None,
+        })
+        .unwrap();
+        let state = AppState::new(config).unwrap();
+
+        let first = "clientKey=a&uuid=01938f4f-f50c-7203-9f89-b367e9d49efb";
+        let second = "clientKey=a&uuid=01938f4f-f50c-7203-9f89-b367e9d49efc";
+
+        assert_eq!(website_status(&state, first).await, StatusCode::OK);
+        assert_eq!(website_status(&state, first).await, StatusCode::OK);
+        assert_eq!(
+            website_status(&state, first).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(website_status(&state, second).await, StatusCode::OK);
+
+        // Left for the route to reject.
+        assert_eq!(website_status(&state, "clientKey=a").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_new_window_resets_the_count() {
+        let limiter = RateLimiter::new(1, Duration::ZERO);
+        let ip = IpAddr::from([10, 0, 0, 1]);
+
+        assert!(limiter.allow(ip).await);
+        assert!(limiter.allow(ip).await);
+    }
+
+    #[tokio::test]
+    async fn sweep_drops_expired_windows() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+
+        limiter.allow(IpAddr::from([10, 0, 0, 1])).await;
+        limiter.sweep().await;
+
+        assert_eq!(limiter.requests.lock().await.len(), 1);
+
+        let limiter = RateLimiter::new(1, Duration::ZERO);
+
+        limiter.allow(IpAddr::from([10, 0, 0, 1])).await;
+        limiter.sweep().await;
+
+        assert!(limiter.requests.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn counts_are_kept_per_state() {
+        let first = state(1);
+        let second = state(1);
+
+        assert_eq!(status(&first, [10, 0, 0, 1]).await, StatusCode::OK);
+        assert_eq!(
+            status(&first, [10, 0, 0, 1]).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(status(&second, [10, 0, 0, 1]).await, StatusCode::OK);
+    }
+}
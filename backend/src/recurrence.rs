@@ -0,0 +1,491 @@
+use std::{collections::VecDeque, str::FromStr};
+
+use time::{Date, Duration, Month, OffsetDateTime, Time, UtcOffset, Weekday};
+
+use crate::{Error, Result};
+
+pub const WEEK_DAYS: [&str; 7] = [
+    "MONDAY",
+    "TUESDAY",
+    "WEDNESDAY",
+    "THURSDAY",
+    "FRIDAY",
+    "SATURDAY",
+    "SUNDAY",
+];
+
+pub const FREQUENCIES: [&str; 5] = ["DAILY", "WEEKLY", "BIWEEKLY", "MONTHLY", "YEARLY"];
+
+#[derive(serde::Deserialize)]
+pub struct RecurrenceRule {
+    pub days: Vec<String>,
+    pub frequency: String,
+    pub interval: usize,
+}
+
+impl RecurrenceRule {
+    /// Every occurrence of the rule, starting with `anchor` (in the schedule's local offset).
+    ///
+    /// `repeats` caps the number of occurrences, including the anchor.
+    /// `end_day` is the last local day an occurrence can fall on.
+    pub fn occurrences(
+        &self,
+        anchor: OffsetDateTime,
+        repeats: Option<usize>,
+        end_day: Option<Date>,
+    ) -> Result<RecurrenceIterator> {
+        let days = self
+            .days
+            .iter()
+            .map(|day| parse_weekday(day))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(RecurrenceIterator::new(
+            anchor,
+            self.frequency.parse()?,
+            self.interval,
+            days,
+            repeats,
+            end_day,
+        ))
+    }
+}
+
+/// The `recurrenceRule` field of a staff schedule. Either a single rule or several,
+/// eg. weekly on Monday & Wednesday plus a monthly special day.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+pub enum RecurrenceRules {
+    One(RecurrenceRule),
+    Many(Vec<RecurrenceRule>),
+}
+
+impl RecurrenceRules {
+    pub fn rules(&self) -> &[RecurrenceRule] {
+        match self {
+            Self::One(rule) => std::slice::from_ref(rule),
+            Self::Many(rules) => rules,
+        }
+    }
+
+    /// Every occurrence of any of the rules, in order. Days hit by several rules occur once.
+    ///
+    /// `repeats` caps the number of occurrences across all rules, including the anchor.
+    pub fn occurrences(
+        &self,
+        anchor: OffsetDateTime,
+        repeats: Option<usize>,
+        end_day: Option<Date>,
+    ) -> Result<MergedOccurrences> {
+        let iterators = self
+            .rules()
+            .iter()
+            .map(|rule| Ok(rule.occurrences(anchor, None, end_day)?.peekable()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(MergedOccurrences {
+            iterators,
+            repeats: repeats.filter(|v| *v != 0),
+            yielded: 0,
+        })
+    }
+}
+
+/// Merges the occurrences of several rules into one ordered, de-duplicated sequence.
+pub struct MergedOccurrences {
+    iterators: Vec<std::iter::Peekable<RecurrenceIterator>>,
+    repeats: Option<usize>,
+    yielded: usize,
+}
+
+impl Iterator for MergedOccurrences {
+    type Item = OffsetDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.repeats.is_some_and(|repeats| self.yielded >= repeats) {
+            return None;
+        }
+
+        let next = self
+            .iterators
+            .iter_mut()
+            .filter_map(|iter| iter.peek().copied())
+            .min()?;
+
+        // Rules share the schedule's start time, so hits on the same day are the same instant.
+        for iter in &mut self.iterators {
+            while iter.next_if(|utc| *utc == next).is_some() {}
+        }
+
+        self.yielded += 1;
+
+        Some(next)
+    }
+}
+
+/// Parses a day of a recurrence rule, eg. `MONDAY`.
+pub fn parse_weekday(day: &str) -> Result<Weekday> {
+    let Some(pos) = WEEK_DAYS.iter().position(|v| v.eq_ignore_ascii_case(day)) else {
+        return Err(eyre::eyre!(
+            "Unknown day {day}. Expected one of {}",
+            WEEK_DAYS.join(", ")
+        ))?;
+    };
+
+    Ok(Weekday::Monday.nth_next(pos as u8))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    /// Weekly, every other week.
+    Biweekly,
+    Monthly,
+    Yearly,
+}
+
+impl FromStr for Frequency {
+    type Err = Error;
+
+    /// Case insensitive. Also accepts aliases such as `week` or `bi-weekly`.
+    fn from_str(frequency: &str) -> Result<Self> {
+        let normalized = frequency.trim().to_uppercase().replace(['-', '_', ' '], "");
+
+        Ok(match normalized.as_str() {
+            "DAILY" | "DAY" => Self::Daily,
+            "WEEKLY" | "WEEK" => Self::Weekly,
+            "BIWEEKLY" | "FORTNIGHTLY" => Self::Biweekly,
+            "MONTHLY" | "MONTH" => Self::Monthly,
+            "YEARLY" | "YEAR" | "ANNUALLY" => Self::Yearly,
+            _ => {
+                return Err(eyre::eyre!(
+                    "Invalid frequency: {frequency}. Expected one of {}",
+                    FREQUENCIES.join(", ")
+                ))?
+            }
+        })
+    }
+}
+
+/// Yields the UTC start of every occurrence of a recurring schedule, in order.
+///
+/// Works on local dates so an occurrence stays on the same local day and time.
+/// Periods are counted from the anchor's day, week (Monday to Sunday), month or year.
+pub struct RecurrenceIterator {
+    anchor: Date,
+    time: Time,
+    offset: UtcOffset,
+    frequency: Frequency,
+    /// Every how many periods the schedule runs. At least 1.
+    interval: u32,
+    /// Days of the week to run on. Empty means the anchor's day.
+    days: Vec<Weekday>,
+    repeats: Option<usize>,
+    end_day: Option<Date>,
+
+    /// Next period to expand.
+    period: u32,
+    /// Dates of the current period which haven't been yielded yet.
+    pending: VecDeque<Date>,
+    yielded: usize,
+}
+
+impl RecurrenceIterator {
+    pub fn new(
+        anchor: OffsetDateTime,
+        frequency: Frequency,
+        interval: usize,
+        mut days: Vec<Weekday>,
+        repeats: Option<usize>,
+        end_day: Option<Date>,
+    ) -> Self {
+        days.sort_by_key(|v| v.number_days_from_monday());
+        days.dedup();
+
+        Self {
+            anchor: anchor.date(),
+            time: anchor.time(),
+            offset: anchor.offset(),
+            frequency,
+            interval: interval.clamp(1, u32::MAX as usize) as u32,
+            days,
+            repeats: repeats.filter(|v| *v != 0),
+            end_day,
+            period: 0,
+            pending: VecDeque::new(),
+            yielded: 0,
+        }
+    }
+
+    /// First day of the nth period. `None` once past the supported date range.
+    fn period_start(&self, period: u32) -> Option<Date> {
+        let steps = i64::from(period) * i64::from(self.interval);
+
+        match self.frequency {
+            Frequency::Daily => self.anchor.checked_add(Duration::days(steps)),
+            Frequency::Weekly | Frequency::Biweekly => {
+                let weeks = if self.frequency == Frequency::Biweekly {
+                    steps * 2
+                } else {
+                    steps
+                };
+
+                let monday = self.anchor.checked_sub(Duration::days(
+                    self.anchor.weekday().number_days_from_monday() as i64,
+                ))?;
+
+                monday.checked_add(Duration::weeks(weeks))
+            }
+            Frequency::Monthly => {
+                let months = i64::from(self.anchor.year()) * 12
+                    + i64::from(self.anchor.month() as u8 - 1)
+                    + steps;
+
+                Date::from_calendar_date(
+                    i32::try_from(months.div_euclid(12)).ok()?,
+                    Month::try_from(months.rem_euclid(12) as u8 + 1).ok()?,
+                    1,
+                )
+                .ok()
+            }
+            Frequency::Yearly => Date::from_calendar_date(
+                i32::try_from(i64::from(self.anchor.year()) + steps).ok()?,
+                Month::January,
+                1,
+            )
+            .ok(),
+        }
+    }
+
+    /// Occurrences within the period starting at `start`, in order.
+    fn period_dates(&self, start: Date) -> VecDeque<Date> {
+        match self.frequency {
+            Frequency::Daily => {
+                if self.days.is_empty() || self.days.contains(&start.weekday()) {
+                    VecDeque::from([start])
+                } else {
+                    VecDeque::new()
+                }
+            }
+            Frequency::Weekly | Frequency::Biweekly => {
+                let days = if self.days.is_empty() {
+                    vec![self.anchor.weekday()]
+                } else {
+                    self.days.clone()
+                };
+
+                days.into_iter()
+                    .filter_map(|day| {
+                        start.checked_add(Duration::days(day.number_days_from_monday() as i64))
+                    })
+                    .filter(|date| *date >= self.anchor)
+                    .collect()
+            }
+            // Months without the anchor's day (eg. the 31st) are skipped.
+            Frequency::Monthly => {
+                Date::from_calendar_date(start.year(), start.month(), self.anchor.day())
+                    .into_iter()
+                    .collect()
+            }
+            // Non-leap years are skipped for an anchor on the 29th of February.
+            Frequency::Yearly => {
+                Date::from_calendar_date(start.year(), self.anchor.month(), self.anchor.day())
+                    .into_iter()
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Iterator for RecurrenceIterator {
+    type Item = OffsetDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.repeats.is_some_and(|repeats| self.yielded >= repeats) {
+                return None;
+            }
+
+            if let Some(date) = self.pending.pop_front() {
+                if self.end_day.is_some_and(|end_day| date > end_day) {
+                    return None;
+                }
+
+                self.yielded += 1;
+
+                return Some(
+                    date.with_time(self.time)
+                        .assume_offset(self.offset)
+                        .to_offset(UtcOffset::UTC),
+                );
+            }
+
+            let start = self.period_start(self.period)?;
+
+            if self.end_day.is_some_and(|end_day| start > end_day) {
+                return None;
+            }
+
+            self.pending = self.period_dates(start);
+            self.period = self.period.checked_add(1)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    fn dates(iter: impl Iterator<Item = OffsetDateTime>) -> Vec<Date> {
+        iter.map(|v| v.date()).collect()
+    }
+
+    #[test]
+    fn weekly_runs_on_each_day_from_the_anchor() {
+        // A Wednesday.
+        let anchor = datetime!(2025-03-12 9:00 +1);
+        let iter = RecurrenceIterator::new(
+            anchor,
+            Frequency::Weekly,
+            1,
+            vec![Weekday::Wednesday, Weekday::Monday],
+            Some(3),
+            None,
+        );
+
+        let occurrences = iter.collect::<Vec<_>>();
+
+        assert_eq!(
+            dates(occurrences.iter().copied()),
+            [
+                date!(2025 - 03 - 12),
+                date!(2025 - 03 - 17),
+                date!(2025 - 03 - 19)
+            ]
+        );
+        assert_eq!(occurrences[0], datetime!(2025-03-12 8:00 UTC));
+    }
+
+    #[test]
+    fn biweekly_skips_every_other_week() {
+        let iter = RecurrenceIterator::new(
+            datetime!(2025-03-12 9:00 UTC),
+            Frequency::Biweekly,
+            1,
+            Vec::new(),
+            None,
+            Some(date!(2025 - 04 - 09)),
+        );
+
+        assert_eq!(
+            dates(iter),
+            [
+                date!(2025 - 03 - 12),
+                date!(2025 - 03 - 26),
+                date!(2025 - 04 - 09)
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_skips_months_without_the_day() {
+        let iter = RecurrenceIterator::new(
+            datetime!(2025-01-31 9:00 UTC),
+            Frequency::Monthly,
+            1,
+            Vec::new(),
+            Some(3),
+            None,
+        );
+
+        assert_eq!(
+            dates(iter),
+            [
+                date!(2025 - 01 - 31),
+                date!(2025 - 03 - 31),
+                date!(2025 - 05 - 31)
+            ]
+        );
+    }
+
+    #[test]
+    fn interval_and_repeats_limit_the_occurrences() {
+        let iter = RecurrenceIterator::new(
+            datetime!(2025-03-12 9:00 UTC),
+            Frequency::Daily,
+            3,
+            Vec::new(),
+            Some(3),
+            None,
+        );
+
+        assert_eq!(
+            dates(iter),
+            [
+                date!(2025 - 03 - 12),
+                date!(2025 - 03 - 15),
+                date!(2025 - 03 - 18)
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_rules_yield_shared_days_once() {
+        let rule = |frequency: &str, days: &[&str]| RecurrenceRule {
+            days: days.iter().map(|v| v.to_string()).collect(),
+            frequency: frequency.to_string(),
+            interval: 1,
+        };
+
+        let rules = RecurrenceRules::Many(vec![
+            rule("WEEKLY", &["MONDAY", "WEDNESDAY"]),
+            rule("daily", &["WEDNESDAY"]),
+        ]);
+
+        let iter = rules
+            .occurrences(datetime!(2025-03-10 9:00 UTC), Some(3), None)
+            .unwrap();
+
+        assert_eq!(
+            dates(iter),
+            [
+                date!(2025 - 03 - 10),
+                date!(2025 - 03 - 12),
+                date!(2025 - 03 - 17)
+            ]
+        );
+    }
+
+    #[test]
+    fn a_single_rule_or_a_list_is_accepted() {
+        let one: RecurrenceRules =
+            serde_json::from_str(r#"{ "days": ["MONDAY"], "frequency": "WEEKLY", "interval": 1 }"#)
+                .unwrap();
+        let many: RecurrenceRules = serde_json::from_str(
+            r#"[
+                { "days": ["MONDAY"], "frequency": "WEEKLY", "interval": 1 },
+                { "days": [], "frequency": "MONTHLY", "interval": 1 }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(one.rules().len(), 1);
+        assert_eq!(many.rules().len(), 2);
+    }
+
+    #[test]
+    fn frequencies_accept_aliases() {
+        assert_eq!(
+            "bi-weekly".parse::<Frequency>().unwrap(),
+            Frequency::Biweekly
+        );
+        assert_eq!("Annually".parse::<Frequency>().unwrap(), Frequency::Yearly);
+        assert_eq!(" week ".parse::<Frequency>().unwrap(), Frequency::Weekly);
+        assert_eq!("daily".parse::<Frequency>().unwrap(), Frequency::Daily);
+        assert!("hourly".parse::<Frequency>().is_err());
+        assert!(parse_weekday("FUNDAY").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use std::{error::Error as StdError, fmt::Display, future::Future, io::ErrorKind};
+
+use reqwest::StatusCode;
+
+use crate::config::Config;
+
+/// Statuses the CMS (or a proxy in front of it) responds with while it's briefly unavailable.
+const TRANSIENT_STATUSES: [StatusCode; 4] = [
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// Why a CMS request failed, found from the typed errors behind it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// The request never reached the CMS, so nothing was written.
+    Unsent,
+    /// Worth retrying for requests without side effects, eg. a timeout or a 503.
+    Transient,
+    Permanent,
+}
+
+impl Failure {
+    /// Walks the error's causes for the `reqwest` or IO error behind it.
+    fn of(error: &(dyn StdError + 'static)) -> Self {
+        let mut cause = Some(error);
+
+        while let Some(error) = cause {
+            if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+                if error.is_connect() {
+                    return Self::Unsent;
+                }
+
+                if error.is_timeout() {
+                    return Self::Transient;
+                }
+
+                if let Some(status) = error.status() {
+                    return if TRANSIENT_STATUSES.contains(&status) {
+                        Self::Transient
+                    } else {
+                        Self::Permanent
+                    };
+                }
+            }
+
+            if let Some(error) = error.downcast_ref::<std::io::Error>() {
+                match error.kind() {
+                    ErrorKind::ConnectionRefused => return Self::Unsent,
+                    ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::TimedOut
+                    | ErrorKind::UnexpectedEof => return Self::Transient,
+                    _ => {}
+                }
+            }
+
+            cause = error.source();
+        }
+
+        Self::Permanent
+    }
+}
+
+/// Runs a request without side effects (eg. a query), retrying transient failures.
+pub async fn read<T, E, F, Fut>(config: &Config, request: F) -> Result<T, E>
+where
+    E: Display + AsRef<dyn StdError + Send + Sync + 'static>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    run(config, request, |failure| failure != Failure::Permanent).await
+}
+
+/// Runs a request which writes to the CMS.
+///
+/// Only retried when the request never reached the CMS. A timeout could mean the row was
+/// written, and retrying it could double book.
+pub async fn write<T, E, F, Fut>(config: &Config, request: F) -> Result<T, E>
+where
+    E: Display + AsRef<dyn StdError + Send + Sync + 'static>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    run(config, request, |failure| failure == Failure::Unsent).await
+}
+
+async fn run<T, E, F, Fut>(
+    config: &Config,
+    mut request: F,
+    is_retryable: impl Fn(Failure) -> bool,
+) -> Result<T, E>
+where
+    E: Display + AsRef<dyn StdError + Send + Sync + 'static>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let attempts = config.cms_retry_attempts;
+    let mut backoff = config.cms_retry_backoff;
+    let mut attempt = 1;
+
+    loop {
+        match request().await {
+            Ok(v) => return Ok(v),
+            Err(e) => {
+                if attempt >= attempts || !is_retryable(Failure::of(e.as_ref())) {
+                    return Err(e);
+                }
+
+                warn!("CMS request failed (attempt {attempt}/{attempts}): {e}");
+
+                tokio::time::sleep(backoff).await;
+
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{routing::get, Router};
+
+    use super::*;
+
+    fn config() -> Config {
+        Config::from_lookup(|key| match key {
+            "BOOKING_CMS_RETRY_ATTEMPTS" => Some(String::from("3")),
+            "BOOKING_CMS_RETRY_BACKOFF_MS" => Some(String::from("1")),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    /// Serves `/` on a random local port, responding with `statuses` in turn and then 200.
+    /// Returns the URL and how many requests were made.
+    async fn mock_cms(statuses: &'static [u16]) -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+
+        let app = Router::new().route(
+            "/",
+            get(move || async move {
+                let index = counted.fetch_add(1, Ordering::SeqCst);
+
+                axum::http::StatusCode::from_u16(statuses.get(index).copied().unwrap_or(200))
+                    .unwrap()
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{addr}/"), requests)
+    }
+
+    async fn fetch(url: &str) -> eyre::Result<()> {
+        reqwest::get(url).await?.error_for_status()?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_read_is_retried_after_a_transient_failure() {
+        let (url, requests) = mock_cms(&[503]).await;
+
+        read(&config(), || fetch(&url)).await.unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn a_read_isnt_retried_after_a_client_error() {
+        let (url, requests) = mock_cms(&[404]).await;
+
+        assert!(read(&config(), || fetch(&url)).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn a_write_which_reached_the_cms_isnt_retried() {
+        let (url, requests) = mock_cms(&[503]).await;
+
+        assert!(write(&config(), || fetch(&url)).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn a_refused_connection_is_unsent() {
+        // Bound then dropped, so nothing listens on the port.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let error = fetch(&format!("http://{addr}/")).await.unwrap_err();
+
+        assert_eq!(Failure::of(error.as_ref()), Failure::Unsent);
+    }
+
+    #[test]
+    fn error_text_alone_isnt_retried() {
+        let error = eyre::eyre!("503 Service Unavailable");
+
+        assert_eq!(Failure::of(error.as_ref()), Failure::Permanent);
+    }
+}
@@ -0,0 +1,572 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use webby_addon_common::{
+    request::{get_cms_row_by_id, query_cms_rows},
+    JsonResponse, WrappingResponse,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use webby_global_common::{
+    request::CmsQuery,
+    uuid::{CollectionName, UuidType},
+};
+use time::{
+    format_description::well_known::Iso8601, macros::format_description, Date, Duration,
+    OffsetDateTime, PrimitiveDateTime,
+};
+
+use crate::{
+    apply_staff_time_zone, book_date_filters, book_days_filters, gather_available_hours, metrics,
+    retry, row_id, row_text, schedule_repeats, staff_schedule_occurrences, staff_time_zones,
+    validate_duration_option, AppState, FoundHour, Occupied, Result,
+};
+
+/// Most days a single search scans.
+const MAX_SEARCH_DAYS: i64 = 31;
+/// Slots returned when no `limit` is given.
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+/// Most slots a single search returns.
+const MAX_SEARCH_LIMIT: usize = 50;
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    service_id: String,
+    /// First day to search (YYYY-MM-DD), in each staff member's time zone.
+    from: String,
+    /// Last day to search (YYYY-MM-DD). At most `MAX_SEARCH_DAYS` days after `from`.
+    to: String,
+    limit: Option<usize>,
+}
+
+/// The first & last day of a search and the days between them, inclusive.
+fn search_range(from: &str, to: &str) -> Result<(Date, Date, i64)> {
+    let date_format = format_description!("[year]-[month]-[day]");
+
+    let from = Date::parse(from, &date_format)?;
+    let to = Date::parse(to, &date_format)?;
+
+    if from > to {
+        return Err(eyre::eyre!(
+            "From date {from} must not be after to date {to}"
+        ))?;
+    }
+
+    let days = (to - from).whole_days() + 1;
+
+    if days > MAX_SEARCH_DAYS {
+        return Err(eyre::eyre!(
+            "Can't search more than {MAX_SEARCH_DAYS} days at once"
+        ))?;
+    }
+
+    Ok((from, to, days))
+}
+
+/// The first open slots of a service within a date range, eg. "when are you free next week?".
+///
+/// Days are scanned in order and the scan stops once `limit` slots are found.
+pub async fn get_search(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(query): Query<SearchQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    metrics::inc(&metrics::AVAILABILITY_REQUESTS);
+
+    let (from, to, days) = search_range(&query.from, &query.to)?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            &query.service_id,
+        )
+    })
+    .await?;
+
+    let form_id = row_text(&service, "formId")?;
+
+    let schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let schedule_repeats = schedule_repeats(&schedule_resp.items)?;
+
+    let mut service_schedules = HashMap::new();
+
+    for row in &schedule_resp.items {
+        if row_text(row, "service")?.as_deref() == Some(query.service_id.as_str()) {
+            service_schedules.insert(row_id(row)?, row);
+        }
+    }
+
+    let mut staff_schedule_items = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?
+    .items;
+
+    let staff_time_zones = staff_time_zones(&state, uuid).await?;
+
+    for item in &mut staff_schedule_items {
+        apply_staff_time_zone(item, &staff_time_zones)?;
+    }
+
+    // Staff schedules of the service with the local days they run on in the range.
+    let mut candidates = Vec::new();
+
+    for staff_schedule in &staff_schedule_items {
+        let Some(schedule) = row_text(staff_schedule, "schedule")?
+            .and_then(|id| service_schedules.get(&id).copied())
+        else {
+            continue;
+        };
+
+        // Occurrences are in UTC, so a day either side covers every local day of the range.
+        let lookup_time = from.midnight().assume_utc() - Duration::days(1);
+        let lookup_end = lookup_time + Duration::days(days + 2);
+
+        let occurrences =
+            staff_schedule_occurrences(staff_schedule, lookup_time, lookup_end, &schedule_repeats)?;
+
+        let run_days = occurrences
+            .starts
+            .iter()
+            .map(|utc| utc.to_offset(occurrences.local_offset).date())
+            .filter(|day| (from..=to).contains(day))
+            .collect::<BTreeSet<_>>();
+
+        if run_days.is_empty() {
+            continue;
+        }
+
+        let filters = book_days_filters(from, days, occurrences.local_offset)?;
+
+        let bookings = retry::read(&state.config, || {
+            query_cms_rows(
+                uuid,
+                CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                CmsQuery {
+                    filters: Some(filters.clone()),
+                    ..CmsQuery::default()
+                },
+            )
+        })
+        .await?;
+
+        let occupied =
+            Occupied::resolve(&state, uuid, staff_schedule, from, days, bookings.items).await?;
+
+        candidates.push((
+            staff_schedule,
+            schedule,
+            occurrences.time_zone_str,
+            run_days,
+            occupied,
+        ));
+    }
+
+    let now = OffsetDateTime::now_utc();
+
+    let mut slots = Vec::new();
+    let mut scanned_to = None;
+
+    for day in (0..days).filter_map(|v| from.checked_add(Duration::days(v))) {
+        let mut day_slots = Vec::new();
+
+        for (staff_schedule, schedule, time_zone_str, run_days, occupied) in &candidates {
+            if !run_days.contains(&day) {
+                continue;
+            }
+
+            // Real offsets so slots of staff in different time zones sort correctly.
+            let found_hours = gather_available_hours(
+                day.midnight(),
+                &service,
+                schedule,
+                staff_schedule,
+                occupied,
+                None,
+                true,
+            )?;
+
+            day_slots.extend(
+                found_hours
+                    .into_iter()
+                    .filter(|v| !v.is_booked && v.start > now)
+                    .map(|v| (v, time_zone_str)),
+            );
+        }
+
+        day_slots.sort_by(|(a, _), (b, _)| a.cmp_chronological(b));
+        slots.extend(day_slots);
+        scanned_to = Some(day);
+
+        if slots.len() >= limit {
+            break;
+        }
+    }
+
+    let items = slots
+        .into_iter()
+        .take(limit)
+        .map(|(v, time_zone_str)| {
+            Ok(serde_json::json!({
+                "start": v.start.format(&Iso8601::DEFAULT)?,
+                "end": v.end.format(&Iso8601::DEFAULT)?,
+                "timeZone": time_zone_str,
+                "dstWarning": v.dst_warning,
+                "serviceId": v.service_id,
+                "scheduleId": v.schedule_id,
+                "staffId": v.staff_id,
+                "staffScheduleId": v.staff_schedule_id,
+                "slotId": v.slot_id().to_string(),
+                "formId": form_id,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "serviceId": query.service_id,
+        "from": from,
+        "to": to,
+        // Last day looked at. Before `to` when the limit was hit early.
+        "scannedTo": scanned_to,
+        "items": items,
+    }))))
+}
+
+/// Open slots of a service on a day across every staff member, for when the customer picks a
+/// time and the staff member is assigned afterwards.
+///
+/// Slots are grouped by their start & end with the staff who can serve each. Without
+/// `raw_offsets` they're grouped by local wall clock time.
+pub async fn service_available_hours(
+    state: &AppState,
+    uuid: UuidType,
+    service_id: &str,
+    list_date: PrimitiveDateTime,
+    (window_start, window_end): (PrimitiveDateTime, PrimitiveDateTime),
+    duration_minutes: Option<f64>,
+    raw_offsets: bool,
+) -> Result<serde_json::Value> {
+    let day = list_date.date();
+
+    let service = retry::read(&state.config, || {
+        get_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            service_id,
+        )
+    })
+    .await?;
+
+    validate_duration_option(&service, duration_minutes)?;
+
+    let form_id = row_text(&service, "formId")?;
+
+    let schedule_resp = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let schedule_repeats = schedule_repeats(&schedule_resp.items)?;
+
+    let mut service_schedules = HashMap::new();
+
+    for row in &schedule_resp.items {
+        if row_text(row, "service")?.as_deref() == Some(service_id) {
+            service_schedules.insert(row_id(row)?, row);
+        }
+    }
+
+    let mut staff_schedule_items = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?
+    .items;
+
+    let staff_time_zones = staff_time_zones(state, uuid).await?;
+
+    for item in &mut staff_schedule_items {
+        apply_staff_time_zone(item, &staff_time_zones)?;
+    }
+
+    // Staff who can serve each slot, keyed by the slot's start & end.
+    let mut slots = BTreeMap::<_, Vec<_>>::new();
+
+    for staff_schedule in &staff_schedule_items {
+        let Some(schedule) = row_text(staff_schedule, "schedule")?
+            .and_then(|id| service_schedules.get(&id).copied())
+        else {
+            continue;
+        };
+
+        // Occurrences are in UTC, so a day either side covers the local day.
+        let lookup_time = day.midnight().assume_utc() - Duration::days(1);
+        let lookup_end = lookup_time + Duration::days(3);
+
+        let occurrences =
+            staff_schedule_occurrences(staff_schedule, lookup_time, lookup_end, &schedule_repeats)?;
+
+        if !occurrences
+            .starts
+            .iter()
+            .any(|utc| utc.to_offset(occurrences.local_offset).date() == day)
+        {
+            continue;
+        }
+
+        let filters = book_date_filters(day, occurrences.local_offset)?;
+
+        let bookings = retry::read(&state.config, || {
+            query_cms_rows(
+                uuid,
+                CollectionName {
+                    id: String::from("bookings"),
+                    ns: Some(String::from("@booking")),
+                },
+                CmsQuery {
+                    filters: Some(filters.clone()),
+                    ..CmsQuery::default()
+                },
+            )
+        })
+        .await?;
+
+        let occupied =
+            Occupied::resolve(state, uuid, staff_schedule, day, 1, bookings.items).await?;
+
+        let found_hours = gather_available_hours(
+            list_date,
+            &service,
+            schedule,
+            staff_schedule,
+            &occupied,
+            duration_minutes,
+            raw_offsets,
+        )?;
+
+        add_open_slots(
+            &mut slots,
+            found_hours,
+            (window_start, window_end),
+            &occurrences.time_zone_str,
+        );
+    }
+
+    Ok(serde_json::json!({
+        "serviceId": service_id,
+        "formId": form_id,
+        "available": group_slots(slots)?,
+    }))
+}
+
+/// Adds a staff member's open slots within the window to the staff who can serve each slot.
+fn add_open_slots(
+    slots: &mut BTreeMap<(OffsetDateTime, OffsetDateTime), Vec<serde_json::Value>>,
+    found_hours: Vec<FoundHour>,
+    (window_start, window_end): (PrimitiveDateTime, PrimitiveDateTime),
+    time_zone: &str,
+) {
+    // `start` & `end` hold the local wall clock time in both offset modes.
+    for v in found_hours.into_iter().filter(|v| {
+        !v.is_booked
+            && PrimitiveDateTime::new(v.start.date(), v.start.time()) >= window_start
+            && PrimitiveDateTime::new(v.end.date(), v.end.time()) <= window_end
+    }) {
+        slots
+            .entry((v.start, v.end))
+            .or_default()
+            .push(serde_json::json!({
+                "staffId": v.staff_id,
+                "staffScheduleId": v.staff_schedule_id,
+                "scheduleId": v.schedule_id,
+                "slotId": v.slot_id().to_string(),
+                "timeZone": time_zone,
+                "dstWarning": v.dst_warning,
+            }));
+    }
+}
+
+/// The slots by time, each with its staff in a stable order.
+fn group_slots(
+    slots: BTreeMap<(OffsetDateTime, OffsetDateTime), Vec<serde_json::Value>>,
+) -> Result<Vec<serde_json::Value>> {
+    slots
+        .into_iter()
+        .map(|((start, end), mut staff)| {
+            staff.sort_by(|a, b| {
+                (a["staffId"].as_str(), a["staffScheduleId"].as_str())
+                    .cmp(&(b["staffId"].as_str(), b["staffScheduleId"].as_str()))
+            });
+
+            Ok(serde_json::json!({
+                "start": start.format(&Iso8601::DEFAULT)?,
+                "end": end.format(&Iso8601::DEFAULT)?,
+                "staff": staff,
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use super::*;
+    use crate::{tests::test_row, SimpleValue};
+
+    /// Open slots on 2025-03-14 of `staff` working `start` to `end` UTC in 60 minute slots.
+    fn open_slots(staff: &str, start: &str, end: &str) -> Vec<FoundHour> {
+        gather_available_hours(
+            datetime!(2025-03-14 00:00),
+            &test_row("service", HashMap::<String, SimpleValue>::new()),
+            &test_row("schedule", [("duration", serde_json::json!(60).into())]),
+            &test_row(
+                staff,
+                [
+                    ("staff", staff.into()),
+                    ("timeZone", "UTC".into()),
+                    ("start", start.into()),
+                    ("end", end.into()),
+                ],
+            ),
+            &Occupied {
+                bookings: Vec::new(),
+                busy: Vec::new(),
+            },
+            None,
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn overlapping_staff_share_a_slot() {
+        let mut slots = BTreeMap::new();
+        let window = (datetime!(2025-03-14 00:00), datetime!(2025-03-15 00:00));
+
+        // Added out of order, they're still listed by staff id.
+        add_open_slots(
+            &mut slots,
+            open_slots("ben", "10:00:00", "13:00:00"),
+            window,
+            "UTC",
+        );
+        add_open_slots(
+            &mut slots,
+            open_slots("ana", "09:00:00", "12:00:00"),
+            window,
+            "UTC",
+        );
+
+        let available = group_slots(slots).unwrap();
+
+        let staff = available
+            .iter()
+            .map(|slot| {
+                let ids = slot["staff"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|v| v["staffId"].as_str().unwrap())
+                    .collect::<Vec<_>>();
+
+                (slot["start"].as_str().unwrap(), ids)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            staff,
+            [
+                ("2025-03-14T09:00:00.000000000Z", vec!["ana"]),
+                ("2025-03-14T10:00:00.000000000Z", vec!["ana", "ben"]),
+                ("2025-03-14T11:00:00.000000000Z", vec!["ana", "ben"]),
+                ("2025-03-14T12:00:00.000000000Z", vec!["ben"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_slots_inside_the_window_are_offered() {
+        let mut slots = BTreeMap::new();
+
+        add_open_slots(
+            &mut slots,
+            open_slots("ana", "09:00:00", "12:00:00"),
+            (datetime!(2025-03-14 10:00), datetime!(2025-03-14 11:30)),
+            "UTC",
+        );
+
+        assert_eq!(slots.len(), 1);
+        assert!(slots.contains_key(&(
+            datetime!(2025-03-14 10:00 UTC),
+            datetime!(2025-03-14 11:00 UTC)
+        )));
+    }
+
+    #[test]
+    fn a_search_covers_both_ends_of_its_range() {
+        assert_eq!(
+            search_range("2025-03-14", "2025-03-14").unwrap(),
+            (date!(2025 - 03 - 14), date!(2025 - 03 - 14), 1)
+        );
+        assert_eq!(
+            search_range("2025-03-01", "2025-03-31").unwrap().2,
+            MAX_SEARCH_DAYS
+        );
+    }
+
+    #[test]
+    fn a_backwards_or_too_long_range_is_rejected() {
+        assert!(search_range("2025-03-15", "2025-03-14").is_err());
+        assert!(search_range("2025-03-01", "2025-04-01").is_err());
+        assert!(search_range("2025-03-01", "next week").is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::{HashMap, HashSet};
+
+use webby_addon_common::{
+    request::{get_cms_row_by_id, query_cms_rows, update_cms_row_by_id},
+    JsonResponse, WrappingResponse,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use webby_global_common::{
+    filter::{Filter, FilterConditionType, FilterValue},
+    request::CmsQuery,
+    schema::SchematicFieldKey,
+    uuid::{CollectionName, UuidType},
+    value::SimpleValue,
+};
+
+use crate::{
+    bookability, page_bounds, retry, row_id, row_number, row_text, row_image_url, service_currency,
+    service_duration_options, service_max_participants, service_min_participants,
+    validate_currency, AppState, Error, Result,
+};
+
+#[derive(serde::Deserialize)]
+pub struct ServicesQuery {
+    /// Only return services of this type (eg. `appointment`)
+    #[serde(rename = "type")]
+    type_of: Option<String>,
+    /// Only return services in this category (eg. `Hair`)
+    category: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+/// Lists every bookable service for the widget's landing page.
+pub async fn get_services(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Query(query): Query<ServicesQuery>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let (offset, limit) = page_bounds(query.offset, query.limit);
+
+    let services = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("services"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery {
+                filters: service_filters(query.type_of.clone(), query.category.clone()),
+                // Hydrates `image` so its URL can be returned.
+                include_files: true,
+                ..CmsQuery::default()
+            },
+        )
+    })
+    .await?;
+
+    let schedules = retry::read(&state.config, || {
+        query_cms_rows(
+            uuid,
+            CollectionName {
+                id: String::from("schedule"),
+                ns: Some(String::from("@booking")),
+            },
+            CmsQuery::default(),
+        )
+    })
+    .await?;
+
+    let scheduled_services = schedules
+        .items
+        .iter()
+        .map(|row| row_text(row, "service"))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<HashSet<_>>();
+
+    let bookable_services = bookability(&state, uuid).await?.services;
+
+    let total = services.items.len();
+
+    let items = services
+        .items
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|row| {
+            let id = row_id(row)?;
+
+            Ok(serde_json::json!({
+                "hasSchedule": scheduled_services.contains(&id),
+                "bookable": bookable_services.contains(&id),
+                "id": id,
+                "name": row_text(row, "name")?,
+                "description": row_text(row, "description")?,
+                "type": row_text(row, "type")?,
+                "category": row_text(row, "category")?,
+                "priceAmount": row_number(row, "priceAmount")?,
+                "depositAmount": row_number(row, "depositAmount")?,
+                "durationOptions": service_duration_options(row)?,
+                "currency": service_currency(&state.config, row)?,
+                "paymentType": row_text(row, "paymentType")?,
+                "maxParticipants": row_number(row, "maxParticipants")?,
+                "minParticipants": service_min_participants(row)?,
+                "cancelCutoffHours": row_number(row, "cancelCutoffHours")?,
+                "maxPerContactPerDay": row_number(row, "maxPerContactPerDay")?,
+                "image": row.fields.get(&SchematicFieldKey::Other(String::from("image"))),
+                "imageUrl": row_image_url(&state.config, row, "image")?,
+                "formId": row_text(row, "formId")?,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "total": total,
+        "offset": offset,
+        "limit": limit,
+        "items": items,
+    }))))
+}
+
+/// Equality filters on the services collection for each of the given values.
+pub fn service_filters(type_of: Option<String>, category: Option<String>) -> Option<Vec<Filter>> {
+    let filters = [("type", type_of), ("category", category)]
+        .into_iter()
+        .filter_map(|(name, value)| {
+            Some(Filter {
+                name: name.to_string(),
+                cond: FilterConditionType::Eq,
+                value: FilterValue::Text(value?),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    (!filters.is_empty()).then_some(filters)
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceUpdateJson {
+    price_amount: Option<f64>,
+    /// Charged when booking online instead of the full price.
+    deposit_amount: Option<f64>,
+    currency: Option<String>,
+    /// Lengths in minutes the customer can choose between. Empty to use the schedule's duration.
+    duration_options: Option<Vec<f64>>,
+    /// Hours before the start in which a booking can no longer be cancelled. `0` turns it off.
+    cancel_cutoff_hours: Option<f64>,
+    /// Bookings a contact can make of the service on a single day. `0` for unlimited.
+    max_per_contact_per_day: Option<f64>,
+    /// Participants a group session needs to run. Sessions with fewer are tentative.
+    min_participants: Option<f64>,
+}
+
+/// Updates the price of a service. Fields which aren't sent are left unchanged.
+pub async fn put_service(
+    State(state): State<AppState>,
+    Path((uuid, id)): Path<(UuidType, String)>,
+    Json(body): Json<ServiceUpdateJson>,
+) -> Result<()> {
+    let mut fields: HashMap<String, SimpleValue> = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (name, value) in [
+        ("priceAmount", body.price_amount),
+        ("depositAmount", body.deposit_amount),
+        ("cancelCutoffHours", body.cancel_cutoff_hours),
+        ("maxPerContactPerDay", body.max_per_contact_per_day),
+    ] {
+        if let Some(value) = value {
+            if !value.is_finite() || value < 0.0 {
+                errors.push(format!("Invalid {name}: {value}. Must be 0 or more"));
+            } else {
+                fields.insert(String::from(name), serde_json::json!(value).into());
+            }
+        }
+    }
+
+    if let Some(min_participants) = body.min_participants {
+        if min_participants < 1.0 || min_participants.fract() != 0.0 {
+            errors.push(format!(
+                "Invalid minParticipants: {min_participants}. Must be a whole number of 1 or more"
+            ));
+        }
+    }
+
+    if let Some(currency) = body.currency {
+        match validate_currency(&currency) {
+            Ok(()) => {
+                fields.insert(String::from("currency"), currency.into());
+            }
+            Err(e) => errors.push(e.validation_message()),
+        }
+    }
+
+    if let Some(duration_options) = body.duration_options {
+        if let Some(value) = duration_options
+            .iter()
+            .find(|v| !v.is_finite() || **v <= 0.0)
+        {
+            errors.push(format!(
+                "Invalid durationOptions: {value}. Must be more than 0"
+            ));
+        }
+
+        fields.insert(
+            String::from("durationOptions"),
+            serde_json::json!(duration_options).into(),
+        );
+    }
+
+    // Nothing is written unless every input is valid.
+    if !errors.is_empty() {
+        return Err(Error::Validation(errors));
+    }
+
+    // The rest are checked against the values already stored.
+    if body.price_amount.is_some()
+        || body.deposit_amount.is_some()
+        || body.min_participants.is_some()
+    {
+        let service = retry::read(&state.config, || {
+            get_cms_row_by_id(
+                uuid,
+                CollectionName {
+                    id: String::from("services"),
+                    ns: Some(String::from("@booking")),
+                },
+                &id,
+            )
+        })
+        .await?;
+
+        let price_amount = match body.price_amount {
+            Some(v) => v,
+            None => row_number(&service, "priceAmount")?.unwrap_or_default(),
+        };
+
+        let deposit_amount = match body.deposit_amount {
+            Some(v) => v,
+            None => row_number(&service, "depositAmount")?.unwrap_or_default(),
+        };
+
+        if deposit_amount > price_amount {
+            errors.push(format!(
+                "depositAmount {deposit_amount} must not be more than priceAmount {price_amount}"
+            ));
+        }
+
+        if let Some(min_participants) = body.min_participants {
+            let max_participants = service_max_participants(&service)?;
+
+            if min_participants as usize > max_participants {
+                errors.push(format!(
+                    "minParticipants {min_participants} must not be more than maxParticipants {max_participants}"
+                ));
+            }
+
+            fields.insert(
+                String::from("minParticipants"),
+                serde_json::json!(min_participants).into(),
+            );
+        }
+
+        if !errors.is_empty() {
+            return Err(Error::Validation(errors));
+        }
+    }
+
+    update_row(&state, uuid, "services", &id, fields).await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleUpdateJson {
+    /// Length of a slot in minutes.
+    duration: Option<f64>,
+    /// Minutes between slots.
+    #[serde(rename = "break")]
+    break_minutes: Option<f64>,
+    /// Minutes between slot starts. `0` places slots back-to-back.
+    slot_interval: Option<f64>,
+    /// Minutes the first slot (and the first after a break) is rounded up to, eg. `15`. `0` turns it off.
+    round_to_minutes: Option<f64>,
+    /// Bookings the staff member serves at once, eg. `2` for two chairs.
+    concurrency: Option<f64>,
+}
+
+/// Updates the slot length of a schedule. Fields which aren't sent are left unchanged.
+pub async fn put_schedule(
+    State(state): State<AppState>,
+    Path((uuid, id)): Path<(UuidType, String)>,
+    Json(body): Json<ScheduleUpdateJson>,
+) -> Result<()> {
+    let mut fields: HashMap<String, SimpleValue> = HashMap::new();
+    let mut errors = Vec::new();
+
+    if let Some(duration) = body.duration {
+        if !duration.is_finite() || duration <= 0.0 {
+            errors.push(format!("Invalid duration: {duration}. Must be more than 0"));
+        } else {
+            fields.insert(String::from("duration"), serde_json::json!(duration).into());
+        }
+    }
+
+    if let Some(concurrency) = body.concurrency {
+        if concurrency.fract() != 0.0 || concurrency < 1.0 {
+            errors.push(format!(
+                "Invalid concurrency: {concurrency}. Must be a whole number of at least 1"
+            ));
+        } else {
+            fields.insert(
+                String::from("concurrency"),
+                serde_json::json!(concurrency).into(),
+            );
+        }
+    }
+
+    for (name, value) in [
+        ("break", body.break_minutes),
+        ("slotInterval", body.slot_interval),
+        ("roundToMinutes", body.round_to_minutes),
+    ] {
+        if let Some(value) = value {
+            if !value.is_finite() || value < 0.0 {
+                errors.push(format!("Invalid {name}: {value}. Must be 0 or more"));
+            } else {
+                fields.insert(String::from(name), serde_json::json!(value).into());
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(Error::Validation(errors));
+    }
+
+    update_row(&state, uuid, "schedule", &id, fields).await
+}
+
+async fn update_row(
+    state: &AppState,
+    uuid: UuidType,
+    collection: &str,
+    id: &str,
+    fields: HashMap<String, SimpleValue>,
+) -> Result<()> {
+    if fields.is_empty() {
+        return Err(Error::Validation(vec![String::from("Nothing to update")]));
+    }
+
+    retry::write(&state.config, || {
+        update_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: collection.to_string(),
+                ns: Some(String::from("@booking")),
+            },
+            id,
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    // Availability is computed from these values.
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, routing::put, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::Config;
+
+    /// Responds with the status & `errors` of a PUT of `body` to `uri`.
+    async fn put_json(uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let state = AppState::new(Config::from_lookup(|_| None).unwrap()).unwrap();
+
+        let response = Router::new()
+            .route("/:uuid/service/:id", put(put_service))
+            .route("/:uuid/schedule/:id", put(put_schedule))
+            .with_state(state)
+            .oneshot(
+                axum::http::Request::builder()
+                    .method("PUT")
+                    .uri(format!("/01938f4f-f50c-7203-9f89-b367e9d49efb/{uri}"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (
+            status,
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()["errors"].take(),
+        )
+    }
+
+    /// The `(name, value)` of each equality filter.
+    fn eq_filters(filters: Option<Vec<Filter>>) -> Vec<(String, String)> {
+        filters
+            .unwrap_or_default()
+            .into_iter()
+            .map(|filter| {
+                assert!(matches!(filter.cond, FilterConditionType::Eq));
+
+                match filter.value {
+                    FilterValue::Text(value) => (filter.name, value),
+                    _ => panic!("{} isn't filtered by text", filter.name),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn services_are_only_filtered_by_type_when_asked() {
+        assert!(service_filters(None, None).is_none());
+        assert_eq!(
+            eq_filters(service_filters(Some(String::from("appointment")), None)),
+            [(String::from("type"), String::from("appointment"))]
+        );
+    }
+
+    #[test]
+    fn services_can_be_filtered_by_category_and_type() {
+        assert_eq!(
+            eq_filters(service_filters(None, Some(String::from("Hair")))),
+            [(String::from("category"), String::from("Hair"))]
+        );
+        assert_eq!(
+            eq_filters(service_filters(
+                Some(String::from("appointment")),
+                Some(String::from("Hair"))
+            )),
+            [
+                (String::from("type"), String::from("appointment")),
+                (String::from("category"), String::from("Hair")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn every_invalid_service_field_is_reported() {
+        let (status, errors) = put_json(
+            "service/haircut",
+            serde_json::json!({
+                "priceAmount": -1,
+                "currency": "usd",
+                "minParticipants": 0.5,
+                "durationOptions": [30, 0],
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(errors.as_array().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn every_invalid_schedule_field_is_reported() {
+        let (status, errors) = put_json(
+            "schedule/weekdays",
+            serde_json::json!({
+                "duration": 0,
+                "concurrency": 1.5,
+                "break": -5,
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            errors,
+            serde_json::json!([
+                "Invalid duration: 0. Must be more than 0",
+                "Invalid concurrency: 1.5. Must be a whole number of at least 1",
+                "Invalid break: -5. Must be 0 or more",
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn an_empty_update_is_a_bad_request() {
+        let (status, errors) = put_json("schedule/weekdays", serde_json::json!({})).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(errors, serde_json::json!(["Nothing to update"]));
+    }
+}
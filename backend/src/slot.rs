@@ -0,0 +1,128 @@
+use std::{fmt, str::FromStr};
+
+use time::Time;
+
+use crate::{Error, Result};
+
+/// Opaque identifier of a single bookable slot.
+///
+/// Returned by `availableHours` and echoed back by the client to book the slot.
+/// Encoded as `{staffScheduleId}.{scheduleId}.{serviceId}.{staffId}.{YYYYMMDDhhmmss}`
+/// where the date and time are in the schedule's local time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotId {
+    pub staff_schedule_id: String,
+    pub schedule_id: String,
+    pub service_id: String,
+    pub staff_id: String,
+
+    pub day: u8,
+    pub month: u8,
+    pub year: usize,
+    pub time: Time,
+}
+
+impl fmt::Display for SlotId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}.{:04}{:02}{:02}{:02}{:02}{:02}",
+            self.staff_schedule_id,
+            self.schedule_id,
+            self.service_id,
+            self.staff_id,
+            self.year,
+            self.month,
+            self.day,
+            self.time.hour(),
+            self.time.minute(),
+            self.time.second(),
+        )
+    }
+}
+
+impl FromStr for SlotId {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || eyre::eyre!("Invalid slotId: {value}");
+
+        let mut parts = value.split('.');
+
+        let (
+            Some(staff_schedule_id),
+            Some(schedule_id),
+            Some(service_id),
+            Some(staff_id),
+            Some(date_time),
+            None,
+        ) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        )
+        else {
+            return Err(invalid())?;
+        };
+
+        if date_time.len() != 14 || !date_time.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid())?;
+        }
+
+        Ok(Self {
+            staff_schedule_id: staff_schedule_id.to_string(),
+            schedule_id: schedule_id.to_string(),
+            service_id: service_id.to_string(),
+            staff_id: staff_id.to_string(),
+
+            year: date_time[0..4].parse()?,
+            month: date_time[4..6].parse()?,
+            day: date_time[6..8].parse()?,
+            time: Time::from_hms(
+                date_time[8..10].parse()?,
+                date_time[10..12].parse()?,
+                date_time[12..14].parse()?,
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::time;
+
+    use super::*;
+
+    #[test]
+    fn round_trips_through_its_string() {
+        let slot = SlotId {
+            staff_schedule_id: String::from("a"),
+            schedule_id: String::from("b"),
+            service_id: String::from("c"),
+            staff_id: String::from("d"),
+            day: 4,
+            month: 3,
+            year: 2025,
+            time: time!(9:30),
+        };
+
+        assert_eq!(slot.to_string(), "a.b.c.d.20250304093000");
+        assert_eq!(slot.to_string().parse::<SlotId>().unwrap(), slot);
+    }
+
+    #[test]
+    fn rejects_malformed_ids() {
+        for value in [
+            "a.b.c.d",
+            "a.b.c.d.20250304093000.e",
+            "a.b.c.d.202503040930",
+            "a.b.c.d.2025030409300x",
+            "a.b.c.d.20250304250000",
+        ] {
+            assert!(value.parse::<SlotId>().is_err(), "{value}");
+        }
+    }
+}
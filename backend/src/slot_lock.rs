@@ -0,0 +1,445 @@
+use std::{collections::HashMap, sync::Arc};
+
+use futures_util::future::BoxFuture;
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+use webby_global_common::uuid::UuidType;
+
+use crate::{config::Config, is_lock_expired, metrics, Result};
+
+/// A slot being booked: its website, schedule id, day, month, year & start time (`HH:MM:SS`).
+pub type LockKey = (UuidType, String, u8, u8, usize, String);
+
+/// A client's hold on one of a slot's places.
+#[derive(Debug, Clone)]
+pub struct HeldLock {
+    /// Client which locked the slot.
+    pub client_key: String,
+    pub locked_at: OffsetDateTime,
+}
+
+/// Where slot locks are kept while their forms are being submitted.
+///
+/// A slot can be held by as many clients as it has places left, eg. a schedule with two chairs
+/// or a group service. Holds stop counting once [`is_lock_expired`].
+pub trait SlotLock: Send + Sync {
+    /// Holds a place of the slot for `client_key` unless `capacity` unexpired holds already
+    /// take every place. A client already holding the slot keeps its hold.
+    /// Returns the client's hold if it has one.
+    fn try_acquire<'a>(
+        &'a self,
+        key: &'a LockKey,
+        client_key: &'a str,
+        capacity: usize,
+    ) -> BoxFuture<'a, Result<Option<HeldLock>>>;
+
+    /// Removes every hold on the slot, returning them.
+    fn release<'a>(&'a self, key: &'a LockKey) -> BoxFuture<'a, Result<Vec<HeldLock>>>;
+
+    /// Removes the hold of `client_key`, in a single step so another client's hold is never
+    /// removed. Returns the hold if there was one, expired or not.
+    fn release_if_owner<'a>(
+        &'a self,
+        key: &'a LockKey,
+        client_key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<HeldLock>>>;
+
+    /// The slot's current holds. May include expired ones which haven't been cleared yet.
+    fn holds<'a>(&'a self, key: &'a LockKey) -> BoxFuture<'a, Result<Vec<HeldLock>>>;
+}
+
+/// The lock backend picked by the configuration. Redis when `BOOKING_LOCK_REDIS_URL` is set.
+pub fn from_config(config: &Config) -> Result<Arc<dyn SlotLock>> {
+    Ok(match &config.lock_redis_url {
+        Some(url) => Arc::new(RedisSlotLock::new(url, config.lock_ttl)?),
+        None => Arc::new(MemorySlotLock::new(config.lock_ttl)),
+    })
+}
+
+/// Locks kept in memory. Only covers a single instance.
+pub struct MemorySlotLock {
+    locks: Mutex<HashMap<LockKey, Vec<HeldLock>>>,
+    ttl: std::time::Duration,
+}
+
+impl MemorySlotLock {
+    pub fn new(ttl: std::time::Duration) -> Self {
+        Self {
+            locks: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+}
+
+impl SlotLock for MemorySlotLock {
+    fn try_acquire<'a>(
+        &'a self,
+        key: &'a LockKey,
+        client_key: &'a str,
+        capacity: usize,
+    ) -> BoxFuture<'a, Result<Option<HeldLock>>> {
+        Box::pin(async move {
+            let mut locks = self.locks.lock().await;
+
+            let mut expired = 0;
+
+            // Expired holds no longer take a place.
+            locks.retain(|_, holds| {
+                let held = holds.len();
+
+                holds.retain(|lock| !is_lock_expired(lock.locked_at, self.ttl));
+                expired += held - holds.len();
+
+                !holds.is_empty()
+            });
+
+            metrics::add(&metrics::LOCKS_EXPIRED, expired as u64);
+
+            let holds = locks.entry(key.clone()).or_default();
+
+            if let Some(lock) = holds.iter().find(|lock| lock.client_key == client_key) {
+                return Ok(Some(lock.clone()));
+            }
+
+            if holds.len() >= capacity {
+                return Ok(None);
+            }
+
+            let lock = HeldLock {
+                client_key: client_key.to_string(),
+                locked_at: OffsetDateTime::now_utc(),
+            };
+
+            holds.push(lock.clone());
+
+            Ok(Some(lock))
+        })
+    }
+
+    fn release<'a>(&'a self, key: &'a LockKey) -> BoxFuture<'a, Result<Vec<HeldLock>>> {
+        Box::pin(async move { Ok(self.locks.lock().await.remove(key).unwrap_or_default()) })
+    }
+
+    fn release_if_owner<'a>(
+        &'a self,
+        key: &'a LockKey,
+        client_key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<HeldLock>>> {
+        Box::pin(async move {
+            let mut locks = self.locks.lock().await;
+
+            let Some(holds) = locks.get_mut(key) else {
+                return Ok(None);
+            };
+
+            let lock = holds
+                .iter()
+                .position(|lock| lock.client_key == client_key)
+                .map(|index| holds.remove(index));
+
+            if holds.is_empty() {
+                locks.remove(key);
+            }
+
+            Ok(lock)
+        })
+    }
+
+    fn holds<'a>(&'a self, key: &'a LockKey) -> BoxFuture<'a, Result<Vec<HeldLock>>> {
+        Box::pin(async move {
+            Ok(self
+                .locks
+                .lock()
+                .await
+                .get(key)
+                .cloned()
+                .unwrap_or_default())
+        })
+    }
+}
+
+/// Locks kept in Redis so they're shared between replicas.
+///
+/// Each slot is a sorted set of the clients holding it, scored by when they locked it in unix
+/// millis. Expired holds are dropped on the next lock attempt and the whole set expires with
+/// the lock TTL.
+pub struct RedisSlotLock {
+    client: redis::Client,
+    ttl: std::time::Duration,
+}
+
+impl RedisSlotLock {
+    pub fn new(url: &str, ttl: std::time::Duration) -> Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(url)?,
+            ttl,
+        })
+    }
+
+    fn key_name((uuid, schedule_id, day, month, year, time): &LockKey) -> String {
+        format!("booking:lock:{uuid:?}:{schedule_id}:{year:04}-{month:02}-{day:02}T{time}")
+    }
+
+    fn parse_hold(client_key: String, locked_at: &str) -> Result<HeldLock> {
+        let millis: i128 = locked_at.parse()?;
+
+        Ok(HeldLock {
+            client_key,
+            locked_at: OffsetDateTime::from_unix_timestamp_nanos(millis * 1_000_000)?,
+        })
+    }
+
+    /// Parses the `[member, score, ..]` pairs of a `WITHSCORES` reply.
+    fn parse_holds(reply: Vec<String>) -> Result<Vec<HeldLock>> {
+        reply
+            .chunks(2)
+            .map(|pair| match pair {
+                [client_key, locked_at] => Self::parse_hold(client_key.clone(), locked_at),
+                _ => Err(eyre::eyre!("Malformed slot lock reply"))?,
+            })
+            .collect()
+    }
+}
+
+impl SlotLock for RedisSlotLock {
+    fn try_acquire<'a>(
+        &'a self,
+        key: &'a LockKey,
+        client_key: &'a str,
+        capacity: usize,
+    ) -> BoxFuture<'a, Result<Option<HeldLock>>> {
+        Box::pin(async move {
+            let mut conn = self.client.get_multiplexed_async_connection().await?;
+
+            let now = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+
+            // Drops the expired holds, then adds the client's if it has none and a place is left.
+            // Replies with the number of expired holds & when the client's hold was locked,
+            // empty when the slot is full.
+            let (expired, locked_at): (u64, String) = redis::Script::new(
+                r"
+                local expired = redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', tonumber(ARGV[2]) - tonumber(ARGV[3]))
+                local held = redis.call('ZSCORE', KEYS[1], ARGV[1])
+                if held then
+                    return { expired, held }
+                end
+                if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[4]) then
+                    return { expired, '' }
+                end
+                redis.call('ZADD', KEYS[1], ARGV[2], ARGV[1])
+                redis.call('PEXPIRE', KEYS[1], ARGV[3])
+                return { expired, ARGV[2] }
+                ",
+            )
+            .key(Self::key_name(key))
+            .arg(client_key)
+            .arg(now.to_string())
+            .arg(self.ttl.as_millis() as u64)
+            .arg(capacity)
+            .invoke_async(&mut conn)
+            .await?;
+
+            metrics::add(&metrics::LOCKS_EXPIRED, expired);
+
+            Some(locked_at)
+                .filter(|v| !v.is_empty())
+                .map(|v| Self::parse_hold(client_key.to_string(), &v))
+                .transpose()
+        })
+    }
+
+    fn release<'a>(&'a self, key: &'a LockKey) -> BoxFuture<'a, Result<Vec<HeldLock>>> {
+        Box::pin(async move {
+            let mut conn = self.client.get_multiplexed_async_connection().await?;
+
+            let reply: Vec<String> = redis::Script::new(
+                r"
+                local holds = redis.call('ZRANGE', KEYS[1], 0, -1, 'WITHSCORES')
+                redis.call('DEL', KEYS[1])
+                return holds
+                ",
+            )
+            .key(Self::key_name(key))
+            .invoke_async(&mut conn)
+            .await?;
+
+            Self::parse_holds(reply)
+        })
+    }
+
+    fn release_if_owner<'a>(
+        &'a self,
+        key: &'a LockKey,
+        client_key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<HeldLock>>> {
+        Box::pin(async move {
+            let mut conn = self.client.get_multiplexed_async_connection().await?;
+
+            let locked_at: Option<String> = redis::Script::new(
+                r"
+                local held = redis.call('ZSCORE', KEYS[1], ARGV[1])
+                if held then
+                    redis.call('ZREM', KEYS[1], ARGV[1])
+                end
+                return held
+                ",
+            )
+            .key(Self::key_name(key))
+            .arg(client_key)
+            .invoke_async(&mut conn)
+            .await?;
+
+            locked_at
+                .map(|v| Self::parse_hold(client_key.to_string(), &v))
+                .transpose()
+        })
+    }
+
+    fn holds<'a>(&'a self, key: &'a LockKey) -> BoxFuture<'a, Result<Vec<HeldLock>>> {
+        Box::pin(async move {
+            let mut conn = self.client.get_multiplexed_async_connection().await?;
+
+            let reply: Vec<String> = redis::cmd("ZRANGE")
+                .arg(Self::key_name(key))
+                .arg(0)
+                .arg(-1)
+                .arg("WITHSCORES")
+                .query_async(&mut conn)
+                .await?;
+
+            Self::parse_holds(reply)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn website(uuid: &str) -> UuidType {
+        serde_json::from_value(serde_json::json!(uuid)).unwrap()
+    }
+
+    fn key() -> LockKey {
+        (
+            website("01938f4f-f50c-7203-9f89-b367e9d49efb"),
+            String::from("schedule"),
+            14,
+            3,
+            2025,
+            String::from("09:00:00"),
+        )
+    }
+
+    #[tokio::test]
+    async fn a_full_slot_cant_be_acquired() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+
+        assert!(locks.try_acquire(&key(), "a", 1).await.unwrap().is_some());
+        assert!(locks.try_acquire(&key(), "b", 1).await.unwrap().is_none());
+
+        let holds = locks.holds(&key()).await.unwrap();
+
+        assert_eq!(holds.len(), 1);
+        assert_eq!(holds[0].client_key, "a");
+    }
+
+    #[tokio::test]
+    async fn a_slot_is_held_up_to_its_capacity() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+
+        assert!(locks.try_acquire(&key(), "a", 2).await.unwrap().is_some());
+        assert!(locks.try_acquire(&key(), "b", 2).await.unwrap().is_some());
+        assert!(locks.try_acquire(&key(), "c", 2).await.unwrap().is_none());
+
+        // The same client doesn't take a second place.
+        assert!(locks.try_acquire(&key(), "a", 2).await.unwrap().is_some());
+        assert_eq!(locks.holds(&key()).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn other_start_times_are_held_separately() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+        let mut later = key();
+
+        later.5 = String::from("09:30:00");
+
+        assert!(locks.try_acquire(&key(), "a", 1).await.unwrap().is_some());
+        assert!(locks.try_acquire(&later, "b", 1).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn other_websites_are_held_separately() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+        let mut other = key();
+
+        other.0 = website("01938f4f-f50c-7203-9f89-000000000000");
+
+        assert!(locks.try_acquire(&key(), "a", 1).await.unwrap().is_some());
+        assert!(locks.try_acquire(&other, "b", 1).await.unwrap().is_some());
+        assert!(locks.holds(&other).await.unwrap().len() == 1);
+        assert!(locks.release(&other).await.unwrap().len() == 1);
+        assert_eq!(locks.holds(&key()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_released_slot_can_be_acquired() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+
+        locks.try_acquire(&key(), "a", 1).await.unwrap();
+
+        let released = locks.release(&key()).await.unwrap();
+
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].client_key, "a");
+        assert!(locks.holds(&key()).await.unwrap().is_empty());
+        assert!(locks.try_acquire(&key(), "b", 1).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn only_the_owner_releases_its_hold() {
+        let locks = MemorySlotLock::new(Duration::from_secs(60));
+
+        locks.try_acquire(&key(), "a", 1).await.unwrap();
+
+        assert!(locks.release_if_owner(&key(), "b").await.unwrap().is_none());
+        assert_eq!(locks.holds(&key()).await.unwrap().len(), 1);
+
+        let own = locks.release_if_owner(&key(), "a").await.unwrap().unwrap();
+
+        assert_eq!(own.client_key, "a");
+        assert!(locks.holds(&key()).await.unwrap().is_empty());
+        assert!(locks.release_if_owner(&key(), "a").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn a_retry_keeps_the_original_expiry() {
+        let ttl = Duration::from_secs(60);
+        let locks = MemorySlotLock::new(ttl);
+
+        let first = locks.try_acquire(&key(), "a", 1).await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let retried = locks.try_acquire(&key(), "a", 1).await.unwrap().unwrap();
+
+        // The returned `lockExpiresAt` comes from the hold, so retrying doesn't extend it.
+        assert_eq!(
+            crate::lock_expires_at(retried.locked_at, ttl),
+            crate::lock_expires_at(first.locked_at, ttl)
+        );
+    }
+
+    #[tokio::test]
+    async fn an_expired_hold_is_reclaimed() {
+        let locks = MemorySlotLock::new(Duration::ZERO);
+
+        locks.try_acquire(&key(), "a", 1).await.unwrap();
+
+        let expired = metrics::LOCKS_EXPIRED.load(std::sync::atomic::Ordering::Relaxed);
+        let held = locks.try_acquire(&key(), "b", 1).await.unwrap().unwrap();
+
+        assert_eq!(held.client_key, "b");
+        assert!(metrics::LOCKS_EXPIRED.load(std::sync::atomic::Ordering::Relaxed) > expired);
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+
+use webby_addon_common::{
+    request::{import_data_row, update_cms_row_by_id},
+    JsonResponse, WrappingResponse,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use webby_global_common::{
+    uuid::{CollectionName, UuidType},
+    value::SimpleValue,
+};
+use time::{macros::format_description, Date, Duration, OffsetDateTime, Time};
+
+use crate::{
+    recurrence::{parse_weekday, Frequency, RecurrenceRule, RecurrenceRules, WEEK_DAYS},
+    retry, validate_time_zone, AppState, BreakWindow, Error, Result, RECURRENCE_TYPE_INSTANCE,
+    RECURRENCE_TYPE_RECURRING, STAFF_SCHEDULE_TYPE_WORKING_HOURS,
+};
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaffScheduleJson {
+    name: String,
+    staff: String,
+    schedule: String,
+
+    start_day: String,
+    /// Last day the schedule runs on. Runs forever if not set.
+    end_day: Option<String>,
+    start: String,
+    end: String,
+    /// Allows `end` to be at or before `start`. The schedule then runs into the next day.
+    #[serde(default)]
+    overnight: bool,
+
+    /// Windows within the day in which no slots are offered.
+    #[serde(default)]
+    breaks: Vec<BreakWindow>,
+    /// One-off days the schedule also runs on (YYYY-MM-DD), eg. a pop-up event.
+    /// Added to the days of the recurrence rule.
+    #[serde(default)]
+    ad_hoc_dates: Vec<String>,
+
+    time_zone: String,
+    recurrence_rule: serde_json::Value,
+    /// `RECURRING` (the default) or `INSTANCE` for a schedule which only runs on `startDay`.
+    recurrence_type: Option<String>,
+    #[serde(rename = "type")]
+    type_of: Option<String>,
+}
+
+impl StaffScheduleJson {
+    /// Validates the schedule and converts it into the CMS row fields.
+    ///
+    /// Every error is a problem with the inputs, so it's reported as a validation error.
+    fn into_fields(self) -> Result<HashMap<String, SimpleValue>> {
+        self.validated_fields().map_err(Error::into_validation)
+    }
+
+    fn validated_fields(self) -> Result<HashMap<String, SimpleValue>> {
+        let date_format = format_description!("[year]-[month]-[day]");
+        let time_format = format_description!("[hour]:[minute]:[second]");
+
+        let start_day = Date::parse(&self.start_day, &date_format)?;
+        let end_day = self
+            .end_day
+            .as_deref()
+            .map(|v| Date::parse(v, &date_format))
+            .transpose()?;
+
+        if let Some(end_day) = end_day.filter(|end_day| *end_day < start_day) {
+            return Err(eyre::eyre!(
+                "End day {end_day} must not be before start day {start_day}"
+            ))?;
+        }
+
+        let start = Time::parse(&self.start, &time_format)?;
+        let end = Time::parse(&self.end, &time_format)?;
+
+        if !self.overnight && start >= end {
+            return Err(eyre::eyre!(
+                "Start time {} must be before end time {} unless the schedule is overnight",
+                self.start,
+                self.end
+            ))?;
+        }
+
+        if self.overnight && start < end {
+            return Err(eyre::eyre!(
+                "An overnight schedule has to end at or before its start time {}, not {}",
+                self.start,
+                self.end
+            ))?;
+        }
+
+        validate_time_zone(&self.time_zone)?;
+
+        if let Some(recurrence_type) = self
+            .recurrence_type
+            .as_deref()
+            .filter(|v| *v != RECURRENCE_TYPE_INSTANCE && *v != RECURRENCE_TYPE_RECURRING)
+        {
+            return Err(eyre::eyre!(
+                "Invalid recurrenceType {recurrence_type}. Expected {RECURRENCE_TYPE_RECURRING} or {RECURRENCE_TYPE_INSTANCE}"
+            ))?;
+        }
+
+        for window in &self.breaks {
+            window.parse()?;
+        }
+
+        let mut ad_hoc_dates = self
+            .ad_hoc_dates
+            .iter()
+            .map(|v| Date::parse(v, &date_format))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        ad_hoc_dates.sort();
+        ad_hoc_dates.dedup();
+
+        let rec_rules: RecurrenceRules = serde_json::from_value(self.recurrence_rule.clone())
+            .map_err(|e| eyre::eyre!("Malformed recurrenceRule: {e}"))?;
+
+        if rec_rules.rules().is_empty() {
+            return Err(eyre::eyre!("Invalid recurrenceRule: no rules given"))?;
+        }
+
+        for rule in rec_rules.rules() {
+            validate_recurrence_rule(rule)?;
+        }
+
+        let mut fields = HashMap::from([
+            (String::from("name"), self.name.into()),
+            (String::from("staff"), self.staff.into()),
+            (String::from("schedule"), self.schedule.into()),
+            (String::from("startDay"), start_day.into()),
+            (String::from("start"), start.into()),
+            (String::from("end"), end.into()),
+            (
+                String::from("overnight"),
+                serde_json::json!(self.overnight).into(),
+            ),
+            (
+                String::from("breaks"),
+                serde_json::to_value(&self.breaks)?.into(),
+            ),
+            (
+                String::from("adHocDates"),
+                serde_json::to_value(&ad_hoc_dates)?.into(),
+            ),
+            (String::from("timeZone"), self.time_zone.into()),
+            (String::from("recurrenceRule"), self.recurrence_rule.into()),
+            (
+                String::from("recurrenceType"),
+                self.recurrence_type
+                    .unwrap_or_else(|| String::from(RECURRENCE_TYPE_RECURRING))
+                    .into(),
+            ),
+            (
+                String::from("type"),
+                self.type_of
+                    .unwrap_or_else(|| String::from(STAFF_SCHEDULE_TYPE_WORKING_HOURS))
+                    .into(),
+            ),
+        ]);
+
+        if let Some(end_day) = end_day {
+            fields.insert(String::from("endDay"), end_day.into());
+        }
+
+        Ok(fields)
+    }
+}
+
+fn validate_recurrence_rule(rule: &RecurrenceRule) -> Result<()> {
+    rule.frequency.parse::<Frequency>()?;
+
+    if rule.interval == 0 {
+        return Err(eyre::eyre!(
+            "Invalid recurrenceRule: interval must be at least 1"
+        ))?;
+    }
+
+    if rule.days.is_empty() {
+        return Err(eyre::eyre!("Invalid recurrenceRule: days cannot be empty"))?;
+    }
+
+    if let Some(day) = rule
+        .days
+        .iter()
+        .find(|day| !WEEK_DAYS.contains(&day.as_str()))
+    {
+        return Err(eyre::eyre!(
+            "Invalid recurrenceRule: unknown day {day}. Expected one of {}",
+            WEEK_DAYS.join(", ")
+        ))?;
+    }
+
+    Ok(())
+}
+
+pub async fn post_staff_schedule(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Json(body): Json<StaffScheduleJson>,
+) -> Result<()> {
+    let mut fields = body.into_fields()?;
+
+    // Only set on create so editing a schedule doesn't resume it.
+    fields.insert(String::from("active"), serde_json::json!(true).into());
+
+    retry::write(&state.config, || {
+        import_data_row(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(())
+}
+
+pub async fn put_staff_schedule(
+    State(state): State<AppState>,
+    Path((uuid, id)): Path<(UuidType, String)>,
+    Json(body): Json<StaffScheduleJson>,
+) -> Result<()> {
+    let fields = body.into_fields()?;
+
+    retry::write(&state.config, || {
+        update_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &id,
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct StaffScheduleActiveJson {
+    active: bool,
+}
+
+/// Pauses or resumes a staff schedule, eg. while the staff member is on leave.
+///
+/// Paused schedules are kept but offer no available days or hours.
+pub async fn put_staff_schedule_active(
+    State(state): State<AppState>,
+    Path((uuid, id)): Path<(UuidType, String)>,
+    Json(StaffScheduleActiveJson { active }): Json<StaffScheduleActiveJson>,
+) -> Result<()> {
+    let fields = HashMap::from([(String::from("active"), serde_json::json!(active).into())]);
+
+    retry::write(&state.config, || {
+        update_cms_row_by_id(
+            uuid,
+            CollectionName {
+                id: String::from("staffSchedule"),
+                ns: Some(String::from("@booking")),
+            },
+            &id,
+            fields.clone(),
+        )
+    })
+    .await?;
+
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct WeeklyHoursJson {
+    start: String,
+    end: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyStaffScheduleJson {
+    /// Defaults to `business`, the same as the schedules created on install.
+    name: Option<String>,
+    staff: String,
+    schedule: String,
+    time_zone: String,
+    /// First day the schedules run on (YYYY-MM-DD). Defaults to today in `timeZone`.
+    start_day: Option<String>,
+    /// Working hours by day of the week, eg. `MONDAY`. Days without hours aren't created.
+    days: HashMap<String, WeeklyHoursJson>,
+}
+
+/// Creates a weekly recurring staff schedule for each day of the week with working hours.
+///
+/// Every day is validated before any row is created.
+pub async fn post_staff_schedule_weekly(
+    State(state): State<AppState>,
+    Path(uuid): Path<UuidType>,
+    Json(body): Json<WeeklyStaffScheduleJson>,
+) -> Result<JsonResponse<serde_json::Value>> {
+    let local_offset = validate_time_zone(&body.time_zone).map_err(Error::into_validation)?;
+
+    let start_day = match &body.start_day {
+        Some(v) => Date::parse(v, &format_description!("[year]-[month]-[day]"))
+            .map_err(|e| Error::from(e).into_validation())?,
+        None => OffsetDateTime::now_utc().to_offset(local_offset).date(),
+    };
+
+    let rows = weekly_rows(body, start_day)?;
+
+    for (_, fields) in &rows {
+        retry::write(&state.config, || {
+            import_data_row(
+                uuid,
+                CollectionName {
+                    id: String::from("staffSchedule"),
+                    ns: Some(String::from("@booking")),
+                },
+                fields.clone(),
+            )
+        })
+        .await?;
+    }
+
+    state.availability_cache.invalidate(uuid).await;
+
+    Ok(Json(WrappingResponse::okay(serde_json::json!({
+        "created": rows.len(),
+        "days": rows.iter().map(|(day, _)| *day).collect::<Vec<_>>(),
+    }))))
+}
+
+/// The staff schedule row of each day of the week with working hours, from Monday on.
+fn weekly_rows(
+    body: WeeklyStaffScheduleJson,
+    start_day: Date,
+) -> Result<Vec<(&'static str, HashMap<String, SimpleValue>)>> {
+    let mut days = body
+        .days
+        .into_iter()
+        .map(|(day, hours)| Ok((parse_weekday(&day)?, hours)))
+        .collect::<Result<Vec<_>>>()
+        .map_err(Error::into_validation)?;
+
+    days.sort_by_key(|(weekday, _)| weekday.number_days_from_monday());
+
+    if let Some(pair) = days.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(Error::Validation(vec![format!(
+            "Duplicate day {}",
+            pair[0].0
+        )]));
+    }
+
+    let mut rows = Vec::new();
+
+    for (weekday, hours) in days {
+        let day = WEEK_DAYS[weekday.number_days_from_monday() as usize];
+
+        // The first day on or after `start_day` which falls on this day of the week.
+        let days_until = (weekday.number_days_from_monday() + 7
+            - start_day.weekday().number_days_from_monday())
+            % 7;
+
+        let mut fields = StaffScheduleJson {
+            name: body
+                .name
+                .clone()
+                .unwrap_or_else(|| String::from("business")),
+            staff: body.staff.clone(),
+            schedule: body.schedule.clone(),
+            start_day: (start_day + Duration::days(days_until as i64)).to_string(),
+            end_day: None,
+            start: hours.start,
+            end: hours.end,
+            overnight: false,
+            breaks: Vec::new(),
+            ad_hoc_dates: Vec::new(),
+            time_zone: body.time_zone.clone(),
+            recurrence_rule: serde_json::json!({
+                "days": [day],
+                "frequency": "WEEKLY",
+                "interval": 1,
+            }),
+            recurrence_type: None,
+            type_of: None,
+        }
+        .into_fields()
+        .map_err(|e| Error::Validation(vec![format!("Invalid hours for {day}: {e}")]))?;
+
+        fields.insert(String::from("active"), serde_json::json!(true).into());
+
+        rows.push((day, fields));
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, time};
+
+    use super::*;
+
+    fn schedule(start: &str, end: &str, overnight: bool) -> StaffScheduleJson {
+        serde_json::from_value(serde_json::json!({
+            "name": "night",
+            "staff": "staff",
+            "schedule": "schedule",
+            "startDay": "2025-03-14",
+            "start": start,
+            "end": end,
+            "overnight": overnight,
+            "timeZone": "UTC",
+            "recurrenceRule": { "days": ["FRIDAY"], "frequency": "WEEKLY", "interval": 1 },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn stores_the_overnight_flag() {
+        let fields = schedule("22:00:00", "02:00:00", true)
+            .into_fields()
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&fields["overnight"]).unwrap(),
+            serde_json::json!(true)
+        );
+    }
+
+    #[test]
+    fn invalid_hours_are_validation_errors() {
+        for (start, end, overnight) in [
+            ("22:00:00", "02:00:00", false),
+            ("09:00:00", "17:00:00", true),
+            ("9am", "17:00:00", false),
+        ] {
+            assert!(matches!(
+                schedule(start, end, overnight).into_fields(),
+                Err(Error::Validation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn a_weekly_template_creates_a_row_for_each_day_with_hours() {
+        let body = serde_json::from_value(serde_json::json!({
+            "staff": "staff",
+            "schedule": "schedule",
+            "timeZone": "UTC",
+            "days": {
+                "WEDNESDAY": { "start": "10:00:00", "end": "14:00:00" },
+                "MONDAY": { "start": "09:00:00", "end": "17:00:00" },
+            },
+        }))
+        .unwrap();
+
+        // A Friday, so each day starts the following week.
+        let rows = weekly_rows(body, date!(2025 - 03 - 14)).unwrap();
+
+        let text =
+            |fields: &HashMap<String, SimpleValue>, name: &str| fields[name].any_as_text().unwrap();
+        // Stored the same way `into_fields` stores them.
+        let stored = |value: SimpleValue| value.any_as_text().unwrap();
+
+        assert_eq!(
+            rows.iter()
+                .map(|(day, fields)| (
+                    *day,
+                    text(fields, "startDay"),
+                    text(fields, "start"),
+                    text(fields, "end")
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "MONDAY",
+                    stored(date!(2025 - 03 - 17).into()),
+                    stored(time!(9:00).into()),
+                    stored(time!(17:00).into())
+                ),
+                (
+                    "WEDNESDAY",
+                    stored(date!(2025 - 03 - 19).into()),
+                    stored(time!(10:00).into()),
+                    stored(time!(14:00).into())
+                ),
+            ]
+        );
+
+        for (day, fields) in &rows {
+            assert_eq!(
+                serde_json::to_value(&fields["recurrenceRule"]).unwrap(),
+                serde_json::json!({ "days": [day], "frequency": "WEEKLY", "interval": 1 })
+            );
+            assert_eq!(text(fields, "recurrenceType"), RECURRENCE_TYPE_RECURRING);
+        }
+    }
+
+    #[test]
+    fn a_weekly_template_with_invalid_hours_creates_nothing() {
+        let body = serde_json::from_value(serde_json::json!({
+            "staff": "staff",
+            "schedule": "schedule",
+            "timeZone": "UTC",
+            "days": {
+                "MONDAY": { "start": "09:00:00", "end": "17:00:00" },
+                "TUESDAY": { "start": "17:00:00", "end": "09:00:00" },
+            },
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            weekly_rows(body, date!(2025 - 03 - 14)),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn an_unknown_time_zone_is_rejected_at_creation() {
+        let mut schedule = schedule("09:00:00", "17:00:00", false);
+
+        schedule.time_zone = String::from("Europe/Zagrebb");
+
+        match schedule.into_fields() {
+            Err(Error::Validation(errors)) => {
+                assert!(errors[0].contains("Europe/Zagrebb"), "{errors:?}");
+            }
+            _ => panic!("Expected a validation error"),
+        }
+    }
+
+    #[test]
+    fn malformed_recurrence_rules_are_validation_errors() {
+        for rule in [
+            serde_json::json!("WEEKLY"),
+            serde_json::json!({ "days": ["FRIDAY"], "frequency": "HOURLY", "interval": 1 }),
+            serde_json::json!({ "days": ["FRIDAY"], "frequency": "WEEKLY", "interval": 0 }),
+            serde_json::json!({ "days": [], "frequency": "WEEKLY", "interval": 1 }),
+            serde_json::json!({ "days": ["FUNDAY"], "frequency": "WEEKLY", "interval": 1 }),
+        ] {
+            let mut schedule = schedule("09:00:00", "17:00:00", false);
+
+            schedule.recurrence_rule = rule.clone();
+
+            assert!(
+                matches!(schedule.into_fields(), Err(Error::Validation(_))),
+                "{rule}"
+            );
+        }
+    }
+}
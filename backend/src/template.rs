@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use time::{macros::format_description, PrimitiveDateTime};
+
+use crate::{config::Config, Result};
+
+/// Subject of the email sent to the owner when a booking is made.
+pub const EMAIL_SUBJECT_TEMPLATE: &str = "You received a new booking for {{bookingDateTime}}!";
+
+/// Body of the email sent to the owner when a booking is made.
+/// `{{SUBMISSION_LINK}}` is filled in by the platform when the form is submitted.
+pub const EMAIL_BODY_TEMPLATE: &str = "{{SUBMISSION_LINK}}";
+
+/// Values for the placeholders of the booking email.
+pub fn email_values(
+    booking_date_time: String,
+    service_name: String,
+    staff_name: String,
+    submission_link: String,
+) -> HashMap<&'static str, String> {
+    HashMap::from([
+        ("bookingDateTime", booking_date_time),
+        ("serviceName", service_name),
+        ("staffName", staff_name),
+        ("submissionLink", submission_link),
+    ])
+}
+
+/// The link to a booking's form submission, from [`Config::submission_link_template`].
+pub fn submission_link(config: &Config, schema_data_uuid: &str, contact_uuid: &str) -> String {
+    render(
+        &config.submission_link_template,
+        &HashMap::from([
+            ("SCHEMA_DATA_UUID", schema_data_uuid.to_string()),
+            ("CONTACT_UUID", contact_uuid.to_string()),
+        ]),
+    )
+}
+
+/// The URL of a stored file, eg. a service's image. Absolute URLs are returned as is.
+pub fn file_url(config: &Config, file: &str) -> String {
+    if file.starts_with("http://") || file.starts_with("https://") {
+        return file.to_string();
+    }
+
+    render(
+        &config.file_url_template,
+        &HashMap::from([("FILE", file.to_string())]),
+    )
+}
+
+/// Replaces each `{{name}}` placeholder with its value.
+/// Unknown placeholders are left untouched for the platform to resolve.
+pub fn render(template: &str, values: &HashMap<&str, String>) -> String {
+    let mut output = template.to_string();
+
+    for (name, value) in values {
+        output = output.replace(&format!("{{{{{name}}}}}"), value);
+    }
+
+    output
+}
+
+/// Formats a local booking time for people to read.
+/// eg. `Monday, December 2, 2024 at 10:00 AM (America/Los_Angeles)`
+pub fn format_booking_date_time(local: PrimitiveDateTime, time_zone: &str) -> Result<String> {
+    let formatted = local.format(&format_description!(
+        "[weekday], [month repr:long] [day padding:none], [year] at [hour repr:12 padding:none]:[minute] [period]"
+    ))?;
+
+    Ok(format!("{formatted} ({time_zone})"))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn the_subject_shows_the_local_time_and_its_time_zone() {
+        let values = email_values(
+            format_booking_date_time(datetime!(2024-12-02 10:00), "America/Los_Angeles").unwrap(),
+            String::from("Haircut"),
+            String::from("Sam"),
+            String::new(),
+        );
+
+        assert_eq!(
+            render(EMAIL_SUBJECT_TEMPLATE, &values),
+            "You received a new booking for Monday, December 2, 2024 at 10:00 AM (America/Los_Angeles)!"
+        );
+    }
+
+    #[test]
+    fn the_submission_link_is_filled_in_from_the_config() {
+        let default = Config::from_lookup(|_| None).unwrap();
+
+        assert_eq!(
+            submission_link(&default, "data", "contact"),
+            "/forms/submissions/data"
+        );
+
+        let config = Config::from_lookup(|name| {
+            (name == "BOOKING_SUBMISSION_LINK_TEMPLATE")
+                .then(|| String::from("https://example.com/{{CONTACT_UUID}}/{{SCHEMA_DATA_UUID}}"))
+        })
+        .unwrap();
+
+        assert_eq!(
+            submission_link(&config, "data", "contact"),
+            "https://example.com/contact/data"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_left_for_the_platform() {
+        let values = HashMap::from([("serviceName", String::from("Haircut"))]);
+
+        assert_eq!(
+            render("{{serviceName}} {{SUBMISSION_LINK}}", &values),
+            "Haircut {{SUBMISSION_LINK}}"
+        );
+    }
+}
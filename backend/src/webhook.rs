@@ -0,0 +1,151 @@
+use std::{sync::LazyLock, time::Duration};
+
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
+
+use crate::{config::Config, Result};
+
+/// Max attempts of a webhook delivery, including the first.
+const ATTEMPTS: u32 = 5;
+/// Wait before the first retry. Doubles with each further attempt.
+const BACKOFF: Duration = Duration::from_secs(1);
+
+/// Booking moved to `cancelled`.
+pub const EVENT_BOOKING_CANCELLED: &str = "booking.cancelled";
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("failed to build the webhook client")
+});
+
+/// Posts the event to the configured webhook in the background, retrying failed deliveries.
+///
+/// Does nothing unless [`Config::webhook_url`] is set.
+pub fn send(config: &Config, event: &'static str, data: serde_json::Value) {
+    let Some(url) = config.webhook_url.clone() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let body = match OffsetDateTime::now_utc().format(&Iso8601::DEFAULT) {
+            Ok(sent_at) => serde_json::json!({
+                "event": event,
+                "sentAt": sent_at,
+                "data": data,
+            }),
+            Err(e) => {
+                warn!("Unable to send {event} webhook: {e}");
+                return;
+            }
+        };
+
+        let mut backoff = BACKOFF;
+
+        for attempt in 1..=ATTEMPTS {
+            match deliver(&url, &body).await {
+                Ok(()) => return,
+                Err(e) if attempt < ATTEMPTS => {
+                    warn!("Webhook {event} failed (attempt {attempt}/{ATTEMPTS}): {e}");
+
+                    tokio::time::sleep(backoff).await;
+
+                    backoff *= 2;
+                }
+                Err(e) => warn!("Giving up on webhook {event} after {ATTEMPTS} attempts: {e}"),
+            }
+        }
+    });
+}
+
+async fn deliver(url: &str, body: &serde_json::Value) -> Result<()> {
+    CLIENT
+        .post(url)
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{http::StatusCode, routing::post, Json, Router};
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// A local webhook receiver failing the first `failures` deliveries, and every body it got.
+    pub(crate) async fn receiver(
+        failures: usize,
+    ) -> (Config, mpsc::UnboundedReceiver<serde_json::Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let failures = Arc::new(AtomicUsize::new(failures));
+
+        let app = Router::new().route(
+            "/",
+            post(move |Json(body): Json<serde_json::Value>| {
+                tx.send(body).unwrap();
+
+                let status = match failures
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| v.checked_sub(1))
+                {
+                    Ok(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                    Err(_) => StatusCode::OK,
+                };
+
+                async move { status }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let config =
+            Config::from_lookup(|name| (name == "BOOKING_WEBHOOK_URL").then(|| url.clone()))
+                .unwrap();
+
+        (config, rx)
+    }
+
+    /// The next delivery the receiver got.
+    pub(crate) async fn delivery(
+        rx: &mut mpsc::UnboundedReceiver<serde_json::Value>,
+    ) -> serde_json::Value {
+        tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("no webhook was delivered")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_failed_delivery_is_retried() {
+        let (config, mut rx) = receiver(1).await;
+
+        send(
+            &config,
+            EVENT_BOOKING_CANCELLED,
+            serde_json::json!({ "id": "booking" }),
+        );
+
+        let first = delivery(&mut rx).await;
+        let retried = delivery(&mut rx).await;
+
+        assert_eq!(first, retried);
+        assert_eq!(retried["event"], EVENT_BOOKING_CANCELLED);
+        assert_eq!(retried["data"], serde_json::json!({ "id": "booking" }));
+        assert!(retried["sentAt"].is_string());
+
+        // Delivered, so not sent again.
+        tokio::time::sleep(BACKOFF * 2).await;
+        assert!(rx.try_recv().is_err());
+    }
+}